@@ -0,0 +1,90 @@
+//! Serves a fake StarCraft web API for development and CI:
+//!
+//!     cargo run --features mock --bin mock_sc_api -- [--port N] [--fixtures DIR]
+//!         [--replay FILE] [--latency-ms N] [--fail-every N]
+//!
+//! On Unix the mock re-executes itself as `StarCraft.exe`, which is how the
+//! app recognizes the game under Wine, so process detection finds it too. On
+//! Windows, copy the binary to `StarCraft.exe` for the same effect.
+
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use cwal_app_lib::mock_sc_api::{MockConfig, MockScApi};
+
+fn main() {
+    #[cfg(unix)]
+    disguise_as_starcraft();
+
+    let config = match parse_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let mock = match MockScApi::start(config) {
+        Ok(mock) => mock,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "[mock-sc-api] pid {} serving http://localhost:{}",
+        std::process::id(),
+        mock.port()
+    );
+
+    loop {
+        std::thread::park();
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<MockConfig, String> {
+    let mut config = MockConfig::default();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for {flag}"))?;
+        match flag.as_str() {
+            "--port" => config.port = number(&flag, &value)?,
+            "--fixtures" => config.fixtures_dir = Some(PathBuf::from(value)),
+            "--replay" => config.replay = Some(PathBuf::from(value)),
+            "--latency-ms" => config.latency = Duration::from_millis(number(&flag, &value)?),
+            "--fail-every" => config.fail_every = number(&flag, &value)?,
+            _ => return Err(format!("Unknown argument: {flag}")),
+        }
+    }
+    Ok(config)
+}
+
+fn number<T: FromStr>(flag: &str, value: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("Invalid value for {flag}: {e}"))
+}
+
+/// Replaces this process with itself under the name `StarCraft.exe`.
+#[cfg(unix)]
+fn disguise_as_starcraft() {
+    use std::os::unix::process::CommandExt;
+
+    const STARCRAFT_EXE: &str = "StarCraft.exe";
+
+    let is_disguised = std::env::args()
+        .next()
+        .is_some_and(|arg0| arg0.rsplit('/').next() == Some(STARCRAFT_EXE));
+    if is_disguised {
+        return;
+    }
+    let Ok(exe) = std::env::current_exe() else {
+        return;
+    };
+    let e = std::process::Command::new(exe)
+        .arg0(STARCRAFT_EXE)
+        .args(std::env::args().skip(1))
+        .exec();
+    eprintln!("[mock-sc-api] Failed to re-execute as {STARCRAFT_EXE}: {e}");
+}
@@ -3,7 +3,7 @@ use std::{
     fs,
     hash::{DefaultHasher, Hash, Hasher},
     num::NonZeroUsize,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Mutex,
 };
 
@@ -25,6 +25,10 @@ impl ReplayCache {
         }
     }
 
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
     fn key(url: &str) -> String {
         // Basic key; consider hashing if URLs are long
         url.to_string()
@@ -0,0 +1,593 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+
+use crate::{
+    downloads::Downloader, filename_template::FilenameTemplate, http::DownloadProgress, persist,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// A job as submitted by the frontend.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewDownloadJob {
+    pub url: String,
+    pub destination_path: String,
+    pub filename: String,
+    #[serde(default)]
+    pub template: Option<FilenameTemplate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadJob {
+    pub id: u64,
+    pub url: String,
+    pub destination_path: String,
+    pub filename: String,
+    #[serde(default)]
+    pub template: Option<FilenameTemplate>,
+    pub status: JobStatus,
+    /// Final location on disk once completed.
+    pub path: Option<String>,
+    pub error: Option<String>,
+    pub received: u64,
+    pub total: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub enum DownloadQueueEvent {
+    JobUpdated(DownloadJob),
+    Progress(DownloadProgress),
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PersistedQueue {
+    next_id: u64,
+    jobs: Vec<DownloadJob>,
+}
+
+struct QueueState {
+    next_id: u64,
+    jobs: Vec<DownloadJob>,
+    max_concurrent: usize,
+    /// Tasks of running jobs by job id, each with the run it was spawned
+    /// for. A job paused and resumed gets a new run, so a task that was
+    /// aborted too late can tell it no longer owns the job.
+    running: HashMap<u64, (u64, JoinHandle<()>)>,
+    next_run: u64,
+}
+
+impl QueueState {
+    fn job_mut(&mut self, id: u64) -> Result<&mut DownloadJob, String> {
+        self.jobs
+            .iter_mut()
+            .find(|j| j.id == id)
+            .ok_or_else(|| format!("No download job with id {id}"))
+    }
+
+    fn owns(&self, id: u64, run: u64) -> bool {
+        self.running
+            .get(&id)
+            .is_some_and(|(current, _)| *current == run)
+    }
+}
+
+/// Bulk download queue with a concurrency cap. The job list is written to
+/// `store_path` on every status change so unfinished jobs survive restarts.
+pub struct DownloadQueue {
+    state: Mutex<QueueState>,
+    store_path: PathBuf,
+    downloader: Arc<Downloader>,
+    on_event: Box<dyn Fn(DownloadQueueEvent) + Send + Sync>,
+}
+
+impl DownloadQueue {
+    pub fn new(
+        store_path: PathBuf,
+        max_concurrent: usize,
+        downloader: Arc<Downloader>,
+        on_event: impl Fn(DownloadQueueEvent) + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        let mut persisted: PersistedQueue = persist::load_json(&store_path, "download-queue");
+
+        // Jobs interrupted by the previous shutdown are queued again and
+        // resume from their partial files.
+        for job in persisted.jobs.iter_mut() {
+            if job.status == JobStatus::Running {
+                job.status = JobStatus::Queued;
+                job.received = 0;
+            }
+        }
+
+        let queue = Arc::new(Self {
+            state: Mutex::new(QueueState {
+                next_id: persisted.next_id,
+                jobs: persisted.jobs,
+                max_concurrent: max_concurrent.max(1),
+                running: HashMap::new(),
+                next_run: 0,
+            }),
+            store_path,
+            downloader,
+            on_event: Box::new(on_event),
+        });
+        queue.schedule();
+        queue
+    }
+
+    pub fn jobs(&self) -> Vec<DownloadJob> {
+        self.state.lock().unwrap().jobs.clone()
+    }
+
+    pub fn enqueue(self: &Arc<Self>, new_jobs: Vec<NewDownloadJob>) -> Vec<DownloadJob> {
+        let added = {
+            let mut state = self.state.lock().unwrap();
+            let mut added = Vec::with_capacity(new_jobs.len());
+            for new_job in new_jobs {
+                let job = DownloadJob {
+                    id: state.next_id,
+                    url: new_job.url,
+                    destination_path: new_job.destination_path,
+                    filename: new_job.filename,
+                    template: new_job.template,
+                    status: JobStatus::Queued,
+                    path: None,
+                    error: None,
+                    received: 0,
+                    total: None,
+                };
+                state.next_id += 1;
+                state.jobs.push(job.clone());
+                added.push(job);
+            }
+            self.persist(&state);
+            added
+        };
+
+        for job in &added {
+            self.emit_job(job);
+        }
+        self.schedule();
+        added
+    }
+
+    pub fn set_max_concurrent(self: &Arc<Self>, max_concurrent: usize) {
+        self.state.lock().unwrap().max_concurrent = max_concurrent.max(1);
+        self.schedule();
+    }
+
+    /// Stops a queued or running job. A running download is aborted; its
+    /// partial file is kept, so resuming picks up where it stopped when the
+    /// server supports range requests.
+    pub fn pause(self: &Arc<Self>, id: u64) -> Result<(), String> {
+        self.transition(id, |job| match job.status {
+            JobStatus::Queued | JobStatus::Running => Ok(JobStatus::Paused),
+            s => Err(format!("Cannot pause a job that is {s:?}")),
+        })
+    }
+
+    pub fn resume(self: &Arc<Self>, id: u64) -> Result<(), String> {
+        self.transition(id, |job| match job.status {
+            JobStatus::Paused | JobStatus::Failed => Ok(JobStatus::Queued),
+            s => Err(format!("Cannot resume a job that is {s:?}")),
+        })
+    }
+
+    pub fn cancel(self: &Arc<Self>, id: u64) -> Result<(), String> {
+        self.transition(id, |job| match job.status {
+            JobStatus::Completed | JobStatus::Cancelled => {
+                Err(format!("Cannot cancel a job that is {:?}", job.status))
+            }
+            _ => Ok(JobStatus::Cancelled),
+        })
+    }
+
+    /// Drops completed and cancelled jobs from the list.
+    pub fn clear_finished(&self) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .jobs
+            .retain(|j| !matches!(j.status, JobStatus::Completed | JobStatus::Cancelled));
+        self.persist(&state);
+    }
+
+    fn transition(
+        self: &Arc<Self>,
+        id: u64,
+        next: impl FnOnce(&DownloadJob) -> Result<JobStatus, String>,
+    ) -> Result<(), String> {
+        let job = {
+            let mut state = self.state.lock().unwrap();
+            let job = state.job_mut(id)?;
+            let status = next(job)?;
+            job.status = status;
+            if status == JobStatus::Queued {
+                job.error = None;
+                job.received = 0;
+            }
+            let job = job.clone();
+
+            if let Some((_, handle)) = state.running.remove(&id) {
+                handle.abort();
+            }
+            self.persist(&state);
+            job
+        };
+
+        self.emit_job(&job);
+        self.schedule();
+        Ok(())
+    }
+
+    /// Starts queued jobs, oldest first, until the concurrency cap is reached.
+    fn schedule(self: &Arc<Self>) {
+        let started = {
+            let mut state = self.state.lock().unwrap();
+            let free = state.max_concurrent.saturating_sub(state.running.len());
+            let ids: Vec<u64> = state
+                .jobs
+                .iter()
+                .filter(|j| j.status == JobStatus::Queued)
+                .take(free)
+                .map(|j| j.id)
+                .collect();
+
+            let mut started = Vec::with_capacity(ids.len());
+            for id in ids {
+                let job = state.job_mut(id).unwrap();
+                job.status = JobStatus::Running;
+                let job = job.clone();
+
+                let run = state.next_run;
+                state.next_run += 1;
+                let queue = self.clone();
+                let task_job = job.clone();
+                let handle = tauri::async_runtime::spawn(async move {
+                    queue.run(task_job, run).await;
+                });
+                state.running.insert(id, (run, handle));
+                started.push(job);
+            }
+            if !started.is_empty() {
+                self.persist(&state);
+            }
+            started
+        };
+
+        for job in &started {
+            self.emit_job(job);
+        }
+    }
+
+    async fn run(self: Arc<Self>, job: DownloadJob, run: u64) {
+        let result = self
+            .downloader
+            .download_to_folder(
+                &job.url,
+                &job.destination_path,
+                &job.filename,
+                job.template.as_ref(),
+                |received, total| {
+                    if let Ok(mut state) = self.state.lock() {
+                        if !state.owns(job.id, run) {
+                            return;
+                        }
+                        if let Ok(j) = state.job_mut(job.id) {
+                            j.received = received;
+                            j.total = total;
+                        }
+                    }
+                    (self.on_event)(DownloadQueueEvent::Progress(DownloadProgress {
+                        url: job.url.clone(),
+                        received,
+                        total,
+                    }));
+                },
+            )
+            .await;
+
+        let finished = {
+            let mut state = self.state.lock().unwrap();
+            // Paused or cancelled, and maybe resumed since, while the last
+            // chunk was in flight or the lock was held.
+            if !state.owns(job.id, run) {
+                return;
+            }
+            state.running.remove(&job.id);
+            let Ok(j) = state.job_mut(job.id) else {
+                return;
+            };
+            match result {
+                Ok(path) => {
+                    j.status = JobStatus::Completed;
+                    j.path = Some(path.to_string_lossy().to_string());
+                }
+                Err(e) => {
+                    println!("[download-queue] Job {} failed: {e}", job.id);
+                    j.status = JobStatus::Failed;
+                    j.error = Some(e);
+                }
+            }
+            let j = j.clone();
+            self.persist(&state);
+            j
+        };
+
+        self.emit_job(&finished);
+        self.schedule();
+    }
+
+    fn emit_job(&self, job: &DownloadJob) {
+        (self.on_event)(DownloadQueueEvent::JobUpdated(job.clone()));
+    }
+
+    fn persist(&self, state: &QueueState) {
+        let persisted = PersistedQueue {
+            next_id: state.next_id,
+            jobs: state.jobs.clone(),
+        };
+        let result = serde_json::to_string_pretty(&persisted)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                if let Some(parent) = self.store_path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                persist::write_atomically(&self.store_path, content.as_bytes())
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            println!(
+                "[download-queue] Failed to persist queue to {}: {e}",
+                self.store_path.display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::ReplayCache,
+        mpq,
+        replay_library::ReplayLibrary,
+        test_dir::TestDir,
+        test_server::{http_client, TestResponse, TestServer},
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Condvar,
+        },
+        time::{Duration, Instant},
+    };
+
+    /// Serves maps, holding every response until `open` is called, and
+    /// tracks how many requests were in flight at once. Each map holds its
+    /// path, so downloads can be told apart.
+    struct GatedServer {
+        server: TestServer,
+        gate: Arc<(Mutex<bool>, Condvar)>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    impl GatedServer {
+        fn start() -> Self {
+            let gate = Arc::new((Mutex::new(false), Condvar::new()));
+            let in_flight = Arc::new(AtomicUsize::new(0));
+            let max_in_flight = Arc::new(AtomicUsize::new(0));
+            let (held, max) = (gate.clone(), max_in_flight.clone());
+            let server = TestServer::start(move |req| {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max.fetch_max(now, Ordering::SeqCst);
+                let (open, opened) = &*held;
+                let _open = opened
+                    .wait_while(open.lock().unwrap(), |open| !*open)
+                    .unwrap();
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                TestResponse::ok(map(&req.path))
+            });
+            Self {
+                server,
+                gate,
+                max_in_flight,
+            }
+        }
+
+        fn open(&self) {
+            let (open, opened) = &*self.gate;
+            *open.lock().unwrap() = true;
+            opened.notify_all();
+        }
+
+        fn job(&self, dir: &TestDir, name: &str) -> NewDownloadJob {
+            NewDownloadJob {
+                url: self.server.url(&format!("/{name}")),
+                destination_path: dir.join("maps").to_string_lossy().to_string(),
+                filename: name.to_string(),
+                template: None,
+            }
+        }
+    }
+
+    fn map(path: &str) -> Vec<u8> {
+        mpq::build_archive(&[("staredit\\scenario.chk", path.as_bytes())])
+    }
+
+    fn queue(dir: &TestDir, max_concurrent: usize) -> Arc<DownloadQueue> {
+        let downloader = Downloader::new(
+            http_client(),
+            Arc::new(ReplayCache::new(dir.join("cache"), 10)),
+            Arc::new(ReplayLibrary::open_in_memory().unwrap()),
+            dir.join("quarantine"),
+            dir.join("partial"),
+        );
+        DownloadQueue::new(
+            dir.join("queue.json"),
+            max_concurrent,
+            Arc::new(downloader),
+            |_| {},
+        )
+    }
+
+    fn statuses(queue: &DownloadQueue) -> Vec<JobStatus> {
+        queue.jobs().iter().map(|j| j.status).collect()
+    }
+
+    /// Waits for the jobs to reach `expected`, failing after a few seconds.
+    fn wait_for(queue: &DownloadQueue, expected: &[JobStatus]) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while statuses(queue) != expected {
+            assert!(Instant::now() < deadline, "stuck at {:?}", statuses(queue));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn runs_at_most_max_concurrent_jobs() {
+        use JobStatus::*;
+        let dir = TestDir::new("download-queue-concurrency");
+        let server = GatedServer::start();
+        let queue = queue(&dir, 2);
+
+        let names = ["a.scx", "b.scx", "c.scx", "d.scx"];
+        queue.enqueue(names.iter().map(|n| server.job(&dir, n)).collect());
+        wait_for(&queue, &[Running, Running, Queued, Queued]);
+        // Requests were sent for the running jobs only.
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 2);
+
+        server.open();
+        wait_for(&queue, &[Completed; 4]);
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 2);
+        for name in names {
+            let path = dir.join("maps").join(name);
+            assert_eq!(fs::read(path).unwrap(), map(&format!("/{name}")));
+        }
+    }
+
+    #[test]
+    fn pauses_resumes_and_cancels_jobs() {
+        use JobStatus::*;
+        let dir = TestDir::new("download-queue-transitions");
+        let server = GatedServer::start();
+        let queue = queue(&dir, 1);
+
+        let jobs = queue.enqueue(vec![server.job(&dir, "a.scx"), server.job(&dir, "b.scx")]);
+        let (a, b) = (jobs[0].id, jobs[1].id);
+        wait_for(&queue, &[Running, Queued]);
+
+        queue.pause(b).unwrap();
+        wait_for(&queue, &[Running, Paused]);
+        assert!(queue.pause(b).is_err());
+        queue.resume(b).unwrap();
+        assert!(queue.resume(b).is_err());
+        wait_for(&queue, &[Running, Queued]);
+
+        // Cancelling the running job frees its slot for the next one.
+        queue.cancel(a).unwrap();
+        wait_for(&queue, &[Cancelled, Running]);
+        assert!(queue.cancel(a).is_err());
+        assert!(queue.resume(a).is_err());
+
+        server.open();
+        wait_for(&queue, &[Cancelled, Completed]);
+        assert!(!dir.join("maps").join("a.scx").exists());
+        assert!(dir.join("maps").join("b.scx").exists());
+
+        queue.clear_finished();
+        assert!(queue.jobs().is_empty());
+    }
+
+    #[test]
+    fn ignores_runs_that_finish_after_a_restart() {
+        use JobStatus::*;
+        let dir = TestDir::new("download-queue-restart");
+        let server = GatedServer::start();
+        let queue = queue(&dir, 1);
+
+        let job = queue.enqueue(vec![server.job(&dir, "a.scx")]).remove(0);
+        wait_for(&queue, &[Running]);
+        let stale_run = queue.state.lock().unwrap().running[&job.id].0;
+        queue.pause(job.id).unwrap();
+        queue.resume(job.id).unwrap();
+        wait_for(&queue, &[Running]);
+
+        // The first run finishing late, having missed its abort, leaves the
+        // job to the second.
+        let fast = TestServer::start(|req| TestResponse::ok(map(&req.path)));
+        let stale = DownloadJob {
+            url: fast.url("/a.scx"),
+            ..job
+        };
+        tauri::async_runtime::block_on(queue.clone().run(stale, stale_run));
+        assert_eq!(statuses(&queue), vec![Running]);
+        assert_eq!(queue.state.lock().unwrap().running.len(), 1);
+
+        // So pausing still stops the download that is actually running.
+        queue.pause(job.id).unwrap();
+        server.open();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(statuses(&queue), vec![Paused]);
+        assert!(queue.state.lock().unwrap().running.is_empty());
+    }
+
+    #[test]
+    fn requeues_interrupted_jobs_on_reload() {
+        use JobStatus::*;
+        let dir = TestDir::new("download-queue-reload");
+        let server = GatedServer::start();
+        server.open();
+        let job = |id: u64, name: &str, status: JobStatus| {
+            let new = server.job(&dir, name);
+            DownloadJob {
+                id,
+                url: new.url,
+                destination_path: new.destination_path,
+                filename: new.filename,
+                template: None,
+                status,
+                path: None,
+                error: None,
+                received: 512,
+                total: Some(1_024),
+            }
+        };
+        let persisted = PersistedQueue {
+            next_id: 5,
+            jobs: vec![job(3, "a.scx", Running), job(4, "b.scx", Paused)],
+        };
+        fs::write(
+            dir.join("queue.json"),
+            serde_json::to_string(&persisted).unwrap(),
+        )
+        .unwrap();
+
+        let queue = queue(&dir, 2);
+        wait_for(&queue, &[Completed, Paused]);
+        let added = queue.enqueue(vec![server.job(&dir, "c.scx")]);
+        assert_eq!(added[0].id, 5);
+        wait_for(&queue, &[Completed, Paused, Completed]);
+
+        // The list on disk follows along.
+        drop(queue);
+        let reloaded = super::tests::queue(&dir, 2);
+        assert_eq!(statuses(&reloaded), vec![Completed, Paused, Completed]);
+    }
+}
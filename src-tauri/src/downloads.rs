@@ -0,0 +1,375 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use crate::{
+    cache::ReplayCache,
+    clock,
+    filename_template::{self, FilenameTemplate},
+    http::HttpClient,
+    mpq, persist,
+    replay_library::{self, ReplayLibrary},
+    replay_parser::ReplayParser,
+};
+
+/// Fetches replays and maps through the shared HTTP client and replay cache.
+/// Downloads are validated before they are cached or written out; rejects
+/// are kept in `quarantine_dir` for inspection. Interrupted transfers
+/// leave a `.part` file in `partial_dir` that the next attempt resumes from;
+/// transfers of the same URL take turns so they never share one. Every
+/// replay written to disk is added to the replay library.
+pub struct Downloader {
+    http: Arc<HttpClient>,
+    cache: Arc<ReplayCache>,
+    library: Arc<ReplayLibrary>,
+    quarantine_dir: PathBuf,
+    partial_dir: PathBuf,
+    transfers: Transfers,
+}
+
+/// Lock on each `.part` file in use, with how many transfers hold or await
+/// it.
+type Transfers = Mutex<HashMap<PathBuf, (Arc<AsyncMutex<()>>, usize)>>;
+
+impl Downloader {
+    pub fn new(
+        http: Arc<HttpClient>,
+        cache: Arc<ReplayCache>,
+        library: Arc<ReplayLibrary>,
+        quarantine_dir: PathBuf,
+        partial_dir: PathBuf,
+    ) -> Self {
+        Self {
+            http,
+            cache,
+            library,
+            quarantine_dir,
+            partial_dir,
+            transfers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the replay at `url` and whether it came from the cache.
+    pub async fn fetch_replay(
+        &self,
+        url: &str,
+        filename: &str,
+        on_progress: impl FnMut(u64, Option<u64>),
+    ) -> Result<(Vec<u8>, bool), String> {
+        // Held through caching, so a second request waiting on this one
+        // finds the replay in the cache instead of fetching it again.
+        let transfer = self.transfer(url).await;
+        if let Some(cached) = self.cache.get(url) {
+            println!(
+                "[replay-cache] Using cached file for {} -> {}",
+                url,
+                cached.display()
+            );
+            let bytes =
+                fs::read(&cached).map_err(|e| format!("Failed to read cached file: {e}"))?;
+            return Ok((bytes, true));
+        }
+
+        println!("[replay-cache] No cache for {url}, downloading");
+        let bytes = self
+            .http
+            .download(url, &transfer.part_path, on_progress)
+            .await?;
+        self.validate_replay(url, filename, &bytes)?;
+        if let Ok(path) = self.cache.put(url, filename, &bytes) {
+            self.index(path).await;
+        }
+        Ok((bytes, false))
+    }
+
+    /// Saves `url` under `destination_path`. Replays are named by `template`
+    /// when given, and by `filename` otherwise. Maps (`.scm` and `.scx`) are
+    /// only checked to be MPQ archives and bypass the replay cache; anything
+    /// else is treated as a replay.
+    pub async fn download_to_folder(
+        &self,
+        url: &str,
+        destination_path: &str,
+        filename: &str,
+        template: Option<&FilenameTemplate>,
+        on_progress: impl FnMut(u64, Option<u64>),
+    ) -> Result<PathBuf, String> {
+        let is_replay = template.is_some() || !is_map(filename);
+
+        let bytes = if is_replay {
+            self.fetch_replay(url, filename, on_progress).await?.0
+        } else {
+            let transfer = self.transfer(url).await;
+            let bytes = self
+                .http
+                .download(url, &transfer.part_path, on_progress)
+                .await?;
+            self.validate_map(url, filename, &bytes)?;
+            bytes
+        };
+
+        let templated = match template {
+            Some(template) => templated_path(template, destination_path, &bytes)?,
+            None => None,
+        };
+        let full_path = templated.unwrap_or_else(|| Path::new(destination_path).join(filename));
+        create_parent(&full_path)?;
+
+        // Through a temporary file, so a download aborted mid-write (a
+        // paused queue job) never leaves a truncated file in place.
+        persist::write_atomically(&full_path, &bytes)
+            .map_err(|e| format!("Failed to write file: {e}"))?;
+        if is_replay {
+            self.index(full_path.clone()).await;
+        }
+        Ok(full_path)
+    }
+
+    /// Adds the replay at `path` to the library, off the async runtime as
+    /// it parses the replay and writes to the database.
+    async fn index(&self, path: PathBuf) {
+        let library = self.library.clone();
+        let indexed = tauri::async_runtime::spawn_blocking(move || {
+            library
+                .index_file(&path)
+                .map_err(|e| format!("Failed to index {}: {e}", path.display()))
+        })
+        .await
+        .map_err(|e| format!("Failed to index replay: {e}"))
+        .and_then(|indexed| indexed);
+        if let Err(e) = indexed {
+            println!("[downloads] {e}");
+        }
+    }
+
+    /// Waits for any other transfer of `url` to finish, then claims its
+    /// `.part` file until the returned guard is dropped.
+    async fn transfer(&self, url: &str) -> Transfer<'_> {
+        let part_path = self
+            .partial_dir
+            .join(format!("{}.part", replay_library::digest(url.as_bytes())));
+        let lock = {
+            let mut transfers = self.transfers.lock().unwrap();
+            let (lock, users) = transfers.entry(part_path.clone()).or_default();
+            *users += 1;
+            lock.clone()
+        };
+        // Built before waiting, so a transfer cancelled in the queue still
+        // gives up its place.
+        let mut transfer = Transfer {
+            transfers: &self.transfers,
+            part_path,
+            guard: None,
+        };
+        transfer.guard = Some(lock.lock_owned().await);
+        transfer
+    }
+
+    fn validate_replay(&self, url: &str, filename: &str, bytes: &[u8]) -> Result<(), String> {
+        match ReplayParser::new(bytes).validate() {
+            Ok(()) => Ok(()),
+            Err(e) => Err(self.quarantine(url, filename, bytes, "replay", &e.to_string())),
+        }
+    }
+
+    /// Maps are only checked to be archives, which catches error pages and
+    /// other bodies served in their place.
+    fn validate_map(&self, url: &str, filename: &str, bytes: &[u8]) -> Result<(), String> {
+        if bytes.starts_with(mpq::MAGIC) {
+            return Ok(());
+        }
+        Err(self.quarantine(url, filename, bytes, "map", "no MPQ header"))
+    }
+
+    /// Keeps a rejected download in `quarantine_dir`, returning the error
+    /// to report.
+    fn quarantine(&self, url: &str, filename: &str, bytes: &[u8], kind: &str, e: &str) -> String {
+        let stamp = clock::now_ms();
+        let name = Path::new(filename)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| format!("download.{kind}"));
+        let quarantined = self.quarantine_dir.join(format!("{stamp}_{name}"));
+
+        let moved =
+            fs::create_dir_all(&self.quarantine_dir).and_then(|_| fs::write(&quarantined, bytes));
+        match moved {
+            Ok(()) => format!(
+                "Downloaded file from {url} is not a valid {kind} ({e}); quarantined at {}",
+                quarantined.display()
+            ),
+            Err(io) => {
+                println!("[downloads] Failed to quarantine {url}: {io}");
+                format!("Downloaded file from {url} is not a valid {kind} ({e}); discarded")
+            }
+        }
+    }
+}
+
+struct Transfer<'a> {
+    transfers: &'a Transfers,
+    part_path: PathBuf,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for Transfer<'_> {
+    fn drop(&mut self) {
+        let mut transfers = self.transfers.lock().unwrap();
+        if let Some((_, users)) = transfers.get_mut(&self.part_path) {
+            *users -= 1;
+            if *users == 0 {
+                transfers.remove(&self.part_path);
+            }
+        }
+    }
+}
+
+/// Resolves the templated location for `bytes`. Replays that don't parse
+/// fall back to the plain filename; a bad template is an error.
+fn templated_path(
+    template: &FilenameTemplate,
+    destination_path: &str,
+    bytes: &[u8],
+) -> Result<Option<PathBuf>, String> {
+    let parsed = match ReplayParser::new(bytes).parse() {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("[downloads] Not applying filename template, replay didn't parse: {e}");
+            return Ok(None);
+        }
+    };
+    let relative = filename_template::render(template, &parsed)?;
+    filename_template::unique_path(Path::new(destination_path), &relative, bytes).map(Some)
+}
+
+fn is_map(filename: &str) -> bool {
+    let filename = filename.to_lowercase();
+    filename.ends_with(".scm") || filename.ends_with(".scx")
+}
+
+fn create_parent(path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {e}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        replay_parser::{builder::ReplayBuilder, game_info::Race},
+        test_dir::TestDir,
+        test_server::{http_client, TestResponse, TestServer},
+    };
+    use std::time::Duration;
+
+    fn downloader(dir: &TestDir) -> Downloader {
+        Downloader::new(
+            http_client(),
+            Arc::new(ReplayCache::new(dir.join("cache"), 10)),
+            Arc::new(ReplayLibrary::open_in_memory().unwrap()),
+            dir.join("quarantine"),
+            dir.join("partial"),
+        )
+    }
+
+    fn replay() -> Vec<u8> {
+        ReplayBuilder::new("Polypoid")
+            .frames(10_000)
+            .player("Flash", Race::Terran)
+            .player("Jaedong", Race::Zerg)
+            .leave(9_000, 1)
+            .build()
+    }
+
+    fn map() -> Vec<u8> {
+        mpq::build_archive(&[(
+            "staredit\\scenario.chk",
+            &b"VER \x02\x00\x00\x00\xcd\x00"[..],
+        )])
+    }
+
+    #[test]
+    fn quarantines_downloads_that_arent_whole_replays_or_maps() {
+        let dir = TestDir::new("downloads-quarantine");
+        let mut truncated = replay();
+        truncated.truncate(truncated.len() - 10);
+        let server = TestServer::start(move |req| match req.path.as_str() {
+            "/replay" => TestResponse::ok(replay()),
+            "/truncated" => TestResponse::ok(truncated.clone()),
+            "/map" => TestResponse::ok(map()),
+            _ => TestResponse::ok(b"<html>Not found</html>".to_vec()),
+        });
+        let downloader = downloader(&dir);
+        let download = |path: &str, filename: &str| {
+            tauri::async_runtime::block_on(downloader.download_to_folder(
+                &server.url(path),
+                &dir.join("replays").to_string_lossy(),
+                filename,
+                None,
+                |_, _| {},
+            ))
+        };
+
+        // Neither the name nor the extension exempts a download from checks.
+        assert!(download("/truncated", "game.rep").is_err());
+        assert!(download("/missing", "game").is_err());
+        let quarantined = fs::read_dir(dir.join("quarantine")).unwrap().count();
+        assert_eq!(quarantined, 2);
+        assert!(!dir.join("replays").exists());
+        assert!(downloader.cache.get(&server.url("/truncated")).is_none());
+
+        let path = download("/replay", "game").unwrap();
+        assert_eq!(fs::read(path).unwrap(), replay());
+        assert!(downloader.cache.get(&server.url("/replay")).is_some());
+
+        // Maps aren't replays, but have to be archives.
+        assert!(download("/missing", "map.scx").is_err());
+        let quarantined = fs::read_dir(dir.join("quarantine")).unwrap().count();
+        assert_eq!(quarantined, 3);
+        assert!(!dir.join("replays").join("map.scx").exists());
+        let path = download("/map", "map.scx").unwrap();
+        assert_eq!(fs::read(path).unwrap(), map());
+    }
+
+    #[test]
+    fn fetches_a_url_once_at_a_time() {
+        let dir = TestDir::new("downloads-concurrent");
+        let requests = Arc::new(Mutex::new(0));
+        let counted = requests.clone();
+        let server = TestServer::start(move |_| {
+            *counted.lock().unwrap() += 1;
+            TestResponse::ok(replay()).delayed(Duration::from_millis(200))
+        });
+        let downloader = Arc::new(downloader(&dir));
+        let url = server.url("/replay");
+
+        let fetches: Vec<_> = (0..3)
+            .map(|_| {
+                let (downloader, url) = (downloader.clone(), url.clone());
+                std::thread::spawn(move || {
+                    tauri::async_runtime::block_on(downloader.fetch_replay(
+                        &url,
+                        "a.rep",
+                        |_, _| {},
+                    ))
+                })
+            })
+            .collect();
+        let cached: Vec<bool> = fetches
+            .into_iter()
+            .map(|f| f.join().unwrap().unwrap().1)
+            .collect();
+
+        assert_eq!(*requests.lock().unwrap(), 1);
+        assert_eq!(cached.iter().filter(|c| !**c).count(), 1);
+        assert!(downloader.transfers.lock().unwrap().is_empty());
+        assert_eq!(fs::read_dir(dir.join("partial")).unwrap().count(), 0);
+    }
+}
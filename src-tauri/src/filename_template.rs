@@ -0,0 +1,334 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calendar,
+    replay_parser::{ParsedReplay, PlayerStruct},
+};
+
+/// Placeholders understood by [`render`].
+pub const PLACEHOLDERS: &[&str] = &[
+    "date", "time", "year", "month", "day", "map", "title", "matchup", "p1", "r1", "p2", "r2",
+    "players", "races", "duration", "winner", "game_id",
+];
+
+/// A filename pattern such as `{matchup}/{map}/{date}_{p1}({r1})_vs_{p2}({r2})`,
+/// plus values the replay itself doesn't carry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilenameTemplate {
+    pub pattern: String,
+    /// Ladder game id from the web API, for `{game_id}`.
+    #[serde(default)]
+    pub game_id: Option<String>,
+    /// Name of the player whose perspective the file is saved from; they
+    /// become `{p1}` when present in the replay.
+    #[serde(default)]
+    pub player: Option<String>,
+}
+
+/// Renders `template` for `parsed` into a relative path ending in `.rep`.
+/// `/` and `\` in the pattern separate folders; placeholder values never do.
+pub fn render(template: &FilenameTemplate, parsed: &ParsedReplay) -> Result<PathBuf, String> {
+    let mut path = PathBuf::new();
+    let components: Vec<&str> = template
+        .pattern
+        .split(['/', '\\'])
+        .filter(|c| !c.trim().is_empty())
+        .collect();
+    if components.is_empty() {
+        return Err("Filename template is empty".to_string());
+    }
+
+    for (i, component) in components.iter().enumerate() {
+        let mut rendered = render_component(component, template, parsed)?;
+        if i == components.len() - 1 && !rendered.to_lowercase().ends_with(".rep") {
+            rendered.push_str(".rep");
+        }
+        path.push(sanitize_component(&rendered));
+    }
+
+    Ok(path)
+}
+
+fn render_component(
+    component: &str,
+    template: &FilenameTemplate,
+    parsed: &ParsedReplay,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = component;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in \"{component}\""))?;
+        let key = &rest[start + 1..start + end];
+        let value = placeholder_value(key, template, parsed)?;
+        // Values are single path components.
+        out.push_str(&value.replace(['/', '\\'], "_"));
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn placeholder_value(
+    key: &str,
+    template: &FilenameTemplate,
+    parsed: &ParsedReplay,
+) -> Result<String, String> {
+    let players = perspective_order(parsed, template.player.as_deref());
+    let name = |i: usize| {
+        players
+            .get(i)
+            .map_or("Unknown".to_string(), |p| p.name.clone())
+    };
+    let race = |i: usize| players.get(i).map_or('U', |p| p.race.initial()).to_string();
+
+    let secs = parsed
+        .game_info
+        .start_time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = calendar::civil_from_days((secs / 86_400) as i64);
+    let seconds_of_day = secs % 86_400;
+
+    let value = match key {
+        "date" => format!("{year:04}-{month:02}-{day:02}"),
+        "time" => format!(
+            "{:02}{:02}{:02}",
+            seconds_of_day / 3600,
+            seconds_of_day / 60 % 60,
+            seconds_of_day % 60
+        ),
+        "year" => format!("{year:04}"),
+        "month" => format!("{month:02}"),
+        "day" => format!("{day:02}"),
+        "map" => parsed.game_info.map_name.clone(),
+        "title" => parsed.game_info.title.clone(),
+        "matchup" => parsed.matchup(),
+        "p1" => name(0),
+        "r1" => race(0),
+        "p2" => name(1),
+        "r2" => race(1),
+        "players" => players
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" vs "),
+        "races" => players.iter().map(|p| p.race.initial()).collect(),
+        "duration" => {
+            let total = parsed.duration_ms() / 1000;
+            format!("{}m{:02}s", total / 60, total % 60)
+        }
+        "winner" => {
+            let winners = parsed.winners();
+            if winners.is_empty() {
+                "Unknown".to_string()
+            } else {
+                winners
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join("+")
+            }
+        }
+        "game_id" => template
+            .game_id
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string()),
+        _ => {
+            return Err(format!(
+                "Unknown placeholder {{{key}}}; expected one of {}",
+                PLACEHOLDERS.join(", ")
+            ))
+        }
+    };
+    Ok(value)
+}
+
+/// Active players in slot order, rotated so `player` (if given) comes first.
+fn perspective_order<'a>(parsed: &'a ParsedReplay, player: Option<&str>) -> Vec<&'a PlayerStruct> {
+    let mut players = parsed.players();
+    if let Some(player) = player {
+        if let Some(i) = players
+            .iter()
+            .position(|p| p.name.eq_ignore_ascii_case(player))
+        {
+            let p = players.remove(i);
+            players.insert(0, p);
+        }
+    }
+    players
+}
+
+/// Makes `s` safe to use as a single file or folder name on Windows, which
+/// is stricter than the other platforms we ship on.
+pub fn sanitize_component(s: &str) -> String {
+    const RESERVED: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+
+    let mut out: String = s
+        .chars()
+        // StarCraft map names embed color codes as control characters.
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c => c,
+        })
+        .collect();
+
+    if out.chars().count() > 120 {
+        out = out.chars().take(120).collect();
+    }
+    // Windows drops trailing dots and spaces, so names differing only in
+    // those would collide.
+    out = out.trim().trim_end_matches(['.', ' ']).to_string();
+
+    let stem = out.split('.').next().unwrap_or_default();
+    if out.is_empty() || out == ".." || RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+/// Picks a free path for `bytes` under `dir`, appending ` (2)`, ` (3)`, ...
+/// to the file stem on collision. An existing file with identical contents
+/// is reused instead of duplicated. Folders are collisions too; any other
+/// error reading a candidate is returned rather than risking an overwrite.
+pub fn unique_path(dir: &Path, relative: &Path, bytes: &[u8]) -> Result<PathBuf, String> {
+    let candidate = dir.join(relative);
+    let stem = candidate
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = candidate
+        .extension()
+        .map(|s| format!(".{}", s.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut path = candidate.clone();
+    let mut n = 2;
+    loop {
+        match fs::read(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(path),
+            Ok(existing) if existing == bytes => return Ok(path),
+            Ok(_) => {}
+            Err(_) if path.is_dir() => {}
+            Err(e) => return Err(format!("Failed to read {}: {e}", path.display())),
+        }
+        path = candidate.with_file_name(format!("{stem} ({n}){extension}"));
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        replay_parser::{builder::ReplayBuilder, game_info::Race, ReplayParser},
+        test_dir::TestDir,
+    };
+
+    fn template(pattern: &str) -> FilenameTemplate {
+        FilenameTemplate {
+            pattern: pattern.to_string(),
+            game_id: Some("1234".to_string()),
+            player: None,
+        }
+    }
+
+    fn parsed(map: &str) -> ParsedReplay {
+        let bytes = ReplayBuilder::new(map)
+            .frames(10_000)
+            .start_time(1_735_689_600 + 3_723)
+            .player("Flash", Race::Terran)
+            .player("Jaedong", Race::Zerg)
+            .leave(9_000, 1)
+            .build();
+        ReplayParser::new(&bytes).parse().unwrap()
+    }
+
+    #[test]
+    fn renders_placeholders_into_folders() {
+        let parsed = parsed("Polypoid");
+        let render = |pattern: &str| render(&template(pattern), &parsed).unwrap();
+
+        assert_eq!(
+            render("{matchup}/{map}/{date}_{p1}({r1})_vs_{p2}({r2})"),
+            Path::new("TvZ/Polypoid/2025-01-01_Flash(T)_vs_Jaedong(Z).rep")
+        );
+        assert_eq!(
+            render("{year}\\{month}-{day} {time} {duration} {winner} {game_id}.REP"),
+            Path::new("2025/01-01 010203 7m00s Flash 1234.REP")
+        );
+        assert_eq!(
+            render("{players} {races}"),
+            Path::new("Flash vs Jaedong TZ.rep")
+        );
+
+        let mut perspective = template("{p1}_vs_{p2}");
+        perspective.player = Some("jaedong".to_string());
+        assert_eq!(
+            super::render(&perspective, &parsed).unwrap(),
+            Path::new("Jaedong_vs_Flash.rep")
+        );
+
+        assert!(super::render(&template("{nope}"), &parsed).is_err());
+        assert!(super::render(&template("{map"), &parsed).is_err());
+        assert!(super::render(&template(" / "), &parsed).is_err());
+    }
+
+    #[test]
+    fn keeps_values_within_one_component() {
+        let parsed = parsed("a/b\\c:d");
+        assert_eq!(
+            render(&template("{map}"), &parsed).unwrap(),
+            Path::new("a_b_c_d.rep")
+        );
+    }
+
+    #[test]
+    fn sanitizes_names_for_windows() {
+        assert_eq!(sanitize_component("a<b>c:\"d|e?*"), "a_b_c__d_e__");
+        assert_eq!(sanitize_component("\x03Blue\x04 Storm "), "Blue Storm");
+        assert_eq!(sanitize_component("trailing. . "), "trailing");
+        assert_eq!(sanitize_component(""), "_");
+        assert_eq!(sanitize_component(".."), "_");
+
+        assert_eq!(sanitize_component("CON"), "_CON");
+        assert_eq!(sanitize_component("com1.rep"), "_com1.rep");
+        assert_eq!(sanitize_component("CONSOLE.rep"), "CONSOLE.rep");
+
+        let long = format!("{}  .x", "a".repeat(119));
+        assert_eq!(sanitize_component(&long), "a".repeat(119));
+        assert_eq!(sanitize_component(&"é".repeat(200)).chars().count(), 120);
+    }
+
+    #[test]
+    fn suffixes_collisions_and_reuses_identical_files() {
+        let dir = TestDir::new("filename-template");
+        let relative = Path::new("TvZ/game.rep");
+        let free = |bytes: &[u8]| unique_path(&dir, relative, bytes).unwrap();
+
+        assert_eq!(free(b"one"), dir.join("TvZ/game.rep"));
+        fs::create_dir_all(dir.join("TvZ")).unwrap();
+        fs::write(dir.join("TvZ/game.rep"), "one").unwrap();
+        assert_eq!(free(b"one"), dir.join("TvZ/game.rep"));
+        assert_eq!(free(b"two"), dir.join("TvZ/game (2).rep"));
+
+        fs::write(dir.join("TvZ/game (2).rep"), "two").unwrap();
+        fs::create_dir(dir.join("TvZ/game (3).rep")).unwrap();
+        assert_eq!(free(b"three"), dir.join("TvZ/game (4).rep"));
+        assert_eq!(free(b"two"), dir.join("TvZ/game (2).rep"));
+    }
+}
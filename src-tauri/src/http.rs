@@ -0,0 +1,656 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tauri_plugin_http::reqwest::{self, header, StatusCode};
+
+use crate::rate_limit::RateLimiter;
+
+/// Tunables for the shared backend HTTP client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HttpClientConfig {
+    pub connect_timeout_ms: u64,
+    pub request_timeout_ms: u64,
+    /// Number of retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each subsequent one.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: 5_000,
+            request_timeout_ms: 60_000,
+            max_retries: 4,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 8_000,
+        }
+    }
+}
+
+/// Payload of the `download-progress` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub url: String,
+    pub received: u64,
+    pub total: Option<u64>,
+}
+
+/// Outcome of a single attempt; `Retry` errors are worth another try.
+enum AttemptError {
+    Retry(String),
+    Fatal(String),
+}
+
+/// One `reqwest::Client` shared by every backend download, with timeouts and
+/// exponential backoff on 5xx responses and connection failures. Every
+/// request, retries included, waits its turn in the shared [`RateLimiter`].
+pub struct HttpClient {
+    inner: RwLock<(reqwest::Client, HttpClientConfig)>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl HttpClient {
+    pub fn new(config: HttpClientConfig, limiter: Arc<RateLimiter>) -> Result<Self, String> {
+        let client = Self::build_client(&config)?;
+        Ok(Self {
+            inner: RwLock::new((client, config)),
+            limiter,
+        })
+    }
+
+    fn build_client(config: &HttpClientConfig) -> Result<reqwest::Client, String> {
+        reqwest::Client::builder()
+            .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+            .timeout(Duration::from_millis(config.request_timeout_ms))
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {e}"))
+    }
+
+    pub fn config(&self) -> HttpClientConfig {
+        self.inner.read().unwrap().1.clone()
+    }
+
+    /// Replaces the configuration; in-flight requests keep their old client.
+    pub fn configure(&self, config: HttpClientConfig) -> Result<(), String> {
+        let client = Self::build_client(&config)?;
+        *self.inner.write().unwrap() = (client, config);
+        Ok(())
+    }
+
+    fn snapshot(&self) -> (reqwest::Client, HttpClientConfig) {
+        let inner = self.inner.read().unwrap();
+        (inner.0.clone(), inner.1.clone())
+    }
+
+    fn backoff(config: &HttpClientConfig, attempt: u32) -> Duration {
+        let factor = 1u64.checked_shl(attempt).unwrap_or(u64::MAX);
+        let ms = config
+            .initial_backoff_ms
+            .saturating_mul(factor)
+            .min(config.max_backoff_ms);
+        Duration::from_millis(ms)
+    }
+
+    /// A single GET with a short timeout and no retries, for liveness checks.
+    /// Returns the status code and body.
+    pub async fn probe(&self, url: &str, timeout: Duration) -> Result<(u16, String), String> {
+        let (client, _) = self.snapshot();
+        self.limiter.acquire(url).await?;
+        let response = client
+            .get(url)
+            .timeout(timeout)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach {url}: {e}"))?;
+        let status = response.status().as_u16();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read response: {e}"))?;
+        Ok((status, body))
+    }
+
+    /// Decides what to do after a retryable failure: the delay before the
+    /// next attempt, or the final error once retries are used up.
+    fn retry_delay(
+        config: &HttpClientConfig,
+        url: &str,
+        attempt: u32,
+        error: String,
+    ) -> Result<Duration, String> {
+        if attempt >= config.max_retries {
+            return Err(format!("{error} (gave up after {} attempts)", attempt + 1));
+        }
+        let delay = Self::backoff(config, attempt);
+        println!("[http] {error}; retrying {url} in {}ms", delay.as_millis());
+        Ok(delay)
+    }
+
+    /// GETs `url` into a string, with the same retry policy as downloads.
+    pub async fn get_text(&self, url: &str) -> Result<String, String> {
+        let (client, config) = self.snapshot();
+
+        let mut attempt = 0;
+        loop {
+            match self.attempt_text(&client, url).await {
+                Ok(text) => return Ok(text),
+                Err(AttemptError::Fatal(e)) => return Err(e),
+                Err(AttemptError::Retry(e)) => {
+                    let delay = Self::retry_delay(&config, url, attempt, e)?;
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    async fn attempt_text(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+    ) -> Result<String, AttemptError> {
+        self.limiter
+            .acquire(url)
+            .await
+            .map_err(AttemptError::Fatal)?;
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| classify(format!("Failed to reach {url}: {e}"), &e))?;
+        let status = response.status();
+        if status.is_server_error() {
+            return Err(AttemptError::Retry(format!(
+                "Request failed with status: {status}"
+            )));
+        }
+        if !status.is_success() {
+            return Err(AttemptError::Fatal(format!(
+                "Request failed with status: {status}"
+            )));
+        }
+        response
+            .text()
+            .await
+            .map_err(|e| classify(format!("Failed to read response: {e}"), &e))
+    }
+
+    /// Downloads `url`, calling `on_progress(received, total)` as chunks
+    /// arrive. Bytes are staged in `part_path`; when the server advertises
+    /// range support and a validator (ETag/Last-Modified), an interrupted
+    /// transfer resumes from there with a Range request, both on retry and on
+    /// a later call. Otherwise the download restarts from zero.
+    pub async fn download(
+        &self,
+        url: &str,
+        part_path: &Path,
+        mut on_progress: impl FnMut(u64, Option<u64>),
+    ) -> Result<Vec<u8>, String> {
+        let (client, config) = self.snapshot();
+
+        let mut attempt = 0;
+        loop {
+            match self
+                .attempt(&client, url, part_path, &mut on_progress)
+                .await
+            {
+                Ok(()) => break,
+                Err(AttemptError::Fatal(e)) => {
+                    discard_part(part_path);
+                    return Err(e);
+                }
+                // The partial file is kept so the next call can resume it.
+                Err(AttemptError::Retry(e)) => {
+                    let delay = Self::retry_delay(&config, url, attempt, e)?;
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+
+        let bytes = fs::read(part_path).map_err(|e| format!("Failed to read download: {e}"))?;
+        discard_part(part_path);
+        Ok(bytes)
+    }
+
+    async fn attempt(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        part_path: &Path,
+        on_progress: &mut impl FnMut(u64, Option<u64>),
+    ) -> Result<(), AttemptError> {
+        let io_error =
+            |e: std::io::Error| AttemptError::Fatal(format!("Failed to write file: {e}"));
+
+        let existing = fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+        let validator = PartMeta::load(part_path)
+            .filter(|meta| meta.url == url && existing > 0)
+            .and_then(|meta| meta.etag.or(meta.last_modified));
+
+        self.limiter
+            .acquire(url)
+            .await
+            .map_err(AttemptError::Fatal)?;
+        let mut request = client.get(url);
+        if let Some(validator) = &validator {
+            request = request
+                .header(header::RANGE, format!("bytes={existing}-"))
+                .header(header::IF_RANGE, validator);
+        }
+        let mut response = request
+            .send()
+            .await
+            .map_err(|e| classify(format!("Failed to download file: {e}"), &e))?;
+
+        let status = response.status();
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            discard_part(part_path);
+            return Err(AttemptError::Retry(
+                "Server rejected the resume range".to_string(),
+            ));
+        }
+        if status.is_server_error() {
+            return Err(AttemptError::Retry(format!(
+                "Download failed with status: {status}"
+            )));
+        }
+        if !status.is_success() {
+            return Err(AttemptError::Fatal(format!(
+                "Download failed with status: {status}"
+            )));
+        }
+
+        let resuming = validator.is_some() && status == StatusCode::PARTIAL_CONTENT;
+        let (mut file, offset) = if resuming {
+            let expected = format!("bytes {existing}-");
+            let content_range = header_str(&response, header::CONTENT_RANGE).unwrap_or_default();
+            if !content_range.starts_with(&expected) {
+                discard_part(part_path);
+                return Err(AttemptError::Retry(format!(
+                    "Unexpected Content-Range \"{content_range}\" when resuming"
+                )));
+            }
+            println!("[http] Resuming {url} from byte {existing}");
+            let file = fs::OpenOptions::new()
+                .append(true)
+                .open(part_path)
+                .map_err(io_error)?;
+            (file, existing)
+        } else {
+            if validator.is_some() {
+                println!("[http] Server sent the full body for {url}, restarting");
+            }
+            if let Some(parent) = part_path.parent() {
+                fs::create_dir_all(parent).map_err(io_error)?;
+            }
+            let file = fs::File::create(part_path).map_err(io_error)?;
+            PartMeta::from_response(url, &response).save(part_path);
+            (file, 0)
+        };
+
+        let total = response.content_length().map(|len| len + offset);
+        let mut received = offset;
+        on_progress(received, total);
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| classify(format!("Failed to read response: {e}"), &e))?
+        {
+            file.write_all(&chunk).map_err(io_error)?;
+            received += chunk.len() as u64;
+            on_progress(received, total);
+        }
+
+        if let Some(total) = total {
+            if received != total {
+                return Err(AttemptError::Retry(format!(
+                    "Response body was truncated: received {received} of {total} bytes"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Validators saved next to a `.part` file so a later request can check the
+/// remote file is unchanged before resuming.
+#[derive(Debug, Serialize, Deserialize)]
+struct PartMeta {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl PartMeta {
+    fn path(part_path: &Path) -> PathBuf {
+        part_path.with_extension("part.json")
+    }
+
+    fn load(part_path: &Path) -> Option<PartMeta> {
+        let content = fs::read_to_string(Self::path(part_path)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn from_response(url: &str, response: &reqwest::Response) -> PartMeta {
+        let accepts_ranges = header_str(response, header::ACCEPT_RANGES)
+            .is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+        // Weak ETags can't be used with If-Range.
+        let etag = header_str(response, header::ETAG).filter(|v| !v.starts_with("W/"));
+        let last_modified = header_str(response, header::LAST_MODIFIED);
+        PartMeta {
+            url: url.to_string(),
+            etag: etag.filter(|_| accepts_ranges),
+            last_modified: last_modified.filter(|_| accepts_ranges),
+        }
+    }
+
+    fn save(&self, part_path: &Path) {
+        let path = Self::path(part_path);
+        if self.etag.is_none() && self.last_modified.is_none() {
+            let _ = fs::remove_file(path);
+            return;
+        }
+        if let Ok(content) = serde_json::to_string(self) {
+            let _ = fs::write(path, content);
+        }
+    }
+}
+
+fn header_str(response: &reqwest::Response, name: header::HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+fn discard_part(part_path: &Path) {
+    let _ = fs::remove_file(part_path);
+    let _ = fs::remove_file(PartMeta::path(part_path));
+}
+
+fn classify(message: String, e: &reqwest::Error) -> AttemptError {
+    if e.is_builder() || e.is_redirect() {
+        AttemptError::Fatal(message)
+    } else {
+        AttemptError::Retry(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_dir::TestDir,
+        test_server::{unlimited, TestRequest, TestResponse, TestServer},
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    fn fast_config() -> HttpClientConfig {
+        HttpClientConfig {
+            connect_timeout_ms: 1_000,
+            request_timeout_ms: 5_000,
+            max_retries: 3,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 5,
+        }
+    }
+
+    fn client() -> HttpClient {
+        HttpClient::new(fast_config(), unlimited()).unwrap()
+    }
+
+    fn download(client: &HttpClient, url: &str, part: &Path) -> Result<Vec<u8>, String> {
+        tauri::async_runtime::block_on(client.download(url, part, |_, _| {}))
+    }
+
+    /// Remote file served by [`range_server`]: (ETag, body). Tests swap it
+    /// to simulate the file changing between attempts.
+    type RemoteFile = Arc<Mutex<(&'static str, Vec<u8>)>>;
+
+    /// Serves the current `file` with range support, honouring `Range` only
+    /// when `If-Range` matches its ETag. The first `truncate` responses are
+    /// cut short.
+    fn range_server(
+        file: RemoteFile,
+        truncate: usize,
+        requests: Arc<Mutex<Vec<TestRequest>>>,
+    ) -> TestServer {
+        TestServer::start(move |req| {
+            let n = {
+                let mut requests = requests.lock().unwrap();
+                requests.push(req.clone());
+                requests.len()
+            };
+            let (etag, body) = file.lock().unwrap().clone();
+            let start = match (req.header("range"), req.header("if-range")) {
+                (Some(range), Some(validator)) if validator == etag => range
+                    .trim_start_matches("bytes=")
+                    .trim_end_matches('-')
+                    .parse::<usize>()
+                    .ok(),
+                _ => None,
+            };
+            let response = match start {
+                Some(start) => TestResponse::status(206)
+                    .body(body[start..].to_vec())
+                    .header(
+                        "Content-Range",
+                        &format!("bytes {start}-{}/{}", body.len() - 1, body.len()),
+                    ),
+                None => TestResponse::ok(body),
+            }
+            .header("Accept-Ranges", "bytes")
+            .header("ETag", etag);
+            if n <= truncate {
+                let len = response.body.len();
+                response.truncated(len / 2)
+            } else {
+                response
+            }
+        })
+    }
+
+    #[test]
+    fn downloads_body_and_reports_progress() {
+        let body = vec![7u8; 100_000];
+        let served = body.clone();
+        let server = TestServer::start(move |req| match req.path.as_str() {
+            "/replay.rep" => TestResponse::ok(served.clone()),
+            _ => TestResponse::status(404),
+        });
+        let client = client();
+        let dir = TestDir::new("http-progress");
+        let part = dir.join("progress.part");
+
+        let mut progress = Vec::new();
+        let bytes = tauri::async_runtime::block_on(client.download(
+            &server.url("/replay.rep"),
+            &part,
+            |r, t| progress.push((r, t)),
+        ))
+        .unwrap();
+
+        assert_eq!(bytes, body);
+        assert_eq!(progress.first(), Some(&(0, Some(100_000))));
+        assert_eq!(progress.last(), Some(&(100_000, Some(100_000))));
+        assert!(!part.exists());
+    }
+
+    #[test]
+    fn retries_server_errors_and_dropped_connections() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let server = TestServer::start(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => TestResponse::status(503),
+            1 => TestResponse::drop_connection(),
+            _ => TestResponse::ok(b"ok".to_vec()),
+        });
+        let client = client();
+
+        let dir = TestDir::new("http-retries");
+        let bytes = download(&client, &server.url("/"), &dir.join("retries.part")).unwrap();
+
+        assert_eq!(bytes, b"ok");
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let server = TestServer::start(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            TestResponse::status(404)
+        });
+        let client = client();
+
+        let dir = TestDir::new("http-client-error");
+        let err = download(&client, &server.url("/"), &dir.join("client-error.part")).unwrap_err();
+
+        assert!(err.contains("404"), "{err}");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let server = TestServer::start(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            TestResponse::status(500)
+        });
+        let client = client();
+
+        let dir = TestDir::new("http-give-up");
+        let err = download(&client, &server.url("/"), &dir.join("give-up.part")).unwrap_err();
+
+        assert!(err.contains("gave up after 4 attempts"), "{err}");
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn resumes_truncated_transfer_with_range_request() {
+        let body: Vec<u8> = (0..50_000u32).map(|i| (i % 251) as u8).collect();
+        let file = Arc::new(Mutex::new(("\"v1\"", body.clone())));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let server = range_server(file, 1, requests.clone());
+        let client = client();
+        let dir = TestDir::new("http-resume");
+        let part = dir.join("resume.part");
+
+        let bytes = download(&client, &server.url("/"), &part).unwrap();
+
+        assert_eq!(bytes, body);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].header("range"), None);
+        assert_eq!(requests[1].header("range"), Some("bytes=25000-"));
+        assert_eq!(requests[1].header("if-range"), Some("\"v1\""));
+        assert!(!part.exists());
+    }
+
+    #[test]
+    fn keeps_part_after_giving_up_and_resumes_next_time() {
+        let body = vec![3u8; 40_000];
+        let file = Arc::new(Mutex::new(("\"v1\"", body.clone())));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        // Every attempt of the first call is cut short.
+        let server = range_server(file, 4, requests.clone());
+        let client = client();
+        let dir = TestDir::new("http-keep");
+        let part = dir.join("keep.part");
+
+        let err = download(&client, &server.url("/"), &part).unwrap_err();
+        assert!(err.contains("gave up after 4 attempts"), "{err}");
+        let kept = fs::metadata(&part).unwrap().len();
+        assert!(kept > 0 && kept < 40_000);
+
+        let bytes = download(&client, &server.url("/"), &part).unwrap();
+
+        assert_eq!(bytes, body);
+        let requests = requests.lock().unwrap();
+        let expected = format!("bytes={kept}-");
+        assert_eq!(
+            requests.last().unwrap().header("range"),
+            Some(expected.as_str())
+        );
+    }
+
+    #[test]
+    fn restarts_when_remote_file_changed() {
+        let file = Arc::new(Mutex::new(("\"v1\"", vec![1u8; 10_000])));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let server = range_server(file.clone(), 4, requests.clone());
+        let client = client();
+        let dir = TestDir::new("http-changed");
+        let part = dir.join("changed.part");
+
+        download(&client, &server.url("/"), &part).unwrap_err();
+        let new = vec![2u8; 12_000];
+        *file.lock().unwrap() = ("\"v2\"", new.clone());
+
+        let bytes = download(&client, &server.url("/"), &part).unwrap();
+
+        assert_eq!(bytes, new);
+        let requests = requests.lock().unwrap();
+        // The stale validator was sent and the server answered with the whole file.
+        assert_eq!(requests.len(), 5);
+        assert_eq!(requests[4].header("if-range"), Some("\"v1\""));
+    }
+
+    #[test]
+    fn restarts_cleanly_without_range_support() {
+        let body = vec![9u8; 20_000];
+        let served = body.clone();
+        let requests = Arc::new(Mutex::new(Vec::<TestRequest>::new()));
+        let log = requests.clone();
+        let server = TestServer::start(move |req| {
+            let mut log = log.lock().unwrap();
+            log.push(req.clone());
+            let response = TestResponse::ok(served.clone()).header("ETag", "\"v1\"");
+            if log.len() == 1 {
+                response.truncated(5_000)
+            } else {
+                response
+            }
+        });
+        let client = client();
+
+        let dir = TestDir::new("http-no-ranges");
+        let bytes = download(&client, &server.url("/"), &dir.join("no-ranges.part")).unwrap();
+
+        assert_eq!(bytes, body);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|r| r.header("range").is_none()));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_cap() {
+        let config = HttpClientConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 350,
+            ..HttpClientConfig::default()
+        };
+        assert_eq!(HttpClient::backoff(&config, 0), Duration::from_millis(100));
+        assert_eq!(HttpClient::backoff(&config, 1), Duration::from_millis(200));
+        assert_eq!(HttpClient::backoff(&config, 2), Duration::from_millis(350));
+        assert_eq!(HttpClient::backoff(&config, 80), Duration::from_millis(350));
+    }
+}
@@ -0,0 +1,843 @@
+//! History collected from the web API over time, kept in SQLite. Unlike the
+//! replay library it can't be rebuilt, so the schema only ever gains tables
+//! and is never dropped.
+
+use std::{fs, path::Path, sync::Mutex};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::{
+    replay_library::Outcome,
+    sc_api::{AuroraProfile, LeaderboardEntry, ToonRequest},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tracked_profiles (
+        toon TEXT NOT NULL COLLATE NOCASE,
+        gateway INTEGER NOT NULL,
+        PRIMARY KEY (toon, gateway)
+    );
+    CREATE TABLE IF NOT EXISTS saved_player_matches (
+        match_guid TEXT NOT NULL,
+        toon TEXT NOT NULL COLLATE NOCASE,
+        gateway INTEGER NOT NULL,
+        aurora_id INTEGER NOT NULL,
+        alias TEXT NOT NULL,
+        match_created_ms INTEGER NOT NULL,
+        map TEXT NOT NULL,
+        race TEXT NOT NULL,
+        opponent TEXT NOT NULL,
+        opponent_gateway INTEGER NOT NULL,
+        opponent_race TEXT NOT NULL,
+        outcome TEXT NOT NULL,
+        points INTEGER NOT NULL,
+        PRIMARY KEY (match_guid, toon, gateway)
+    );
+    CREATE INDEX IF NOT EXISTS saved_player_matches_created
+        ON saved_player_matches (match_created_ms);
+    CREATE TABLE IF NOT EXISTS leaderboard_snapshots (
+        id INTEGER PRIMARY KEY,
+        leaderboard_id INTEGER NOT NULL,
+        taken_at_ms INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS leaderboard_snapshots_taken
+        ON leaderboard_snapshots (leaderboard_id, taken_at_ms);
+    CREATE TABLE IF NOT EXISTS leaderboard_entries (
+        snapshot_id INTEGER NOT NULL REFERENCES leaderboard_snapshots(id) ON DELETE CASCADE,
+        rank INTEGER NOT NULL,
+        toon TEXT NOT NULL COLLATE NOCASE,
+        gateway INTEGER NOT NULL,
+        points INTEGER NOT NULL,
+        wins INTEGER NOT NULL,
+        losses INTEGER NOT NULL,
+        PRIMARY KEY (snapshot_id, rank)
+    );
+    CREATE INDEX IF NOT EXISTS leaderboard_entries_toon
+        ON leaderboard_entries (toon, gateway);
+    CREATE TABLE IF NOT EXISTS rating_samples (
+        toon TEXT NOT NULL COLLATE NOCASE,
+        gateway INTEGER NOT NULL,
+        taken_at_ms INTEGER NOT NULL,
+        points INTEGER NOT NULL,
+        wins INTEGER NOT NULL,
+        losses INTEGER NOT NULL,
+        PRIMARY KEY (toon, gateway, taken_at_ms)
+    );
+    CREATE TABLE IF NOT EXISTS toon_observations (
+        aurora_id INTEGER NOT NULL,
+        toon TEXT NOT NULL COLLATE NOCASE,
+        gateway INTEGER NOT NULL,
+        first_seen_ms INTEGER NOT NULL,
+        last_seen_ms INTEGER NOT NULL,
+        PRIMARY KEY (aurora_id, toon, gateway)
+    );
+    CREATE INDEX IF NOT EXISTS toon_observations_toon
+        ON toon_observations (toon, gateway);
+    CREATE TABLE IF NOT EXISTS replay_sightings (
+        aurora_id INTEGER NOT NULL,
+        toon TEXT NOT NULL COLLATE NOCASE,
+        gateway INTEGER NOT NULL,
+        first_seen_ms INTEGER NOT NULL,
+        last_seen_ms INTEGER NOT NULL,
+        PRIMARY KEY (aurora_id, toon, gateway)
+    );
+    CREATE INDEX IF NOT EXISTS replay_sightings_toon
+        ON replay_sightings (toon, gateway);
+";
+
+/// A finished ladder game of a saved player's toon.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedPlayerMatch {
+    pub aurora_id: u64,
+    /// The saved player's alias when the match was recorded.
+    pub alias: String,
+    pub toon: String,
+    pub gateway: u16,
+    pub match_guid: String,
+    pub match_created_ms: u64,
+    pub map: String,
+    pub race: String,
+    pub opponent: String,
+    pub opponent_gateway: u16,
+    pub opponent_race: String,
+    pub outcome: Outcome,
+    /// Rating after the match.
+    pub points: i64,
+}
+
+/// A toon's rating at one snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RatingSample {
+    pub toon: String,
+    pub gateway: u16,
+    pub points: i64,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+/// One point of a toon's rating history. `rank` is known when the toon was
+/// on a snapshotted leaderboard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RatingPoint {
+    pub taken_at_ms: u64,
+    pub points: i64,
+    pub wins: u32,
+    pub losses: u32,
+    pub leaderboard_id: Option<u32>,
+    pub rank: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RankedToon {
+    pub toon: String,
+    pub gateway: u16,
+    pub rank: u32,
+    pub points: i64,
+}
+
+/// A toon's place in two snapshots; `None` where it wasn't on the board.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RankMovement {
+    pub toon: String,
+    pub gateway: u16,
+    pub from_rank: Option<u32>,
+    pub to_rank: Option<u32>,
+    pub from_points: Option<i64>,
+    pub to_points: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RankMovements {
+    pub from_taken_at_ms: u64,
+    pub to_taken_at_ms: u64,
+    /// By rank in the later snapshot, then those who dropped off.
+    pub movements: Vec<RankMovement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopChurn {
+    pub from_taken_at_ms: u64,
+    pub to_taken_at_ms: u64,
+    /// In the later top N but not the earlier one, with their later ranks.
+    pub entered: Vec<RankedToon>,
+    /// In the earlier top N but not the later one, with their earlier ranks.
+    pub left: Vec<RankedToon>,
+    pub stayed: u32,
+}
+
+/// A name an account was seen using, and over which period.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToonAlias {
+    pub aurora_id: u64,
+    pub toon: String,
+    pub gateway: u16,
+    /// Period the web API reported the account using the name.
+    pub first_seen_ms: u64,
+    pub last_seen_ms: u64,
+    /// Period of replays under the name credited to the account. Kept apart
+    /// from the API's, as the account is inferred rather than reported.
+    pub replay_first_seen_ms: Option<u64>,
+    pub replay_last_seen_ms: Option<u64>,
+}
+
+/// The account behind a name, with the name it was seen using last.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedToon {
+    pub aurora_id: u64,
+    pub current: ToonAlias,
+    /// Every name of the account, most recently seen first.
+    pub aliases: Vec<ToonAlias>,
+}
+
+pub struct LadderHistory {
+    conn: Mutex<Connection>,
+}
+
+impl LadderHistory {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {e}"))?;
+        }
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open ladder history {}: {e}", path.display()))?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")
+            .map_err(|e| format!("Failed to open ladder history: {e}"))?;
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to create ladder history schema: {e}"))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Records `matches` of the toon `toon` on `gateway`, returning those not
+    /// seen before. The first matches recorded for a toon are what it had
+    /// already played rather than new activity, so none are returned.
+    pub fn record_matches(
+        &self,
+        toon: &str,
+        gateway: u16,
+        matches: &[SavedPlayerMatch],
+    ) -> Result<Vec<SavedPlayerMatch>, String> {
+        let db_err = |e: rusqlite::Error| format!("Failed to record matches: {e}");
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        let first_check = tx
+            .execute(
+                "INSERT OR IGNORE INTO tracked_profiles (toon, gateway) VALUES (?1, ?2)",
+                params![toon, gateway],
+            )
+            .map_err(db_err)?
+            > 0;
+        let mut new = Vec::new();
+        for m in matches {
+            let inserted = tx
+                .execute(
+                    "INSERT OR IGNORE INTO saved_player_matches
+                        (match_guid, toon, gateway, aurora_id, alias, match_created_ms, map, race,
+                         opponent, opponent_gateway, opponent_race, outcome, points)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                    params![
+                        m.match_guid,
+                        m.toon,
+                        m.gateway,
+                        m.aurora_id as i64,
+                        m.alias,
+                        m.match_created_ms as i64,
+                        m.map,
+                        m.race,
+                        m.opponent,
+                        m.opponent_gateway,
+                        m.opponent_race,
+                        m.outcome.as_str(),
+                        m.points
+                    ],
+                )
+                .map_err(db_err)?;
+            if inserted > 0 && !first_check {
+                new.push(m.clone());
+            }
+        }
+        tx.commit().map_err(db_err)?;
+        new.sort_by_key(|m| m.match_created_ms);
+        Ok(new)
+    }
+
+    /// Recorded matches, newest first, of one saved player or all of them.
+    pub fn saved_player_matches(
+        &self,
+        aurora_id: Option<u64>,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<SavedPlayerMatch>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT aurora_id, alias, toon, gateway, match_guid, match_created_ms, map, race,
+                        opponent, opponent_gateway, opponent_race, outcome, points
+                 FROM saved_player_matches
+                 WHERE ?1 IS NULL OR aurora_id = ?1
+                 ORDER BY match_created_ms DESC, match_guid LIMIT ?2 OFFSET ?3",
+            )
+            .map_err(|e| format!("Failed to load saved player matches: {e}"))?;
+        let rows = stmt
+            .query_map(
+                params![aurora_id.map(|id| id as i64), limit, offset],
+                |row| {
+                    Ok(SavedPlayerMatch {
+                        aurora_id: row.get::<_, i64>(0)? as u64,
+                        alias: row.get(1)?,
+                        toon: row.get(2)?,
+                        gateway: row.get(3)?,
+                        match_guid: row.get(4)?,
+                        match_created_ms: row.get::<_, i64>(5)? as u64,
+                        map: row.get(6)?,
+                        race: row.get(7)?,
+                        opponent: row.get(8)?,
+                        opponent_gateway: row.get(9)?,
+                        opponent_race: row.get(10)?,
+                        outcome: Outcome::parse(&row.get::<_, String>(11)?),
+                        points: row.get(12)?,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to load saved player matches: {e}"))?;
+        Ok(rows.flatten().collect())
+    }
+
+    /// When the last snapshot of any kind was taken.
+    pub fn latest_snapshot_ms(&self) -> Result<Option<u64>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT max(taken_at_ms) FROM (
+                SELECT taken_at_ms FROM leaderboard_snapshots
+                UNION ALL SELECT taken_at_ms FROM rating_samples
+             )",
+            [],
+            |row| row.get::<_, Option<i64>>(0),
+        )
+        .map(|ms| ms.map(|ms| ms as u64))
+        .map_err(|e| format!("Failed to read ladder history: {e}"))
+    }
+
+    pub fn record_leaderboard(
+        &self,
+        leaderboard_id: u32,
+        taken_at_ms: u64,
+        entries: &[LeaderboardEntry],
+    ) -> Result<(), String> {
+        let db_err = |e: rusqlite::Error| format!("Failed to record leaderboard snapshot: {e}");
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        tx.execute(
+            "INSERT INTO leaderboard_snapshots (leaderboard_id, taken_at_ms) VALUES (?1, ?2)",
+            params![leaderboard_id, taken_at_ms as i64],
+        )
+        .map_err(db_err)?;
+        let snapshot_id = tx.last_insert_rowid();
+        for entry in entries {
+            tx.execute(
+                "INSERT OR REPLACE INTO leaderboard_entries
+                    (snapshot_id, rank, toon, gateway, points, wins, losses)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    snapshot_id,
+                    entry.rank,
+                    entry.toon,
+                    entry.gateway_id,
+                    entry.points,
+                    entry.wins,
+                    entry.losses
+                ],
+            )
+            .map_err(db_err)?;
+        }
+        tx.commit().map_err(db_err)
+    }
+
+    pub fn record_ratings(&self, taken_at_ms: u64, samples: &[RatingSample]) -> Result<(), String> {
+        let db_err = |e: rusqlite::Error| format!("Failed to record ratings: {e}");
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        for sample in samples {
+            tx.execute(
+                "INSERT OR REPLACE INTO rating_samples
+                    (toon, gateway, taken_at_ms, points, wins, losses)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    sample.toon,
+                    sample.gateway,
+                    taken_at_ms as i64,
+                    sample.points,
+                    sample.wins,
+                    sample.losses
+                ],
+            )
+            .map_err(db_err)?;
+        }
+        tx.commit().map_err(db_err)
+    }
+
+    /// The toon's rating over time, oldest first, from both leaderboard
+    /// snapshots and rating samples. A snapshot seen in both is one point.
+    pub fn rating_history(
+        &self,
+        toon: &str,
+        gateway: u16,
+        after_ms: Option<u64>,
+        before_ms: Option<u64>,
+    ) -> Result<Vec<RatingPoint>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT taken_at_ms, points, wins, losses, leaderboard_id, rank FROM (
+                    SELECT s.taken_at_ms, e.points, e.wins, e.losses, s.leaderboard_id, e.rank
+                    FROM leaderboard_entries e
+                    JOIN leaderboard_snapshots s ON s.id = e.snapshot_id
+                    WHERE e.toon = ?1 AND e.gateway = ?2
+                    UNION ALL
+                    SELECT taken_at_ms, points, wins, losses, NULL, NULL
+                    FROM rating_samples WHERE toon = ?1 AND gateway = ?2
+                 )
+                 WHERE taken_at_ms >= ?3 AND taken_at_ms < ?4
+                 ORDER BY taken_at_ms, rank IS NULL",
+            )
+            .map_err(|e| format!("Failed to load rating history: {e}"))?;
+        let rows = stmt
+            .query_map(
+                params![
+                    toon,
+                    gateway,
+                    after_ms.unwrap_or(0) as i64,
+                    before_ms.map_or(i64::MAX, |ms| ms as i64)
+                ],
+                |row| {
+                    Ok(RatingPoint {
+                        taken_at_ms: row.get::<_, i64>(0)? as u64,
+                        points: row.get(1)?,
+                        wins: row.get(2)?,
+                        losses: row.get(3)?,
+                        leaderboard_id: row.get(4)?,
+                        rank: row.get(5)?,
+                    })
+                },
+            )
+            .map_err(|e| format!("Failed to load rating history: {e}"))?;
+        let mut points: Vec<RatingPoint> = Vec::new();
+        for point in rows.flatten() {
+            // Ranked points sort first within a snapshot.
+            if points.last().map(|p| p.taken_at_ms) != Some(point.taken_at_ms) {
+                points.push(point);
+            }
+        }
+        Ok(points)
+    }
+
+    /// How ranks on `leaderboard_id` changed from the first snapshot at or
+    /// after `from_ms` to the last one at or before `to_ms`; `None` without
+    /// snapshots in between.
+    pub fn rank_movements(
+        &self,
+        leaderboard_id: u32,
+        from_ms: u64,
+        to_ms: u64,
+    ) -> Result<Option<RankMovements>, String> {
+        let conn = self.conn.lock().unwrap();
+        let Some(((from_id, from_taken), (to_id, to_taken))) =
+            snapshot_range(&conn, leaderboard_id, from_ms, to_ms)?
+        else {
+            return Ok(None);
+        };
+        let before = snapshot_entries(&conn, from_id)?;
+        let after = snapshot_entries(&conn, to_id)?;
+
+        let same = |a: &RankedToon, b: &RankedToon| {
+            a.gateway == b.gateway && a.toon.eq_ignore_ascii_case(&b.toon)
+        };
+        let mut movements: Vec<RankMovement> = after
+            .iter()
+            .map(|now| {
+                let then = before.iter().find(|b| same(b, now));
+                RankMovement {
+                    toon: now.toon.clone(),
+                    gateway: now.gateway,
+                    from_rank: then.map(|t| t.rank),
+                    to_rank: Some(now.rank),
+                    from_points: then.map(|t| t.points),
+                    to_points: Some(now.points),
+                }
+            })
+            .collect();
+        movements.extend(
+            before
+                .iter()
+                .filter(|then| !after.iter().any(|a| same(a, then)))
+                .map(|then| RankMovement {
+                    toon: then.toon.clone(),
+                    gateway: then.gateway,
+                    from_rank: Some(then.rank),
+                    to_rank: None,
+                    from_points: Some(then.points),
+                    to_points: None,
+                }),
+        );
+        Ok(Some(RankMovements {
+            from_taken_at_ms: from_taken,
+            to_taken_at_ms: to_taken,
+            movements,
+        }))
+    }
+
+    /// Who entered and left the top `n` of `leaderboard_id` between the same
+    /// snapshots as `rank_movements`.
+    pub fn top_churn(
+        &self,
+        leaderboard_id: u32,
+        n: u32,
+        from_ms: u64,
+        to_ms: u64,
+    ) -> Result<Option<TopChurn>, String> {
+        let conn = self.conn.lock().unwrap();
+        let Some(((from_id, from_taken), (to_id, to_taken))) =
+            snapshot_range(&conn, leaderboard_id, from_ms, to_ms)?
+        else {
+            return Ok(None);
+        };
+        let top = |id: i64| -> Result<Vec<RankedToon>, String> {
+            Ok(snapshot_entries(&conn, id)?
+                .into_iter()
+                .filter(|e| e.rank <= n)
+                .collect())
+        };
+        let (before, after) = (top(from_id)?, top(to_id)?);
+        let contains = |list: &[RankedToon], toon: &RankedToon| {
+            list.iter()
+                .any(|e| e.gateway == toon.gateway && e.toon.eq_ignore_ascii_case(&toon.toon))
+        };
+        let entered: Vec<RankedToon> = after
+            .iter()
+            .filter(|e| !contains(&before, e))
+            .cloned()
+            .collect();
+        let left: Vec<RankedToon> = before
+            .iter()
+            .filter(|e| !contains(&after, e))
+            .cloned()
+            .collect();
+        Ok(Some(TopChurn {
+            from_taken_at_ms: from_taken,
+            to_taken_at_ms: to_taken,
+            stayed: (after.len() - entered.len()) as u32,
+            entered,
+            left,
+        }))
+    }
+
+    /// Records that `aurora_id` used each of `toons` at `seen_at_ms`,
+    /// widening the period each was seen over.
+    pub fn observe_toons(
+        &self,
+        aurora_id: u64,
+        toons: &[(String, u16)],
+        seen_at_ms: u64,
+    ) -> Result<(), String> {
+        let db_err = |e: rusqlite::Error| format!("Failed to record toon names: {e}");
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        for (toon, gateway) in toons.iter().filter(|(toon, _)| !toon.is_empty()) {
+            tx.execute(
+                "INSERT INTO toon_observations
+                    (aurora_id, toon, gateway, first_seen_ms, last_seen_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?4)
+                 ON CONFLICT (aurora_id, toon, gateway) DO UPDATE SET
+                    first_seen_ms = min(first_seen_ms, excluded.first_seen_ms),
+                    last_seen_ms = max(last_seen_ms, excluded.last_seen_ms)",
+                params![aurora_id as i64, toon, gateway, seen_at_ms as i64],
+            )
+            .map_err(db_err)?;
+        }
+        tx.commit().map_err(db_err)
+    }
+
+    /// Records the toons `profile`, fetched by `request`, shows its account
+    /// using: the one asked for and those with ladder stats.
+    pub fn observe_profile(
+        &self,
+        request: &ToonRequest,
+        profile: &AuroraProfile,
+        seen_at_ms: u64,
+    ) -> Result<(), String> {
+        if profile.aurora_id == 0 {
+            return Ok(());
+        }
+        let toons: Vec<(String, u16)> = std::iter::once((request.toon.clone(), request.gateway))
+            .chain(
+                profile
+                    .matchmaked_stats
+                    .iter()
+                    .map(|s| (s.toon.clone(), s.gateway_id)),
+            )
+            .collect();
+        self.observe_toons(profile.aurora_id, &toons, seen_at_ms)
+    }
+
+    /// Records sightings of known names among `names` in a replay played
+    /// at `played_at_ms`. Replays carry neither gateway nor account, so
+    /// names the web API never reported are skipped, and a name is credited
+    /// to the account whose reported period lies closest to the game.
+    pub fn observe_replay_names(&self, names: &[String], played_at_ms: u64) -> Result<(), String> {
+        let db_err = |e: rusqlite::Error| format!("Failed to record replay names: {e}");
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        for name in names {
+            tx.execute(
+                "INSERT INTO replay_sightings
+                    (aurora_id, toon, gateway, first_seen_ms, last_seen_ms)
+                 SELECT aurora_id, toon, gateway, ?2, ?2 FROM toon_observations
+                 WHERE toon = ?1
+                 ORDER BY max(first_seen_ms - ?2, ?2 - last_seen_ms, 0), last_seen_ms DESC
+                 LIMIT 1
+                 ON CONFLICT (aurora_id, toon, gateway) DO UPDATE SET
+                    first_seen_ms = min(first_seen_ms, excluded.first_seen_ms),
+                    last_seen_ms = max(last_seen_ms, excluded.last_seen_ms)",
+                params![name, played_at_ms as i64],
+            )
+            .map_err(db_err)?;
+        }
+        tx.commit().map_err(db_err)
+    }
+
+    /// Every name `aurora_id` was seen using, most recently seen first.
+    pub fn toon_aliases(&self, aurora_id: u64) -> Result<Vec<ToonAlias>, String> {
+        let conn = self.conn.lock().unwrap();
+        aliases_of(&conn, aurora_id)
+    }
+
+    /// The account that used `toon`, on `gateway` if given, around
+    /// `at_ms`; without a time, the one that used it last. Replay sightings
+    /// count alongside the API's periods. `None` if no account was seen
+    /// using it.
+    pub fn resolve_toon(
+        &self,
+        toon: &str,
+        gateway: Option<u16>,
+        at_ms: Option<u64>,
+    ) -> Result<Option<ResolvedToon>, String> {
+        let conn = self.conn.lock().unwrap();
+        let aurora_id = conn
+            .query_row(
+                "SELECT aurora_id FROM (
+                    SELECT aurora_id, toon, gateway, first_seen_ms, last_seen_ms
+                    FROM toon_observations
+                    UNION ALL
+                    SELECT aurora_id, toon, gateway, first_seen_ms, last_seen_ms
+                    FROM replay_sightings
+                 )
+                 WHERE toon = ?1 COLLATE NOCASE AND (?2 IS NULL OR gateway = ?2)
+                 ORDER BY CASE WHEN ?3 IS NULL THEN 0
+                          ELSE max(first_seen_ms - ?3, ?3 - last_seen_ms, 0) END,
+                          last_seen_ms DESC
+                 LIMIT 1",
+                params![toon, gateway, at_ms.map(|ms| ms as i64)],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to resolve toon: {e}"))?;
+        let Some(aurora_id) = aurora_id.map(|id| id as u64) else {
+            return Ok(None);
+        };
+        let aliases = aliases_of(&conn, aurora_id)?;
+        Ok(aliases.first().cloned().map(|current| ResolvedToon {
+            aurora_id,
+            current,
+            aliases,
+        }))
+    }
+
+    /// `toon` and every other name of the accounts seen using it, for
+    /// finding replays played under names since changed.
+    pub fn names_of(&self, toon: &str, gateway: Option<u16>) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT DISTINCT toon FROM toon_observations WHERE aurora_id IN (
+                    SELECT aurora_id FROM toon_observations
+                    WHERE toon = ?1 AND (?2 IS NULL OR gateway = ?2)
+                 )",
+            )
+            .map_err(|e| format!("Failed to load toon names: {e}"))?;
+        let rows = stmt
+            .query_map(params![toon, gateway], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to load toon names: {e}"))?;
+        let mut names = vec![toon.to_string()];
+        for name in rows.flatten() {
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+}
+
+fn aliases_of(conn: &Connection, aurora_id: u64) -> Result<Vec<ToonAlias>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT o.toon, o.gateway, o.first_seen_ms, o.last_seen_ms,
+                    r.first_seen_ms, r.last_seen_ms
+             FROM toon_observations o
+             LEFT JOIN replay_sightings r
+                ON r.aurora_id = o.aurora_id AND r.toon = o.toon AND r.gateway = o.gateway
+             WHERE o.aurora_id = ?1 ORDER BY o.last_seen_ms DESC, o.first_seen_ms DESC",
+        )
+        .map_err(|e| format!("Failed to load toon names: {e}"))?;
+    let rows = stmt
+        .query_map(params![aurora_id as i64], |row| {
+            Ok(ToonAlias {
+                aurora_id,
+                toon: row.get(0)?,
+                gateway: row.get(1)?,
+                first_seen_ms: row.get::<_, i64>(2)? as u64,
+                last_seen_ms: row.get::<_, i64>(3)? as u64,
+                replay_first_seen_ms: row.get::<_, Option<i64>>(4)?.map(|ms| ms as u64),
+                replay_last_seen_ms: row.get::<_, Option<i64>>(5)?.map(|ms| ms as u64),
+            })
+        })
+        .map_err(|e| format!("Failed to load toon names: {e}"))?;
+    Ok(rows.flatten().collect())
+}
+
+/// Id and time of a leaderboard snapshot.
+type SnapshotRef = (i64, u64);
+
+/// The first snapshot at or after `from_ms` and the last at or before
+/// `to_ms`.
+fn snapshot_range(
+    conn: &Connection,
+    leaderboard_id: u32,
+    from_ms: u64,
+    to_ms: u64,
+) -> Result<Option<(SnapshotRef, SnapshotRef)>, String> {
+    let find = |order: &str| -> Result<Option<SnapshotRef>, String> {
+        conn.query_row(
+            &format!(
+                "SELECT id, taken_at_ms FROM leaderboard_snapshots
+                 WHERE leaderboard_id = ?1 AND taken_at_ms >= ?2 AND taken_at_ms <= ?3
+                 ORDER BY taken_at_ms {order} LIMIT 1"
+            ),
+            params![leaderboard_id, from_ms as i64, to_ms as i64],
+            |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to load leaderboard snapshots: {e}"))
+    };
+    Ok(find("ASC")?.zip(find("DESC")?))
+}
+
+fn snapshot_entries(conn: &Connection, snapshot_id: i64) -> Result<Vec<RankedToon>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT toon, gateway, rank, points FROM leaderboard_entries
+             WHERE snapshot_id = ?1 ORDER BY rank",
+        )
+        .map_err(|e| format!("Failed to load leaderboard snapshot: {e}"))?;
+    let rows = stmt
+        .query_map(params![snapshot_id], |row| {
+            Ok(RankedToon {
+                toon: row.get(0)?,
+                gateway: row.get(1)?,
+                rank: row.get(2)?,
+                points: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Failed to load leaderboard snapshot: {e}"))?;
+    Ok(rows.flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_old_names_to_the_account() {
+        let history = LadderHistory::open_in_memory().unwrap();
+        let toons = |names: &[&str]| -> Vec<(String, u16)> {
+            names.iter().map(|n| (n.to_string(), 30)).collect()
+        };
+        history.observe_toons(1, &toons(&["Bisu"]), 1_000).unwrap();
+        history.observe_toons(1, &toons(&["Bisu"]), 2_000).unwrap();
+        history
+            .observe_toons(1, &toons(&["Revival"]), 5_000)
+            .unwrap();
+        // Someone else picked up the old name later.
+        history.observe_toons(2, &toons(&["Bisu"]), 9_000).unwrap();
+        history
+            .observe_replay_names(&["bisu".to_string(), "Unknown".to_string()], 500)
+            .unwrap();
+        history
+            .observe_replay_names(&["Bisu".to_string()], 800)
+            .unwrap();
+
+        // The sightings don't stretch what the API reported.
+        let names = history
+            .toon_aliases(1)
+            .unwrap()
+            .into_iter()
+            .map(|a| {
+                let (first, last) = (a.replay_first_seen_ms, a.replay_last_seen_ms);
+                (a.toon, a.first_seen_ms, a.last_seen_ms, first, last)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                ("Revival".to_string(), 5_000, 5_000, None, None),
+                ("Bisu".to_string(), 1_000, 2_000, Some(500), Some(800))
+            ]
+        );
+        assert_eq!(
+            history.toon_aliases(2).unwrap()[0].replay_first_seen_ms,
+            None
+        );
+
+        let old = history
+            .resolve_toon("BISU", Some(30), Some(1_500))
+            .unwrap()
+            .unwrap();
+        assert_eq!((old.aurora_id, old.current.toon.as_str()), (1, "Revival"));
+        // Replay sightings count when resolving.
+        let sighted = history
+            .resolve_toon("Bisu", Some(30), Some(600))
+            .unwrap()
+            .unwrap();
+        assert_eq!(sighted.aurora_id, 1);
+        let latest = history.resolve_toon("Bisu", None, None).unwrap().unwrap();
+        assert_eq!(latest.aurora_id, 2);
+        assert!(history
+            .resolve_toon("Bisu", Some(10), None)
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            history.names_of("Revival", None).unwrap(),
+            vec!["Revival", "Bisu"]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! Periodic snapshots of selected leaderboards and of the saved players'
+//! ratings into `LadderHistory`, since the web API only ever shows the ladder
+//! as it is now. Snapshots are taken hourly while the game is running, and
+//! only once configured from the frontend settings.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+
+use crate::{
+    clock,
+    ladder_history::{LadderHistory, RatingSample},
+    saved_player_tracker::load_saved_players,
+    sc_api::{LeaderboardRequest, ScApiClient, ToonRequest},
+    session::{current_season, LADDER_1V1},
+};
+
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often to check whether a snapshot is due.
+pub const SNAPSHOT_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Leaderboard rows fetched per request.
+const PAGE_LENGTH: u32 = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LadderSnapshotConfig {
+    /// Leaderboards to snapshot, e.g. the current season's global 1v1.
+    pub leaderboard_ids: Vec<u32>,
+    /// Rows kept of each leaderboard.
+    #[serde(default = "default_top_n")]
+    pub top_n: u32,
+}
+
+fn default_top_n() -> u32 {
+    100
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotSummary {
+    pub taken_at_ms: u64,
+    pub leaderboards: u32,
+    pub entries: u32,
+    pub ratings: u32,
+}
+
+pub struct LadderSnapshotter {
+    api: Arc<ScApiClient>,
+    history: Arc<LadderHistory>,
+    players_path: PathBuf,
+    config: Mutex<Option<LadderSnapshotConfig>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl LadderSnapshotter {
+    pub fn new(
+        api: Arc<ScApiClient>,
+        history: Arc<LadderHistory>,
+        players_path: PathBuf,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            api,
+            history,
+            players_path,
+            config: Mutex::new(None),
+            last_error: Mutex::new(None),
+        })
+    }
+
+    pub fn config(&self) -> Option<LadderSnapshotConfig> {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// `None` stops taking snapshots.
+    pub fn configure(&self, config: Option<LadderSnapshotConfig>) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    /// Takes a snapshot whenever one is due, checking every `interval`
+    /// until the returned task is aborted.
+    pub fn start(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let snapshotter = self.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let result = snapshotter.snapshot_if_due().await;
+                snapshotter.log_error(result.err());
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// Logs a failed snapshot once rather than on every attempt until it
+    /// clears.
+    fn log_error(&self, error: Option<String>) {
+        let mut last_error = self.last_error.lock().unwrap();
+        if let Some(e) = error.as_ref().filter(|e| last_error.as_ref() != Some(*e)) {
+            println!("[ladder-snapshots] Failed to take snapshot: {e}");
+        }
+        *last_error = error;
+    }
+
+    async fn snapshot_if_due(&self) -> Result<(), String> {
+        if self.config().is_none() || !self.api.is_available() {
+            return Ok(());
+        }
+        let now = clock::now_ms();
+        let due = match self.with_history(|h| h.latest_snapshot_ms()).await? {
+            Some(last) => now.saturating_sub(last) >= SNAPSHOT_INTERVAL.as_millis() as u64,
+            None => true,
+        };
+        if due {
+            self.snapshot(now).await?;
+        }
+        Ok(())
+    }
+
+    /// Snapshots the configured leaderboards and the saved players' ratings
+    /// as of `taken_at_ms`. Does nothing while unconfigured.
+    pub async fn snapshot(&self, taken_at_ms: u64) -> Result<SnapshotSummary, String> {
+        let mut summary = SnapshotSummary {
+            taken_at_ms,
+            ..SnapshotSummary::default()
+        };
+        let Some(config) = self.config() else {
+            return Ok(summary);
+        };
+
+        for &leaderboard_id in &config.leaderboard_ids {
+            let mut entries = Vec::new();
+            while (entries.len() as u32) < config.top_n {
+                let page = self
+                    .api
+                    .leaderboard(&LeaderboardRequest {
+                        leaderboard_id,
+                        offset: entries.len() as u32,
+                        length: PAGE_LENGTH.min(config.top_n - entries.len() as u32),
+                    })
+                    .await?;
+                if page.entries.is_empty() {
+                    break;
+                }
+                entries.extend(page.entries);
+            }
+            entries.truncate(config.top_n as usize);
+            summary.leaderboards += 1;
+            summary.entries += entries.len() as u32;
+            self.with_history(move |h| h.record_leaderboard(leaderboard_id, taken_at_ms, &entries))
+                .await?;
+        }
+
+        let players = load_saved_players(&self.players_path);
+        if players.iter().all(|p| p.profiles.is_empty()) {
+            return Ok(summary);
+        }
+        let leaderboards = self.api.leaderboards().await?;
+        let season = current_season(&leaderboards)
+            .ok_or("No 1v1 leaderboard found to determine the season".to_string())?;
+        let mut samples = Vec::new();
+        for profile in players.iter().flat_map(|p| &p.profiles) {
+            let request = ToonRequest {
+                toon: profile.toon.clone(),
+                gateway: profile.gateway,
+            };
+            let stats = match self.api.player_stats(&request, LADDER_1V1, season).await {
+                Ok(stats) => stats,
+                Err(e) => {
+                    println!(
+                        "[ladder-snapshots] Failed to fetch the rating of {}: {e}",
+                        profile.toon
+                    );
+                    continue;
+                }
+            };
+            let stat = stats.into_iter().find(|s| {
+                s.toon.eq_ignore_ascii_case(&profile.toon) && s.gateway_id == profile.gateway
+            });
+            if let Some(stat) = stat {
+                samples.push(RatingSample {
+                    toon: stat.toon,
+                    gateway: stat.gateway_id,
+                    points: stat.points,
+                    wins: stat.wins,
+                    losses: stat.losses,
+                });
+            }
+        }
+        summary.ratings = samples.len() as u32;
+        self.with_history(move |h| h.record_ratings(taken_at_ms, &samples))
+            .await?;
+        Ok(summary)
+    }
+
+    /// Runs `f` on the history off the async runtime, as it blocks on the
+    /// database.
+    async fn with_history<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&LadderHistory) -> Result<T, String> + Send + 'static,
+    ) -> Result<T, String> {
+        let history = self.history.clone();
+        tauri::async_runtime::spawn_blocking(move || f(&history))
+            .await
+            .map_err(|e| format!("Failed to access ladder history: {e}"))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_dir::TestDir,
+        test_server::{api_client, TestResponse, TestServer},
+    };
+    use std::{
+        fs,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    #[test]
+    fn snapshots_leaderboards_and_saved_players() {
+        let dir = TestDir::new("ladder-snapshots");
+        let players_path = dir.join("saved_players.json");
+        fs::write(
+            &players_path,
+            r#"[{"auroraId": 7, "alias": "Flash", "profiles": [{"toon": "Flash", "gateway": 30}]}]"#,
+        )
+        .unwrap();
+
+        // A week later Flash has passed Jaedong, Bisu has dropped out of the
+        // top 3 and Light has come in.
+        let later = Arc::new(AtomicBool::new(false));
+        let is_later = later.clone();
+        let server = TestServer::start(move |req| {
+            let path = req.path.as_str();
+            let later = is_later.load(Ordering::SeqCst);
+            let body = if path == "/web-api/v1/leaderboard" {
+                r#"{"leaderboards": {"12": {"id": 12, "gamemode_id": 1, "season_id": 17}}}"#
+                    .to_string()
+            } else if path.starts_with("/web-api/v1/leaderboard/12?") {
+                let rows = if later {
+                    r#"[[1, "Flash", 30, 2600], [2, "Jaedong", 10, 2500], [3, "Light", 30, 2400],
+                        [4, "Bisu", 30, 2300]]"#
+                } else {
+                    r#"[[1, "Jaedong", 10, 2500], [2, "Flash", 30, 2450], [3, "Bisu", 30, 2400],
+                        [4, "Light", 30, 2300]]"#
+                };
+                format!(
+                    r#"{{"columns": ["rank", "toon", "gateway_id", "points"], "rows": {rows}}}"#
+                )
+            } else if path == "/web-api/v1/matchmaker-player-stat-by-toon/Flash/30/1/17" {
+                let points = if later { 2600 } else { 2450 };
+                format!(
+                    r#"[{{"toon": "Flash", "gateway_id": 30, "points": {points}, "wins": 10, "losses": 2}}]"#
+                )
+            } else {
+                return TestResponse::status(404);
+            };
+            TestResponse::ok(body.into_bytes())
+        });
+
+        let api = api_client(&server);
+        let history = Arc::new(LadderHistory::open_in_memory().unwrap());
+        let snapshotter = LadderSnapshotter::new(api, history.clone(), players_path);
+
+        let snapshot = |at: u64| tauri::async_runtime::block_on(snapshotter.snapshot(at)).unwrap();
+        assert_eq!(snapshot(1_000).leaderboards, 0);
+        snapshotter.configure(Some(LadderSnapshotConfig {
+            leaderboard_ids: vec![12],
+            top_n: 3,
+        }));
+        let first = snapshot(1_000);
+        assert_eq!((first.entries, first.ratings), (3, 1));
+        // Ratings alone, off the leaderboard schedule.
+        history
+            .record_ratings(
+                5_000,
+                &[RatingSample {
+                    toon: "Flash".to_string(),
+                    gateway: 30,
+                    points: 2500,
+                    wins: 11,
+                    losses: 2,
+                }],
+            )
+            .unwrap();
+        later.store(true, Ordering::SeqCst);
+        snapshot(10_000);
+        assert_eq!(history.latest_snapshot_ms().unwrap(), Some(10_000));
+
+        let series: Vec<(u64, i64, Option<u32>)> = history
+            .rating_history("flash", 30, None, None)
+            .unwrap()
+            .iter()
+            .map(|p| (p.taken_at_ms, p.points, p.rank))
+            .collect();
+        assert_eq!(
+            series,
+            vec![
+                (1_000, 2450, Some(2)),
+                (5_000, 2500, None),
+                (10_000, 2600, Some(1))
+            ]
+        );
+        assert_eq!(
+            history
+                .rating_history("Flash", 30, Some(2_000), Some(10_000))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let movements = history.rank_movements(12, 0, 20_000).unwrap().unwrap();
+        assert_eq!(
+            (movements.from_taken_at_ms, movements.to_taken_at_ms),
+            (1_000, 10_000)
+        );
+        let ranks: Vec<(&str, Option<u32>, Option<u32>)> = movements
+            .movements
+            .iter()
+            .map(|m| (m.toon.as_str(), m.from_rank, m.to_rank))
+            .collect();
+        assert_eq!(
+            ranks,
+            vec![
+                ("Flash", Some(2), Some(1)),
+                ("Jaedong", Some(1), Some(2)),
+                ("Light", None, Some(3)),
+                ("Bisu", Some(3), None),
+            ]
+        );
+
+        let churn = history.top_churn(12, 2, 0, 20_000).unwrap().unwrap();
+        assert_eq!(
+            (churn.entered.len(), churn.left.len(), churn.stayed),
+            (0, 0, 2)
+        );
+        let churn = history.top_churn(12, 3, 0, 20_000).unwrap().unwrap();
+        assert_eq!(churn.entered[0].toon, "Light");
+        assert_eq!(churn.left[0].toon, "Bisu");
+        assert_eq!(churn.stayed, 2);
+        assert!(history.top_churn(12, 3, 20_000, 30_000).unwrap().is_none());
+    }
+}
@@ -2,40 +2,451 @@
 
 use tauri::Emitter;
 mod cache;
+mod calendar;
+mod clock;
+mod download_queue;
+mod downloads;
+mod filename_template;
+mod http;
+mod ladder_history;
+mod ladder_snapshots;
+mod map_extract;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock_sc_api;
+mod mpq;
+mod openings;
+mod persist;
+mod player_stats;
+#[cfg(target_os = "linux")]
+mod procfs;
+mod rate_limit;
+mod reference_builds;
+mod replay_library;
 mod replay_parser;
+mod replay_search;
+mod replay_watcher;
+mod saved_player_tracker;
+mod sc_api;
+mod scouting;
 mod scr_events;
 mod scr_process;
+mod session;
+#[cfg(test)]
+mod test_dir;
+#[cfg(any(test, feature = "mock"))]
+pub mod test_server;
 
 use std::fs;
-use std::path::Path;
-use std::sync::{atomic::AtomicBool, Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use cache::ReplayCache;
+use download_queue::{DownloadJob, DownloadQueue, DownloadQueueEvent, NewDownloadJob};
+use downloads::Downloader;
+use filename_template::FilenameTemplate;
+use http::{DownloadProgress, HttpClient, HttpClientConfig};
+use ladder_history::{
+    LadderHistory, RankMovements, RatingPoint, ResolvedToon, SavedPlayerMatch, ToonAlias, TopChurn,
+};
+use ladder_snapshots::{
+    LadderSnapshotConfig, LadderSnapshotter, SnapshotSummary, SNAPSHOT_CHECK_INTERVAL,
+};
+use openings::{OpeningBook, OpeningDefinition};
+use player_stats::{PlayerStats, StatsFilter};
+#[cfg(target_os = "linux")]
+use procfs::ProcfsProbe;
+use rate_limit::{RateLimitConfig, RateLimitCounters, RateLimiter};
+use reference_builds::{BuildComparison, ReferenceBuild, ReferenceBuildStore};
+use replay_library::{LibraryReplay, ReplayLibrary, RescanSummary};
 use replay_parser::ReplayParser;
-use scr_events::ScrProcessEventProvider;
+use replay_search::{ChatHit, ChatSearch, SearchResults};
+use replay_watcher::{ReplayWatcher, ReplayWatcherConfig};
+use saved_player_tracker::{SavedPlayerTracker, SAVED_PLAYER_POLL_INTERVAL};
+use sc_api::{
+    AuroraProfile, LeaderboardPage, LeaderboardRequest, Leaderboards, MapStats,
+    MatchHistoryRequest, MatchmakerGame, MatchmakerPlayerStat, ProfileFlags, ScApiClient,
+    ToonRequest,
+};
+use scouting::{ScoutTarget, ScoutingReport};
+use scr_events::{ScrEvent, ScrProcessEventProvider, ScrSnapshot};
+use scr_process::ProcessProbe;
+#[cfg(not(target_os = "linux"))]
+use scr_process::SystemProbe;
+use session::{Session, SessionMonitor, SESSION_POLL_INTERVAL};
 use tauri::path::BaseDirectory;
+use tauri::AppHandle;
 use tauri::Manager;
 use tauri::State;
-use tauri::Window;
 
-static INITIALIZED: AtomicBool = AtomicBool::new(false);
+/// Starts the process monitor. Safe to call again, e.g. after a frontend
+/// reload or a `stop_process_monitor`.
+#[tauri::command]
+fn init_process(provider: State<'_, Arc<ScrProcessEventProvider>>) {
+    provider.start();
+}
 
 #[tauri::command]
-fn init_process(window: Window) {
-    if INITIALIZED.load(std::sync::atomic::Ordering::Relaxed) {
-        return;
+fn stop_process_monitor(provider: State<'_, Arc<ScrProcessEventProvider>>) {
+    provider.stop();
+}
+
+#[tauri::command]
+fn set_process_poll_interval(
+    interval_ms: u64,
+    provider: State<'_, Arc<ScrProcessEventProvider>>,
+) -> Result<(), String> {
+    if interval_ms == 0 {
+        return Err("Poll interval must be greater than zero".to_string());
     }
+    provider.set_poll_interval(Duration::from_millis(interval_ms));
+    Ok(())
+}
 
-    INITIALIZED.store(true, std::sync::atomic::Ordering::Relaxed);
+#[tauri::command]
+fn get_scr_snapshot(provider: State<'_, Arc<ScrProcessEventProvider>>) -> ScrSnapshot {
+    provider.snapshot()
+}
+
+/// The followed account and its in-progress ladder game, if any.
+#[tauri::command]
+fn get_session(monitor: State<'_, Arc<SessionMonitor>>) -> Session {
+    monitor.session()
+}
+
+/// Follows `account`'s ladder games while the game runs; `None` stops.
+#[tauri::command]
+fn configure_session_account(
+    account: Option<ToonRequest>,
+    monitor: State<'_, Arc<SessionMonitor>>,
+) {
+    monitor.set_account(account)
+}
+
+/// Starts watching the StarCraft replay folder; `None` stops watching.
+#[tauri::command]
+fn configure_replay_watcher(
+    config: Option<ReplayWatcherConfig>,
+    watcher: State<'_, Arc<ReplayWatcher>>,
+) -> Result<(), String> {
+    watcher.configure(config)
+}
+
+#[tauri::command]
+fn get_replay_watcher_config(
+    watcher: State<'_, Arc<ReplayWatcher>>,
+) -> Option<ReplayWatcherConfig> {
+    watcher.config()
+}
+
+/// Games in the replay library, newest first.
+#[tauri::command]
+async fn library_list(
+    offset: u32,
+    limit: u32,
+    library: State<'_, Arc<ReplayLibrary>>,
+) -> Result<Vec<LibraryReplay>, String> {
+    let library = library.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || library.list(offset, limit))
+        .await
+        .map_err(|e| format!("Failed to list replay library: {e}"))?
+}
+
+#[tauri::command]
+async fn library_count(library: State<'_, Arc<ReplayLibrary>>) -> Result<u64, String> {
+    let library = library.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || library.count())
+        .await
+        .map_err(|e| format!("Failed to count replay library: {e}"))?
+}
+
+#[tauri::command]
+async fn library_get(
+    id: i64,
+    library: State<'_, Arc<ReplayLibrary>>,
+) -> Result<Option<LibraryReplay>, String> {
+    let library = library.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || library.get(id))
+        .await
+        .map_err(|e| format!("Failed to get replay: {e}"))?
+}
+
+/// Searches the library with the query syntax in `replay_search`. Player
+/// names also match the other names their account was seen using.
+#[tauri::command]
+async fn library_search(
+    query: String,
+    offset: u32,
+    limit: u32,
+    library: State<'_, Arc<ReplayLibrary>>,
+    history: State<'_, Arc<LadderHistory>>,
+) -> Result<SearchResults, String> {
+    let mut search = replay_search::parse_query(&query)?;
+    let library = library.inner().clone();
+    let history = history.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        search.add_aliases(|name| history.names_of(name, None))?;
+        library.search(&search, offset, limit)
+    })
+    .await
+    .map_err(|e| format!("Failed to search replay library: {e}"))?
+}
+
+/// Full-text search over the chat of every replay in the library.
+#[tauri::command]
+async fn library_search_chat(
+    search: ChatSearch,
+    offset: u32,
+    limit: u32,
+    library: State<'_, Arc<ReplayLibrary>>,
+) -> Result<Vec<ChatHit>, String> {
+    let library = library.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || library.search_chat(&search, offset, limit))
+        .await
+        .map_err(|e| format!("Failed to search replay chat: {e}"))?
+}
+
+/// Win rates, lengths, APM and openings aggregated from the library.
+#[tauri::command]
+async fn player_stats(
+    filter: StatsFilter,
+    library: State<'_, Arc<ReplayLibrary>>,
+) -> Result<PlayerStats, String> {
+    let library = library.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || player_stats::player_stats(&library, &filter))
+        .await
+        .map_err(|e| format!("Failed to aggregate player stats: {e}"))?
+}
+
+/// Matches the saved-player tracker recorded, newest first, of one saved
+/// player or all of them.
+#[tauri::command]
+async fn saved_player_activity(
+    aurora_id: Option<u64>,
+    offset: u32,
+    limit: u32,
+    history: State<'_, Arc<LadderHistory>>,
+) -> Result<Vec<SavedPlayerMatch>, String> {
+    let history = history.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        history.saved_player_matches(aurora_id, offset, limit)
+    })
+    .await
+    .map_err(|e| format!("Failed to list saved player matches: {e}"))?
+}
+
+/// Every name the account was seen using, most recently seen first.
+#[tauri::command]
+async fn toon_aliases(
+    aurora_id: u64,
+    history: State<'_, Arc<LadderHistory>>,
+) -> Result<Vec<ToonAlias>, String> {
+    let history = history.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || history.toon_aliases(aurora_id))
+        .await
+        .map_err(|e| format!("Failed to list toon aliases: {e}"))?
+}
 
-    std::thread::spawn(move || {
-        let window = Mutex::new(Arc::new(window));
-        let mut _listen = ScrProcessEventProvider::new(Arc::new(Mutex::new(move |event| {
-            println!("event: {event:?}");
-            let window = window.lock().unwrap();
-            window.emit("scr-event", event).unwrap();
-        })));
-    });
+/// The account behind `toon`, e.g. a name in an old replay, and the name it
+/// uses now. `at_ms` picks between accounts that used the same name.
+#[tauri::command]
+async fn resolve_toon(
+    toon: String,
+    gateway: Option<u16>,
+    at_ms: Option<u64>,
+    history: State<'_, Arc<LadderHistory>>,
+) -> Result<Option<ResolvedToon>, String> {
+    let history = history.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || history.resolve_toon(&toon, gateway, at_ms))
+        .await
+        .map_err(|e| format!("Failed to resolve toon: {e}"))?
+}
+
+/// Starts snapshotting with `config`; `None` stops.
+#[tauri::command]
+fn configure_ladder_snapshots(
+    config: Option<LadderSnapshotConfig>,
+    snapshotter: State<'_, Arc<LadderSnapshotter>>,
+) {
+    snapshotter.configure(config)
+}
+
+#[tauri::command]
+fn get_ladder_snapshot_config(
+    snapshotter: State<'_, Arc<LadderSnapshotter>>,
+) -> Option<LadderSnapshotConfig> {
+    snapshotter.config()
+}
+
+/// Takes a snapshot now rather than waiting for the schedule.
+#[tauri::command]
+async fn take_ladder_snapshot(
+    snapshotter: State<'_, Arc<LadderSnapshotter>>,
+) -> Result<SnapshotSummary, String> {
+    snapshotter.snapshot(clock::now_ms()).await
+}
+
+/// A toon's rating over time from the snapshots, oldest first.
+#[tauri::command]
+async fn rating_history(
+    toon: String,
+    gateway: u16,
+    after_ms: Option<u64>,
+    before_ms: Option<u64>,
+    history: State<'_, Arc<LadderHistory>>,
+) -> Result<Vec<RatingPoint>, String> {
+    let history = history.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        history.rating_history(&toon, gateway, after_ms, before_ms)
+    })
+    .await
+    .map_err(|e| format!("Failed to get rating history: {e}"))?
+}
+
+#[tauri::command]
+async fn rank_movements(
+    leaderboard_id: u32,
+    from_ms: u64,
+    to_ms: u64,
+    history: State<'_, Arc<LadderHistory>>,
+) -> Result<Option<RankMovements>, String> {
+    let history = history.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        history.rank_movements(leaderboard_id, from_ms, to_ms)
+    })
+    .await
+    .map_err(|e| format!("Failed to compare snapshots: {e}"))?
+}
+
+#[tauri::command]
+async fn top_churn(
+    leaderboard_id: u32,
+    n: u32,
+    from_ms: u64,
+    to_ms: u64,
+    history: State<'_, Arc<LadderHistory>>,
+) -> Result<Option<TopChurn>, String> {
+    let history = history.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        history.top_churn(leaderboard_id, n, from_ms, to_ms)
+    })
+    .await
+    .map_err(|e| format!("Failed to compare snapshots: {e}"))?
+}
+
+/// Scouting report on `toon`, as emitted on `scouting-report` when a match
+/// starts.
+#[tauri::command]
+async fn scout_opponent(
+    toon: String,
+    gateway: u16,
+    map: String,
+    api: State<'_, Arc<ScApiClient>>,
+    library: State<'_, Arc<ReplayLibrary>>,
+    history: State<'_, Arc<LadderHistory>>,
+) -> Result<ScoutingReport, String> {
+    let target = ScoutTarget {
+        toon,
+        gateway_id: gateway,
+        map,
+    };
+    scouting::scout(
+        &api,
+        library.inner().clone(),
+        history.inner().clone(),
+        target,
+    )
+    .await
+}
+
+/// The opening definitions replays are classified with.
+#[tauri::command]
+fn get_openings(library: State<'_, Arc<ReplayLibrary>>) -> Vec<OpeningDefinition> {
+    library.opening_definitions()
+}
+
+/// Re-reads the user's `openings.json` and reclassifies the library.
+#[tauri::command]
+async fn reload_openings(
+    app: AppHandle,
+    library: State<'_, Arc<ReplayLibrary>>,
+) -> Result<Vec<OpeningDefinition>, String> {
+    let path = openings_path(&app)?;
+    let book = OpeningBook::load(&path)?;
+    let library = library.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        library.set_openings(book)?;
+        Ok(library.opening_definitions())
+    })
+    .await
+    .map_err(|e| format!("Failed to reload openings: {e}"))?
+}
+
+fn openings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .resolve("openings.json", BaseDirectory::AppData)
+        .map_err(|e| format!("Failed to resolve openings file: {e}"))
+}
+
+#[tauri::command]
+fn reference_builds_list(store: State<'_, Arc<ReferenceBuildStore>>) -> Vec<ReferenceBuild> {
+    store.list()
+}
+
+/// Adds `build`, or replaces the stored one with its id.
+#[tauri::command]
+fn reference_build_save(
+    build: ReferenceBuild,
+    store: State<'_, Arc<ReferenceBuildStore>>,
+) -> Result<ReferenceBuild, String> {
+    store.save(build)
+}
+
+#[tauri::command]
+fn reference_build_delete(
+    id: u64,
+    store: State<'_, Arc<ReferenceBuildStore>>,
+) -> Result<(), String> {
+    store.delete(id)
+}
+
+/// Compares `player`'s build order in library game `game_id` with
+/// reference build `reference_id`.
+#[tauri::command]
+async fn compare_build(
+    reference_id: u64,
+    game_id: i64,
+    player: String,
+    store: State<'_, Arc<ReferenceBuildStore>>,
+    library: State<'_, Arc<ReplayLibrary>>,
+) -> Result<BuildComparison, String> {
+    let reference = store
+        .get(reference_id)
+        .ok_or_else(|| format!("No reference build with id {reference_id}"))?;
+    let library = library.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let build = library
+            .build_of(game_id, &player)?
+            .ok_or_else(|| format!("{player} didn't play in game {game_id}"))?;
+        Ok(reference_builds::compare(&reference, &build))
+    })
+    .await
+    .map_err(|e| format!("Failed to compare build: {e}"))?
+}
+
+/// Brings the library up to date with `paths` and the replay cache.
+#[tauri::command]
+async fn library_rescan(
+    paths: Vec<String>,
+    library: State<'_, Arc<ReplayLibrary>>,
+    cache: State<'_, Arc<ReplayCache>>,
+) -> Result<RescanSummary, String> {
+    let mut roots: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+    roots.push(cache.dir().to_path_buf());
+    let library = library.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || library.rescan(&roots))
+        .await
+        .map_err(|e| format!("Failed to rescan replay library: {e}"))?
 }
 
 #[tauri::command]
@@ -66,54 +477,189 @@ fn write_settings_file(path: String, content: String) -> Result<(), String> {
     }
 }
 
+fn emit_download_progress(app: &AppHandle, url: &str, received: u64, total: Option<u64>) {
+    let _ = app.emit(
+        "download-progress",
+        DownloadProgress {
+            url: url.to_string(),
+            received,
+            total,
+        },
+    );
+}
+
 #[tauri::command]
 async fn download_file(
+    app: AppHandle,
     url: String,
     destination_path: String,
     filename: String,
-    cache: State<'_, Arc<ReplayCache>>,
+    template: Option<FilenameTemplate>,
+    downloader: State<'_, Arc<Downloader>>,
 ) -> Result<String, String> {
-    use tauri_plugin_http::reqwest;
+    let full_path = downloader
+        .download_to_folder(
+            &url,
+            &destination_path,
+            &filename,
+            template.as_ref(),
+            |received, total| emit_download_progress(&app, &url, received, total),
+        )
+        .await?;
+    Ok(full_path.to_string_lossy().to_string())
+}
 
-    let full_path = Path::new(&destination_path).join(&filename);
-    if let Some(parent) = full_path.parent() {
-        if let Err(e) = fs::create_dir_all(parent) {
-            return Err(format!("Failed to create directory: {e}"));
+/// Writes the map embedded in the replay at `url` to `destination_path`.
+#[tauri::command]
+async fn extract_replay_map(
+    app: AppHandle,
+    url: String,
+    filename: String,
+    destination_path: String,
+    downloader: State<'_, Arc<Downloader>>,
+) -> Result<String, String> {
+    let (bytes, _) = downloader
+        .fetch_replay(&url, &filename, |received, total| {
+            emit_download_progress(&app, &url, received, total)
+        })
+        .await?;
+    let path = map_extract::write_map_from_replay(&bytes, Path::new(&destination_path))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn enqueue_downloads(
+    jobs: Vec<NewDownloadJob>,
+    queue: State<'_, Arc<DownloadQueue>>,
+) -> Vec<DownloadJob> {
+    queue.enqueue(jobs)
+}
+
+#[tauri::command]
+fn list_download_jobs(queue: State<'_, Arc<DownloadQueue>>) -> Vec<DownloadJob> {
+    queue.jobs()
+}
+
+#[tauri::command]
+fn pause_download(id: u64, queue: State<'_, Arc<DownloadQueue>>) -> Result<(), String> {
+    queue.pause(id)
+}
+
+#[tauri::command]
+fn resume_download(id: u64, queue: State<'_, Arc<DownloadQueue>>) -> Result<(), String> {
+    queue.resume(id)
+}
+
+#[tauri::command]
+fn cancel_download(id: u64, queue: State<'_, Arc<DownloadQueue>>) -> Result<(), String> {
+    queue.cancel(id)
+}
+
+#[tauri::command]
+fn clear_finished_downloads(queue: State<'_, Arc<DownloadQueue>>) {
+    queue.clear_finished()
+}
+
+#[tauri::command]
+fn set_download_concurrency(max_concurrent: usize, queue: State<'_, Arc<DownloadQueue>>) {
+    queue.set_max_concurrent(max_concurrent)
+}
+
+#[tauri::command]
+fn get_http_client_config(http: State<'_, Arc<HttpClient>>) -> HttpClientConfig {
+    http.config()
+}
+
+#[tauri::command]
+fn configure_http_client(
+    config: HttpClientConfig,
+    http: State<'_, Arc<HttpClient>>,
+) -> Result<(), String> {
+    http.configure(config)
+}
+
+#[tauri::command]
+fn get_rate_limit_config(limiter: State<'_, Arc<RateLimiter>>) -> RateLimitConfig {
+    limiter.config()
+}
+
+#[tauri::command]
+fn configure_rate_limits(config: RateLimitConfig, limiter: State<'_, Arc<RateLimiter>>) {
+    limiter.configure(config)
+}
+
+#[tauri::command]
+fn get_rate_limit_counters(limiter: State<'_, Arc<RateLimiter>>) -> RateLimitCounters {
+    limiter.counters()
+}
+
+/// Lets the frontend's own fetches share the backend's per-host limits.
+#[tauri::command]
+async fn acquire_request_slot(
+    url: String,
+    limiter: State<'_, Arc<RateLimiter>>,
+) -> Result<(), String> {
+    limiter.acquire(&url).await
+}
+
+#[tauri::command]
+async fn sc_api_aurora_profile(
+    request: ToonRequest,
+    flags: Option<ProfileFlags>,
+    api: State<'_, Arc<ScApiClient>>,
+    history: State<'_, Arc<LadderHistory>>,
+) -> Result<AuroraProfile, String> {
+    let profile = api
+        .aurora_profile(&request, flags.unwrap_or_default())
+        .await?;
+    let history = history.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = history.observe_profile(&request, &profile, clock::now_ms()) {
+            println!("[ladder-history] {e}");
         }
-    }
+        profile
+    })
+    .await
+    .map_err(|e| format!("Failed to record profile names: {e}"))
+}
 
-    if let Some(cached) = cache.get(&url) {
-        println!(
-            "[replay-cache] Using cached file for {} -> {}",
-            url,
-            cached.display()
-        );
-        fs::copy(&cached, &full_path).map_err(|e| format!("Failed to copy from cache: {e}"))?;
-        return Ok(full_path.to_string_lossy().to_string());
-    } else {
-        println!("[replay-cache] No cache for {url}, downloading");
-    }
+#[tauri::command]
+async fn sc_api_leaderboards(api: State<'_, Arc<ScApiClient>>) -> Result<Leaderboards, String> {
+    api.leaderboards().await
+}
 
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to download file: {e}"))?;
-    if !response.status().is_success() {
-        return Err(format!(
-            "Download failed with status: {}",
-            response.status()
-        ));
-    }
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read response: {e}"))?;
+#[tauri::command]
+async fn sc_api_leaderboard(
+    request: LeaderboardRequest,
+    api: State<'_, Arc<ScApiClient>>,
+) -> Result<LeaderboardPage, String> {
+    api.leaderboard(&request).await
+}
 
-    fs::write(&full_path, &bytes).map_err(|e| format!("Failed to write file: {e}"))?;
-    let _ = cache.put(&url, &filename, &bytes);
-    Ok(full_path.to_string_lossy().to_string())
+#[tauri::command]
+async fn sc_api_match_history(
+    request: MatchHistoryRequest,
+    api: State<'_, Arc<ScApiClient>>,
+) -> Result<Vec<MatchmakerGame>, String> {
+    api.match_history(&request).await
+}
+
+#[tauri::command]
+async fn sc_api_player_stats(
+    request: ToonRequest,
+    gamemode: u32,
+    season: u32,
+    api: State<'_, Arc<ScApiClient>>,
+) -> Result<Vec<MatchmakerPlayerStat>, String> {
+    api.player_stats(&request, gamemode, season).await
+}
+
+#[tauri::command]
+async fn sc_api_map_stats(
+    request: ToonRequest,
+    api: State<'_, Arc<ScApiClient>>,
+) -> Result<MapStats, String> {
+    api.map_stats(&request).await
 }
 
 #[derive(serde::Serialize)]
@@ -164,44 +710,16 @@ fn parse_replay_bytes(bytes: &[u8]) -> Result<(u32, u64, Vec<ParsedChatMessage>)
 
 #[tauri::command]
 async fn download_and_parse_replay(
+    app: AppHandle,
     url: String,
     filename: String,
-    cache: State<'_, Arc<ReplayCache>>,
+    downloader: State<'_, Arc<Downloader>>,
 ) -> Result<DownloadAndParseReplayResponse, String> {
-    use tauri_plugin_http::reqwest;
-
-    // Acquire bytes from cache or network
-    let cached_path = cache.get(&url);
-    let cached = cached_path.is_some();
-    let bytes: Vec<u8> = if let Some(ref cached_path) = cached_path {
-        println!(
-            "[replay-cache] Parse using cached file for {} -> {}",
-            url,
-            cached_path.display()
-        );
-        fs::read(cached_path).map_err(|e| format!("Failed to read cached file: {e}"))?
-    } else {
-        println!("[replay-cache] No cache for {url}, downloading for parse");
-        let client = reqwest::Client::new();
-        let response = client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to download file: {e}"))?;
-        if !response.status().is_success() {
-            return Err(format!(
-                "Download failed with status: {}",
-                response.status()
-            ));
-        }
-        let b = response
-            .bytes()
-            .await
-            .map_err(|e| format!("Failed to read response: {e}"))?;
-        let vec = b.to_vec();
-        let _ = cache.put(&url, &filename, &vec);
-        vec
-    };
+    let (bytes, cached) = downloader
+        .fetch_replay(&url, &filename, |received, total| {
+            emit_download_progress(&app, &url, received, total)
+        })
+        .await?;
 
     let (duration_ms, start_time_ms, chat_messages) = parse_replay_bytes(&bytes)?;
     Ok(DownloadAndParseReplayResponse {
@@ -228,15 +746,233 @@ pub fn run() {
                     p
                 });
             let cache = Arc::new(ReplayCache::new(cache_dir, 1000));
-            app.manage(cache);
+            app.manage(cache.clone());
+
+            let emitter = app_handle.clone();
+            let limiter = Arc::new(RateLimiter::new(
+                RateLimitConfig::default(),
+                move |counters| {
+                    let _ = emitter.emit("rate-limit-counters", counters);
+                },
+            ));
+            app.manage(limiter.clone());
+
+            let http = Arc::new(HttpClient::new(HttpClientConfig::default(), limiter)?);
+            app.manage(http.clone());
+
+            let quarantine_dir = app_handle
+                .path()
+                .resolve("quarantine", BaseDirectory::AppData)?;
+            let partial_dir = app_handle
+                .path()
+                .resolve("partial-downloads", BaseDirectory::AppData)?;
+            let library_path = app_handle
+                .path()
+                .resolve("replay-library.sqlite3", BaseDirectory::AppData)?;
+            let library = Arc::new(ReplayLibrary::open(&library_path)?);
+            app.manage(library.clone());
+            let openings = openings_path(app_handle)?;
+            let opening_library = library.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let book = OpeningBook::load(&openings).unwrap_or_else(|e| {
+                    println!("[openings] {e}; using the built-in definitions");
+                    OpeningBook::default()
+                });
+                if let Err(e) = opening_library.set_openings(book) {
+                    println!("[openings] {e}");
+                }
+            });
+
+            let reference_builds_path = app_handle
+                .path()
+                .resolve("reference-builds.json", BaseDirectory::AppData)?;
+            app.manage(Arc::new(ReferenceBuildStore::new(reference_builds_path)));
+
+            let downloader = Arc::new(Downloader::new(
+                http.clone(),
+                cache,
+                library.clone(),
+                quarantine_dir,
+                partial_dir,
+            ));
+            app.manage(downloader.clone());
+
+            let queue_path = app_handle
+                .path()
+                .resolve("download-queue.json", BaseDirectory::AppData)?;
+            let emitter = app_handle.clone();
+            let queue = DownloadQueue::new(queue_path, 3, downloader, move |event| {
+                let _ = match event {
+                    DownloadQueueEvent::JobUpdated(job) => emitter.emit("download-job", job),
+                    DownloadQueueEvent::Progress(progress) => {
+                        emitter.emit("download-progress", progress)
+                    }
+                };
+            });
+            app.manage(queue);
+
+            let emitter = app_handle.clone();
+            let session_emitter = app_handle.clone();
+            let probe_http = http.clone();
+            let provider = ScrProcessEventProvider::new(
+                move || -> Box<dyn ProcessProbe> {
+                    #[cfg(target_os = "linux")]
+                    return Box::new(ProcfsProbe::new("/proc", probe_http.clone()));
+                    #[cfg(not(target_os = "linux"))]
+                    return Box::new(SystemProbe::new(probe_http.clone()));
+                },
+                move |event| {
+                    let _ = emitter.emit("scr-event", event);
+                },
+            );
+            let provider = Arc::new(provider);
+            app.manage(provider.clone());
+
+            let api = Arc::new(ScApiClient::new(http, move || provider.snapshot().port));
+            app.manage(api.clone());
+
+            let history_path = app_handle
+                .path()
+                .resolve("ladder-history.sqlite3", BaseDirectory::AppData)?;
+            let history = Arc::new(LadderHistory::open(&history_path)?);
+            app.manage(history.clone());
+
+            let scout_api = api.clone();
+            let tracker_api = api.clone();
+            let scout_library = library.clone();
+            let session_history = history.clone();
+            let session = SessionMonitor::new(api, move |event| {
+                if let ScrEvent::UserChanged { user: Some(user) } = &event {
+                    let toons = [(user.toon.clone(), user.gateway_id)];
+                    let aurora_id = user.aurora_id;
+                    let seen_at_ms = clock::now_ms();
+                    let history = session_history.clone();
+                    tauri::async_runtime::spawn_blocking(move || {
+                        if let Err(e) = history.observe_toons(aurora_id, &toons, seen_at_ms) {
+                            println!("[ladder-history] {e}");
+                        }
+                    });
+                }
+                if let ScrEvent::MatchStarted {
+                    opponent,
+                    map,
+                    gateway_id,
+                    ..
+                } = &event
+                {
+                    let target = ScoutTarget {
+                        toon: opponent.clone(),
+                        gateway_id: *gateway_id,
+                        map: map.clone(),
+                    };
+                    let api = scout_api.clone();
+                    let library = scout_library.clone();
+                    let history = session_history.clone();
+                    let emitter = session_emitter.clone();
+                    tauri::async_runtime::spawn(async move {
+                        match scouting::scout(&api, library, history, target).await {
+                            Ok(report) => {
+                                let _ = emitter.emit("scouting-report", report);
+                            }
+                            Err(e) => println!("[scouting] {e}"),
+                        }
+                    });
+                }
+                let _ = session_emitter.emit("scr-event", event);
+            });
+            session.start(SESSION_POLL_INTERVAL);
+            app.manage(session);
+
+            let saved_players_path = app_handle
+                .path()
+                .resolve("saved_players.json", BaseDirectory::AppData)?;
+            let emitter = app_handle.clone();
+            let tracker = SavedPlayerTracker::new(
+                tracker_api.clone(),
+                history.clone(),
+                saved_players_path.clone(),
+                move |m| {
+                    let _ = emitter.emit("saved-player-new-match", m);
+                },
+            );
+            tracker.start(SAVED_PLAYER_POLL_INTERVAL);
+            app.manage(tracker);
+
+            let snapshotter =
+                LadderSnapshotter::new(tracker_api, history.clone(), saved_players_path);
+            snapshotter.start(SNAPSHOT_CHECK_INTERVAL);
+            app.manage(snapshotter);
+
+            let emitter = app_handle.clone();
+            let watcher = ReplayWatcher::new(move |replay| {
+                if let Err(e) = library.index_file(Path::new(&replay.path)) {
+                    println!("[replay-library] Failed to index {}: {e}", replay.path);
+                }
+                let names: Vec<String> = replay.players.iter().map(|p| p.name.clone()).collect();
+                if let Err(e) = history.observe_replay_names(&names, replay.start_time_ms) {
+                    println!("[ladder-history] {e}");
+                }
+                let _ = emitter.emit("replay-ingested", replay);
+            });
+            app.manage(Arc::new(watcher));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             init_process,
+            get_scr_snapshot,
+            get_session,
+            configure_session_account,
+            configure_replay_watcher,
+            get_replay_watcher_config,
+            library_list,
+            library_count,
+            library_get,
+            library_search,
+            library_search_chat,
+            player_stats,
+            scout_opponent,
+            saved_player_activity,
+            toon_aliases,
+            resolve_toon,
+            configure_ladder_snapshots,
+            get_ladder_snapshot_config,
+            take_ladder_snapshot,
+            rating_history,
+            rank_movements,
+            top_churn,
+            get_openings,
+            reload_openings,
+            reference_builds_list,
+            reference_build_save,
+            reference_build_delete,
+            compare_build,
+            library_rescan,
+            stop_process_monitor,
+            sc_api_aurora_profile,
+            sc_api_leaderboards,
+            sc_api_leaderboard,
+            sc_api_match_history,
+            sc_api_player_stats,
+            sc_api_map_stats,
+            set_process_poll_interval,
             read_settings_file,
             write_settings_file,
             download_file,
             download_and_parse_replay,
+            extract_replay_map,
+            get_http_client_config,
+            configure_http_client,
+            get_rate_limit_config,
+            configure_rate_limits,
+            get_rate_limit_counters,
+            acquire_request_slot,
+            enqueue_downloads,
+            list_download_jobs,
+            pause_download,
+            resume_download,
+            cancel_download,
+            clear_finished_downloads,
+            set_download_concurrency,
             reveal_in_folder
         ])
         .run(tauri::generate_context!())
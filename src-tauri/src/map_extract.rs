@@ -0,0 +1,39 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    filename_template::{sanitize_component, unique_path},
+    mpq,
+    replay_parser::{map, ReplayParser},
+};
+
+/// Packages the map embedded in `replay` as a `.scx`/`.scm` archive under
+/// `destination_path`, named after the map title.
+pub fn write_map_from_replay(replay: &[u8], destination_path: &Path) -> Result<PathBuf, String> {
+    let parser = ReplayParser::new(replay);
+    let game_info = parser
+        .game_info()
+        .map_err(|e| format!("Failed to parse replay: {e}"))?;
+    let chk = parser
+        .map_data()
+        .map_err(|e| format!("Failed to read map data: {e}"))?;
+    let info = map::parse_chk(&chk).map_err(|e| format!("Failed to read map data: {e}"))?;
+
+    let title = [info.title.as_deref(), Some(game_info.map_name.as_str())]
+        .into_iter()
+        .flatten()
+        .map(sanitize_component)
+        .find(|t| !t.is_empty() && t != "_")
+        .unwrap_or_else(|| "Untitled".to_string());
+    let filename = format!("{title}.{}", info.extension());
+
+    let archive = mpq::build_archive(&[("staredit\\scenario.chk", &chk)]);
+    let path = unique_path(destination_path, Path::new(&filename), &archive)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {e}"))?;
+    }
+    fs::write(&path, &archive).map_err(|e| format!("Failed to write map: {e}"))?;
+    Ok(path)
+}
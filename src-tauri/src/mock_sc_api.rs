@@ -0,0 +1,285 @@
+//! Stand-in for the web API StarCraft: Remastered serves on localhost, so the
+//! detection → API → download → parse pipeline can run without the game.
+//! Run it with `cargo run --features mock --bin mock_sc_api`; see
+//! `src/bin/mock_sc_api.rs`.
+//!
+//! Responses come from the JSON fixtures in `fixtures/web-api`, which a
+//! directory of recorded responses with the same file names can override.
+//! `{port}` in a fixture is replaced with the mock's port, so replay URLs
+//! point back at it.
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    replay_parser::{builder::ReplayBuilder, game_info::Race},
+    scr_process::WEB_API_PROBE_PATH,
+    test_server::{TestResponse, TestServer},
+};
+
+const FIXTURES: &[(&str, &str)] = &[
+    ("gateway", include_str!("../fixtures/web-api/gateway.json")),
+    (
+        "leaderboard",
+        include_str!("../fixtures/web-api/leaderboard.json"),
+    ),
+    (
+        "leaderboard-page",
+        include_str!("../fixtures/web-api/leaderboard-page.json"),
+    ),
+    (
+        "aurora-profile",
+        include_str!("../fixtures/web-api/aurora-profile.json"),
+    ),
+    (
+        "matchmaker-gameinfo",
+        include_str!("../fixtures/web-api/matchmaker-gameinfo.json"),
+    ),
+    (
+        "matchmaker-player-stat",
+        include_str!("../fixtures/web-api/matchmaker-player-stat.json"),
+    ),
+    (
+        "map-stats",
+        include_str!("../fixtures/web-api/map-stats.json"),
+    ),
+];
+
+/// Path prefix of the replay download URLs in the fixtures.
+pub const REPLAY_PATH_PREFIX: &str = "/replays/";
+
+#[derive(Debug, Clone, Default)]
+pub struct MockConfig {
+    /// Port to listen on; 0 picks a free one.
+    pub port: u16,
+    /// Recorded responses overriding the built-in fixtures, named like them
+    /// (`leaderboard.json`, `aurora-profile.json`, ...).
+    pub fixtures_dir: Option<PathBuf>,
+    /// Replay served for every download URL. A generated TvZ when unset.
+    pub replay: Option<PathBuf>,
+    /// Added before every response.
+    pub latency: Duration,
+    /// Every nth request fails with a 500; 0 never fails.
+    pub fail_every: u32,
+}
+
+pub struct MockScApi {
+    server: TestServer,
+}
+
+impl MockScApi {
+    pub fn start(config: MockConfig) -> Result<Self, String> {
+        let replay = match &config.replay {
+            Some(path) => fs::read(path)
+                .map_err(|e| format!("Failed to read replay {}: {e}", path.display()))?,
+            None => default_replay(),
+        };
+        let requests = Arc::new(AtomicU32::new(0));
+        // The handler needs the port for `{port}`, which is only known once bound.
+        let port = Arc::new(AtomicU32::new(0));
+
+        let bound_port = port.clone();
+        let server = TestServer::bind(config.port, move |req| {
+            let count = requests.fetch_add(1, Ordering::SeqCst) + 1;
+            let response = if config.fail_every > 0 && count.is_multiple_of(config.fail_every) {
+                TestResponse::status(500).body(b"Internal Server Error".to_vec())
+            } else {
+                respond(
+                    &config,
+                    &replay,
+                    &req.path,
+                    bound_port.load(Ordering::SeqCst),
+                )
+            };
+            response.delayed(config.latency)
+        })
+        .map_err(|e| format!("Failed to start mock web API: {e}"))?;
+        port.store(server.port() as u32, Ordering::SeqCst);
+
+        Ok(Self { server })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.server.port()
+    }
+}
+
+fn respond(config: &MockConfig, replay: &[u8], path: &str, port: u32) -> TestResponse {
+    let path = path.split('?').next().unwrap_or_default();
+    if path.starts_with(REPLAY_PATH_PREFIX) {
+        return TestResponse::ok(replay.to_vec())
+            .header("Content-Type", "application/octet-stream");
+    }
+
+    let Some(name) = fixture_name(path) else {
+        return TestResponse::status(404).body(b"Not Found".to_vec());
+    };
+    match fixture(config, name) {
+        Ok(body) => TestResponse::ok(body.replace("{port}", &port.to_string()).into_bytes())
+            .header("Content-Type", "application/json"),
+        Err(e) => {
+            println!("[mock-sc-api] {e}");
+            TestResponse::status(500).body(e.into_bytes())
+        }
+    }
+}
+
+/// Fixture answering `path`, which has no query string.
+fn fixture_name(path: &str) -> Option<&'static str> {
+    let name = match path {
+        WEB_API_PROBE_PATH => "gateway",
+        "/web-api/v1/leaderboard" => "leaderboard",
+        p if p.starts_with("/web-api/v1/leaderboard/") => "leaderboard-page",
+        p if p.starts_with("/web-api/v2/aurora-profile-by-toon/") => "aurora-profile",
+        p if p.starts_with("/web-api/v1/matchmaker-gameinfo-by-toon/") => "matchmaker-gameinfo",
+        p if p.starts_with("/web-api/v1/matchmaker-player-stat-by-toon/") => {
+            "matchmaker-player-stat"
+        }
+        p if p.starts_with("/web-api/v1/map-stats-by-toon/") => "map-stats",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn fixture(config: &MockConfig, name: &str) -> Result<String, String> {
+    if let Some(dir) = &config.fixtures_dir {
+        let recorded = dir.join(format!("{name}.json"));
+        if recorded.exists() {
+            return fs::read_to_string(&recorded)
+                .map_err(|e| format!("Failed to read {}: {e}", recorded.display()));
+        }
+    }
+    FIXTURES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, body)| body.to_string())
+        .ok_or_else(|| format!("No fixture named {name}"))
+}
+
+/// The game the built-in fixtures describe: Flash (T) beats Jaedong (Z).
+fn default_replay() -> Vec<u8> {
+    ReplayBuilder::new("Polypoid")
+        .frames(24 * 60 * 12)
+        .start_time(1_735_689_600)
+        .player("Flash", Race::Terran)
+        .player("Jaedong", Race::Zerg)
+        .chat(240, 0, "gl hf")
+        .chat(264, 1, "gl hf")
+        .leave(24 * 60 * 12 - 24, 1)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::ReplayCache,
+        downloads::Downloader,
+        http::{HttpClient, HttpClientConfig},
+        replay_library::ReplayLibrary,
+        replay_parser::ReplayParser,
+        sc_api::{ProfileFlags, ScApiClient, ToonRequest},
+        scr_process::probe_web_api,
+        test_dir::TestDir,
+        test_server::unlimited,
+    };
+
+    fn http(max_retries: u32) -> Arc<HttpClient> {
+        let config = HttpClientConfig {
+            max_retries,
+            initial_backoff_ms: 1,
+            ..HttpClientConfig::default()
+        };
+        Arc::new(HttpClient::new(config, unlimited()).unwrap())
+    }
+
+    #[test]
+    fn serves_api_and_replays_through_the_whole_pipeline() {
+        let mock = MockScApi::start(MockConfig::default()).unwrap();
+        let http = http(0);
+
+        // Detection confirms the port the same way it does for the game.
+        assert_eq!(probe_web_api(&http, mock.port()), Some(200));
+
+        let port = mock.port();
+        let api = ScApiClient::new(http.clone(), move || Some(port));
+        let profile = tauri::async_runtime::block_on(api.aurora_profile(
+            &ToonRequest {
+                toon: "Flash".to_string(),
+                gateway: 30,
+            },
+            ProfileFlags::Profile,
+        ))
+        .unwrap();
+        let url = &profile.replays[0].url;
+        assert_eq!(
+            *url,
+            format!("http://localhost:{port}/replays/flash-vs-jaedong.rep")
+        );
+
+        let dir = TestDir::new("mock-sc-api");
+        let library = Arc::new(ReplayLibrary::open_in_memory().unwrap());
+        let downloader = Downloader::new(
+            http,
+            Arc::new(ReplayCache::new(dir.join("cache"), 10)),
+            library.clone(),
+            dir.join("quarantine"),
+            dir.join("partial"),
+        );
+        let (bytes, cached) = tauri::async_runtime::block_on(downloader.fetch_replay(
+            url,
+            "flash-vs-jaedong.rep",
+            |_, _| {},
+        ))
+        .unwrap();
+
+        assert!(!cached);
+        assert_eq!(library.list(0, 1).unwrap()[0].matchup, "TvZ");
+        let parsed = ReplayParser::new(&bytes).parse().unwrap();
+        assert_eq!(parsed.matchup(), "TvZ");
+        assert_eq!(parsed.winners()[0].name, "Flash");
+    }
+
+    #[test]
+    fn simulates_failures_and_prefers_recorded_fixtures() {
+        let dir = TestDir::new("mock-fixtures");
+        fs::write(
+            dir.join("aurora-profile.json"),
+            r#"{"aurora_id": 5, "battle_tag": "Bisu#5"}"#,
+        )
+        .unwrap();
+
+        let mock = MockScApi::start(MockConfig {
+            fixtures_dir: Some(dir.to_path_buf()),
+            fail_every: 2,
+            ..MockConfig::default()
+        })
+        .unwrap();
+        let port = mock.port();
+        let api = ScApiClient::new(http(0), move || Some(port));
+        let retrying = ScApiClient::new(http(2), move || Some(port));
+
+        let bisu = ToonRequest {
+            toon: "Bisu".to_string(),
+            gateway: 30,
+        };
+        let profile =
+            tauri::async_runtime::block_on(api.aurora_profile(&bisu, ProfileFlags::Profile))
+                .unwrap();
+        assert_eq!(profile.battle_tag, "Bisu#5");
+        // The second request fails, and without retries so does the call.
+        let err = tauri::async_runtime::block_on(api.leaderboards()).unwrap_err();
+        assert!(err.contains("500"), "{err}");
+        tauri::async_runtime::block_on(api.leaderboards()).unwrap();
+        // The fourth fails too, but is retried.
+        let leaderboards = tauri::async_runtime::block_on(retrying.leaderboards()).unwrap();
+        assert_eq!(leaderboards.leaderboards["12960"].season_id, 17);
+    }
+}
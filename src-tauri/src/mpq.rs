@@ -0,0 +1,234 @@
+//! Minimal MPQ (format version 1) writer, enough to package a scenario.chk
+//! into a map archive StarCraft can load. Files are stored uncompressed.
+
+const HASH_OFFSET: u32 = 0;
+const HASH_NAME_A: u32 = 1;
+const HASH_NAME_B: u32 = 2;
+const HASH_FILE_KEY: u32 = 3;
+
+/// Signature every archive starts with, maps included.
+pub const MAGIC: &[u8; 4] = b"MPQ\x1A";
+const HEADER_SIZE: u32 = 32;
+const SECTOR_SIZE_SHIFT: u16 = 3;
+const FILE_EXISTS: u32 = 0x8000_0000;
+
+fn crypt_table() -> [u32; 0x500] {
+    let mut table = [0u32; 0x500];
+    let mut seed: u32 = 0x0010_0001;
+    for index1 in 0..0x100 {
+        let mut index2 = index1;
+        for _ in 0..5 {
+            seed = (seed * 125 + 3) % 0x2A_AAAB;
+            let temp1 = (seed & 0xFFFF) << 16;
+            seed = (seed * 125 + 3) % 0x2A_AAAB;
+            let temp2 = seed & 0xFFFF;
+            table[index2] = temp1 | temp2;
+            index2 += 0x100;
+        }
+    }
+    table
+}
+
+fn hash_string(table: &[u32; 0x500], name: &str, hash_type: u32) -> u32 {
+    let mut seed1: u32 = 0x7FED_7FED;
+    let mut seed2: u32 = 0xEEEE_EEEE;
+    for byte in name.bytes() {
+        let ch = byte.to_ascii_uppercase() as u32;
+        seed1 = table[(hash_type * 0x100 + ch) as usize] ^ seed1.wrapping_add(seed2);
+        seed2 = ch
+            .wrapping_add(seed1)
+            .wrapping_add(seed2)
+            .wrapping_add(seed2 << 5)
+            .wrapping_add(3);
+    }
+    seed1
+}
+
+fn encrypt(table: &[u32; 0x500], data: &mut [u32], key: u32) {
+    let mut seed1 = key;
+    let mut seed2: u32 = 0xEEEE_EEEE;
+    for value in data.iter_mut() {
+        seed2 = seed2.wrapping_add(table[0x400 + (seed1 & 0xFF) as usize]);
+        let plain = *value;
+        *value = plain ^ seed1.wrapping_add(seed2);
+        seed1 = ((!seed1) << 0x15).wrapping_add(0x1111_1111) | (seed1 >> 0x0B);
+        seed2 = plain
+            .wrapping_add(seed2)
+            .wrapping_add(seed2 << 5)
+            .wrapping_add(3);
+    }
+}
+
+/// Builds an archive containing `files` (archive path, contents), plus a
+/// `(listfile)` naming them.
+pub fn build_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let table = crypt_table();
+
+    let listfile: String = files
+        .iter()
+        .map(|(name, _)| format!("{name}\r\n"))
+        .collect();
+    let mut entries: Vec<(&str, &[u8])> = files.to_vec();
+    entries.push(("(listfile)", listfile.as_bytes()));
+
+    let hash_entries = (entries.len() * 2).next_power_of_two().max(16);
+
+    let mut out = vec![0u8; HEADER_SIZE as usize];
+    let mut blocks: Vec<u32> = Vec::with_capacity(entries.len() * 4);
+    let mut hashes: Vec<u32> = [u32::MAX; 4].repeat(hash_entries);
+
+    for (block_index, (name, data)) in entries.iter().enumerate() {
+        let offset = out.len() as u32;
+        out.extend_from_slice(data);
+        blocks.extend_from_slice(&[offset, data.len() as u32, data.len() as u32, FILE_EXISTS]);
+
+        let mut slot = hash_string(&table, name, HASH_OFFSET) as usize & (hash_entries - 1);
+        while hashes[slot * 4 + 3] != u32::MAX {
+            slot = (slot + 1) & (hash_entries - 1);
+        }
+        hashes[slot * 4] = hash_string(&table, name, HASH_NAME_A);
+        hashes[slot * 4 + 1] = hash_string(&table, name, HASH_NAME_B);
+        // Neutral locale, default platform.
+        hashes[slot * 4 + 2] = 0;
+        hashes[slot * 4 + 3] = block_index as u32;
+    }
+
+    encrypt(
+        &table,
+        &mut hashes,
+        hash_string(&table, "(hash table)", HASH_FILE_KEY),
+    );
+    encrypt(
+        &table,
+        &mut blocks,
+        hash_string(&table, "(block table)", HASH_FILE_KEY),
+    );
+
+    let hash_table_offset = out.len() as u32;
+    out.extend(hashes.iter().flat_map(|v| v.to_le_bytes()));
+    let block_table_offset = out.len() as u32;
+    out.extend(blocks.iter().flat_map(|v| v.to_le_bytes()));
+    let archive_size = out.len() as u32;
+
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    header.extend_from_slice(&archive_size.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&SECTOR_SIZE_SHIFT.to_le_bytes());
+    header.extend_from_slice(&hash_table_offset.to_le_bytes());
+    header.extend_from_slice(&block_table_offset.to_le_bytes());
+    header.extend_from_slice(&(hash_entries as u32).to_le_bytes());
+    header.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    out[..HEADER_SIZE as usize].copy_from_slice(&header);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decrypt(table: &[u32; 0x500], data: &mut [u32], key: u32) {
+        let mut seed1 = key;
+        let mut seed2: u32 = 0xEEEE_EEEE;
+        for value in data.iter_mut() {
+            seed2 = seed2.wrapping_add(table[0x400 + (seed1 & 0xFF) as usize]);
+            *value ^= seed1.wrapping_add(seed2);
+            seed1 = ((!seed1) << 0x15).wrapping_add(0x1111_1111) | (seed1 >> 0x0B);
+            seed2 = value
+                .wrapping_add(seed2)
+                .wrapping_add(seed2 << 5)
+                .wrapping_add(3);
+        }
+    }
+
+    fn u32s(bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .collect()
+    }
+
+    /// Looks `name` up the way StarCraft does: probe the hash table from the
+    /// name's offset hash until both name hashes match.
+    fn read_file<'a>(archive: &'a [u8], name: &str) -> Option<&'a [u8]> {
+        let table = crypt_table();
+        let header = u32s(&archive[..HEADER_SIZE as usize]);
+        assert_eq!(&archive[..4], b"MPQ\x1A");
+        assert_eq!(header[2] as usize, archive.len());
+        let (hash_offset, block_offset) = (header[4] as usize, header[5] as usize);
+        let (hash_entries, block_entries) = (header[6] as usize, header[7] as usize);
+
+        let mut hashes = u32s(&archive[hash_offset..hash_offset + hash_entries * 16]);
+        decrypt(&table, &mut hashes, hash_string(&table, "(hash table)", 3));
+        let mut blocks = u32s(&archive[block_offset..block_offset + block_entries * 16]);
+        decrypt(&table, &mut blocks, hash_string(&table, "(block table)", 3));
+
+        let mut slot = hash_string(&table, name, HASH_OFFSET) as usize & (hash_entries - 1);
+        while hashes[slot * 4 + 3] != u32::MAX {
+            let entry = &hashes[slot * 4..slot * 4 + 4];
+            if entry[0] == hash_string(&table, name, HASH_NAME_A)
+                && entry[1] == hash_string(&table, name, HASH_NAME_B)
+            {
+                let block = &blocks[entry[3] as usize * 4..entry[3] as usize * 4 + 4];
+                assert_eq!(block[1], block[2], "stored uncompressed");
+                assert_eq!(block[3], FILE_EXISTS);
+                return Some(&archive[block[0] as usize..(block[0] + block[1]) as usize]);
+            }
+            slot = (slot + 1) & (hash_entries - 1);
+        }
+        None
+    }
+
+    #[test]
+    fn hashes_match_known_values() {
+        let table = crypt_table();
+        assert_eq!(table[0], 0x55C6_36E2);
+        assert_eq!(
+            hash_string(&table, "(hash table)", HASH_FILE_KEY),
+            0xC3AF_3770
+        );
+        assert_eq!(
+            hash_string(&table, "(block table)", HASH_FILE_KEY),
+            0xEC83_B3A3
+        );
+        // Names are case-insensitive.
+        assert_eq!(
+            hash_string(&table, "staredit\\scenario.chk", HASH_NAME_A),
+            hash_string(&table, "STAREDIT\\SCENARIO.CHK", HASH_NAME_A)
+        );
+    }
+
+    #[test]
+    fn encryption_round_trips() {
+        let table = crypt_table();
+        let plain: Vec<u32> = (0..64).map(|i| i * 0x0101_0101).collect();
+        let mut data = plain.clone();
+        encrypt(&table, &mut data, 0xC3AF_3770);
+        assert_ne!(data, plain);
+        decrypt(&table, &mut data, 0xC3AF_3770);
+        assert_eq!(data, plain);
+    }
+
+    #[test]
+    fn archives_read_back() {
+        let chk = b"VER \x02\x00\x00\x00\xcd\x00".to_vec();
+        let files: Vec<(String, Vec<u8>)> = (0..20)
+            .map(|i| (format!("file{i}.txt"), vec![i as u8; i * 7]))
+            .chain([("staredit\\scenario.chk".to_string(), chk)])
+            .collect();
+        let entries: Vec<(&str, &[u8])> = files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+            .collect();
+        let archive = build_archive(&entries);
+
+        for (name, data) in &entries {
+            assert_eq!(read_file(&archive, name), Some(*data), "{name}");
+        }
+        let listfile = String::from_utf8(read_file(&archive, "(listfile)").unwrap().to_vec());
+        assert_eq!(listfile.unwrap().lines().count(), entries.len());
+        assert_eq!(read_file(&archive, "missing.txt"), None);
+    }
+}
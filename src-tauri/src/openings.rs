@@ -0,0 +1,255 @@
+//! Rule-based opening classification of build orders. Definitions ship in
+//! `data/openings.json`; users can add their own, or replace built-in ones
+//! of the same name and race, in an `openings.json` next to the app data.
+//!
+//! A definition is a list of steps, each naming the nth building or unit of
+//! a kind (`count`, default 1) and optionally the supply or time it must be
+//! ordered by. Each step must come after the previous one. A build's
+//! confidence for a definition is the share of its steps the build follows
+//! before departing from it.
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::replay_parser::build_order::BuildStep;
+
+const BUILTIN: &str = include_str!("../data/openings.json");
+
+/// Matches below this confidence aren't reported.
+pub const MIN_CONFIDENCE: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpeningStep {
+    /// Name as in build orders, e.g. `Spawning Pool`.
+    pub item: String,
+    #[serde(default = "one")]
+    pub count: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_supply: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_supply: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_time_ms: Option<u32>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpeningDefinition {
+    pub name: String,
+    /// `Zerg`, `Terran` or `Protoss`.
+    pub race: String,
+    pub steps: Vec<OpeningStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpeningMatch {
+    pub name: String,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpeningBook {
+    definitions: Vec<OpeningDefinition>,
+}
+
+impl Default for OpeningBook {
+    fn default() -> Self {
+        Self {
+            definitions: serde_json::from_str(BUILTIN).expect("built-in openings are valid"),
+        }
+    }
+}
+
+impl OpeningBook {
+    /// The built-in definitions merged with those in `user_file`, if it
+    /// exists.
+    pub fn load(user_file: &Path) -> Result<Self, String> {
+        let mut book = Self::default();
+        if !user_file.exists() {
+            return Ok(book);
+        }
+        let content = fs::read_to_string(user_file)
+            .map_err(|e| format!("Failed to read {}: {e}", user_file.display()))?;
+        let user: Vec<OpeningDefinition> = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {e}", user_file.display()))?;
+        for definition in user {
+            let existing = book.definitions.iter_mut().find(|d| {
+                d.name.eq_ignore_ascii_case(&definition.name)
+                    && d.race.eq_ignore_ascii_case(&definition.race)
+            });
+            match existing {
+                Some(existing) => *existing = definition,
+                None => book.definitions.push(definition),
+            }
+        }
+        Ok(book)
+    }
+
+    pub fn definitions(&self) -> &[OpeningDefinition] {
+        &self.definitions
+    }
+
+    /// The definition for `race` that `build` satisfies best. Ties go to the
+    /// definition with more steps, being the more specific, then to the
+    /// earlier one.
+    pub fn classify(&self, race: &str, build: &[BuildStep]) -> Option<OpeningMatch> {
+        let mut best: Option<(&OpeningDefinition, f64)> = None;
+        for definition in &self.definitions {
+            if !definition.race.eq_ignore_ascii_case(race) || definition.steps.is_empty() {
+                continue;
+            }
+            let confidence = confidence(definition, build);
+            let better = match best {
+                None => true,
+                Some((b, c)) => {
+                    confidence > c || (confidence == c && definition.steps.len() > b.steps.len())
+                }
+            };
+            if better {
+                best = Some((definition, confidence));
+            }
+        }
+        best.filter(|(_, c)| *c >= MIN_CONFIDENCE)
+            .map(|(definition, confidence)| OpeningMatch {
+                name: definition.name.clone(),
+                confidence,
+            })
+    }
+}
+
+fn confidence(definition: &OpeningDefinition, build: &[BuildStep]) -> f64 {
+    let mut satisfied = 0;
+    let mut previous: Option<usize> = None;
+    for step in &definition.steps {
+        let found = build
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.name.eq_ignore_ascii_case(&step.item))
+            .nth(step.count.max(1) as usize - 1);
+        let Some((index, built)) = found else {
+            break;
+        };
+        let ok = previous.is_none_or(|p| index > p)
+            && step.min_supply.is_none_or(|min| built.supply >= min)
+            && step.max_supply.is_none_or(|max| built.supply <= max)
+            && step.max_time_ms.is_none_or(|max| built.time_ms <= max);
+        if !ok {
+            break;
+        }
+        satisfied += 1;
+        previous = Some(index);
+    }
+    satisfied as f64 / definition.steps.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{replay_parser::build_order::BuildItemKind, test_dir::TestDir};
+
+    fn build(steps: &[(u32, &str)]) -> Vec<BuildStep> {
+        steps
+            .iter()
+            .enumerate()
+            .map(|(i, (supply, name))| BuildStep {
+                frame: i as u32 * 500,
+                time_ms: i as u32 * 21_000,
+                supply: *supply,
+                kind: BuildItemKind::Building,
+                name: name.to_string(),
+            })
+            .collect()
+    }
+
+    fn classify(book: &OpeningBook, race: &str, steps: &[(u32, &str)]) -> Option<String> {
+        book.classify(race, &build(steps)).map(|m| m.name)
+    }
+
+    #[test]
+    fn classifies_built_in_openings() {
+        let book = OpeningBook::default();
+        let zerg = |steps: &[(u32, &str)]| classify(&book, "Zerg", steps);
+        assert_eq!(
+            zerg(&[(9, "Spawning Pool"), (8, "Extractor")]).as_deref(),
+            Some("9 Pool")
+        );
+        assert_eq!(
+            zerg(&[(9, "Overlord"), (9, "Spawning Pool")]).as_deref(),
+            Some("Overpool")
+        );
+        assert_eq!(
+            zerg(&[(12, "Hatchery"), (11, "Spawning Pool"), (10, "Extractor")]).as_deref(),
+            Some("12 Hatch")
+        );
+        assert_eq!(
+            zerg(&[(12, "Spawning Pool"), (11, "Hatchery")]).as_deref(),
+            Some("12 Pool")
+        );
+
+        let protoss = |steps: &[(u32, &str)]| classify(&book, "Protoss", steps);
+        assert_eq!(
+            protoss(&[(9, "Pylon"), (9, "Gateway"), (10, "Gateway")]).as_deref(),
+            Some("2 Gate")
+        );
+        assert_eq!(
+            protoss(&[(10, "Gateway"), (12, "Cybernetics Core"), (20, "Gateway")]).as_deref(),
+            Some("1 Gate Core")
+        );
+
+        let bbs = book
+            .classify(
+                "Terran",
+                &build(&[(8, "Barracks"), (9, "Barracks"), (9, "Supply Depot")]),
+            )
+            .unwrap();
+        assert_eq!((bbs.name.as_str(), bbs.confidence), ("BBS", 1.0));
+        let partial = book.classify("Zerg", &build(&[(11, "Hatchery")])).unwrap();
+        assert_eq!(
+            (partial.name.as_str(), partial.confidence),
+            ("12 Hatch", 0.5)
+        );
+        // Nothing recognisable.
+        assert_eq!(classify(&book, "Terran", &[(10, "Supply Depot")]), None);
+        // Definitions are per race.
+        assert_eq!(classify(&book, "Protoss", &[(9, "Spawning Pool")]), None);
+    }
+
+    #[test]
+    fn merges_user_definitions() {
+        let dir = TestDir::new("openings");
+        let path = dir.join("openings.json");
+        fs::write(
+            &path,
+            r#"[
+                {"name": "9 Pool", "race": "Zerg", "steps": [{"item": "Spawning Pool", "maxSupply": 10}]},
+                {"name": "Proxy Gate", "race": "Protoss", "steps": [
+                    {"item": "Gateway", "maxSupply": 8}, {"item": "Pylon", "maxSupply": 8}
+                ]}
+            ]"#,
+        )
+        .unwrap();
+        let book = OpeningBook::load(&path).unwrap();
+
+        assert_eq!(
+            book.definitions().len(),
+            OpeningBook::default().definitions().len() + 1
+        );
+        assert_eq!(
+            classify(&book, "Zerg", &[(10, "Spawning Pool")]).as_deref(),
+            Some("9 Pool")
+        );
+        assert_eq!(
+            classify(&book, "Protoss", &[(8, "Gateway"), (8, "Pylon")]).as_deref(),
+            Some("Proxy Gate")
+        );
+
+        assert!(OpeningBook::load(Path::new("/nonexistent/openings.json")).is_ok());
+    }
+}
@@ -0,0 +1,75 @@
+//! Files the backend keeps across restarts. They are replaced whole through
+//! a temporary sibling, so an interrupted write never leaves half a file,
+//! and one that no longer parses is set aside rather than overwritten.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::de::DeserializeOwned;
+
+/// Writes `bytes` to `path` by renaming a fully written temporary file over
+/// it.
+pub fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temp = sibling(path, "tmp");
+    fs::write(&temp, bytes)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+/// The JSON value stored at `path`, or the default when there is none. A
+/// file that can't be read or parsed is logged under `tag` and moved to
+/// `<path>.corrupt`, so the next write doesn't destroy what it held.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path, tag: &str) -> T {
+    let parsed = match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| e.to_string()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return T::default(),
+        Err(e) => Err(e.to_string()),
+    };
+    parsed.unwrap_or_else(|e| {
+        let corrupt = sibling(path, "corrupt");
+        println!(
+            "[{tag}] Failed to load {}: {e}; moving it to {}",
+            path.display(),
+            corrupt.display()
+        );
+        if let Err(e) = fs::rename(path, &corrupt) {
+            println!("[{tag}] Failed to move {}: {e}", path.display());
+        }
+        T::default()
+    })
+}
+
+/// `path` with `.extension` appended to its file name.
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{extension}"));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn sets_aside_files_that_dont_parse() {
+        let dir = TestDir::new("persist");
+        let path = dir.join("queue.json");
+
+        assert_eq!(load_json::<Vec<u32>>(&path, "test"), Vec::<u32>::new());
+        write_atomically(&path, b"[1, 2]").unwrap();
+        assert_eq!(load_json::<Vec<u32>>(&path, "test"), vec![1, 2]);
+        assert!(!dir.join("queue.json.tmp").exists());
+
+        fs::write(&path, "[1, 2").unwrap();
+        assert_eq!(load_json::<Vec<u32>>(&path, "test"), Vec::<u32>::new());
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("queue.json.corrupt")).unwrap(),
+            "[1, 2"
+        );
+    }
+}
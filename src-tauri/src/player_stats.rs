@@ -0,0 +1,278 @@
+//! Per-player statistics aggregated from the replay library, with the
+//! per-matchup and per-map breakdowns the ladder API doesn't give.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::replay_library::{LibraryPlayer, LibraryReplay, Outcome, ReplayLibrary};
+
+/// Games listed in `PlayerStats::recent_form`.
+const RECENT_GAMES: usize = 10;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StatsFilter {
+    /// Names of one player's toons; a game counts if any of them played.
+    pub names: Vec<String>,
+    pub after_ms: Option<u64>,
+    pub before_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WinRecord {
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    /// Wins over decided games; 0 when none were decided.
+    pub win_rate: f64,
+}
+
+impl WinRecord {
+    pub fn add(&mut self, outcome: Outcome) {
+        self.games += 1;
+        match outcome {
+            Outcome::Win => self.wins += 1,
+            Outcome::Loss => self.losses += 1,
+            Outcome::Unknown => {}
+        }
+        let decided = self.wins + self.losses;
+        if decided > 0 {
+            self.win_rate = self.wins as f64 / decided as f64;
+        }
+    }
+}
+
+/// A `WinRecord` for one matchup, map or opening.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Breakdown {
+    pub key: String,
+    #[serde(flatten)]
+    pub record: WinRecord,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentGame {
+    pub game_id: i64,
+    pub start_time_ms: u64,
+    pub map_name: String,
+    pub matchup: String,
+    pub opponents: Vec<String>,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStats {
+    pub overall: WinRecord,
+    /// From the player's side, e.g. `TvZ`; most played first.
+    pub by_matchup: Vec<Breakdown>,
+    pub by_map: Vec<Breakdown>,
+    pub openings: Vec<Breakdown>,
+    pub average_duration_ms: u32,
+    pub average_apm: u32,
+    /// Newest first.
+    pub recent_form: Vec<RecentGame>,
+}
+
+pub fn player_stats(library: &ReplayLibrary, filter: &StatsFilter) -> Result<PlayerStats, String> {
+    let games = library.games_of(&filter.names, filter.after_ms, filter.before_ms)?;
+    Ok(aggregate(&games, &filter.names))
+}
+
+/// Aggregates `games`, newest first, from the side of whichever of `names`
+/// played in each.
+fn aggregate(games: &[LibraryReplay], names: &[String]) -> PlayerStats {
+    let mut stats = PlayerStats::default();
+    let mut by_matchup: HashMap<String, WinRecord> = HashMap::new();
+    let mut by_map: HashMap<String, WinRecord> = HashMap::new();
+    let mut openings: HashMap<String, WinRecord> = HashMap::new();
+    let mut total_duration = 0u64;
+    let mut total_apm = 0u64;
+
+    for game in games {
+        let Some(me) = game
+            .players
+            .iter()
+            .find(|p| names.iter().any(|n| n.eq_ignore_ascii_case(&p.name)))
+        else {
+            continue;
+        };
+        let opponents: Vec<&LibraryPlayer> =
+            game.players.iter().filter(|p| p.side != me.side).collect();
+        let matchup = perspective_matchup(me, &opponents);
+
+        stats.overall.add(me.outcome);
+        by_matchup
+            .entry(matchup.clone())
+            .or_default()
+            .add(me.outcome);
+        by_map
+            .entry(game.map_name.clone())
+            .or_default()
+            .add(me.outcome);
+        if let Some(opening) = &me.opening {
+            openings.entry(opening.clone()).or_default().add(me.outcome);
+        }
+        total_duration += game.duration_ms as u64;
+        total_apm += me.apm as u64;
+
+        if stats.recent_form.len() < RECENT_GAMES {
+            stats.recent_form.push(RecentGame {
+                game_id: game.id,
+                start_time_ms: game.start_time_ms,
+                map_name: game.map_name.clone(),
+                matchup,
+                opponents: opponents.iter().map(|p| p.name.clone()).collect(),
+                outcome: me.outcome,
+            });
+        }
+    }
+
+    if stats.overall.games > 0 {
+        let games = stats.overall.games as u64;
+        stats.average_duration_ms = (total_duration / games) as u32;
+        stats.average_apm = (total_apm / games) as u32;
+    }
+    stats.by_matchup = breakdowns(by_matchup);
+    stats.by_map = breakdowns(by_map);
+    stats.openings = breakdowns(openings);
+    stats
+}
+
+/// The player's race, then each opposing side's, e.g. `TvZ` or `TvPZ`.
+pub fn perspective_matchup(me: &LibraryPlayer, opponents: &[&LibraryPlayer]) -> String {
+    let initial = |p: &LibraryPlayer| p.race.chars().next().unwrap_or('U');
+    let mut sides: Vec<(u32, String)> = Vec::new();
+    for opponent in opponents {
+        match sides.iter_mut().find(|(side, _)| *side == opponent.side) {
+            Some((_, races)) => races.push(initial(opponent)),
+            None => sides.push((opponent.side, initial(opponent).to_string())),
+        }
+    }
+    let mut matchup = initial(me).to_string();
+    for (_, races) in sides {
+        matchup.push('v');
+        matchup.push_str(&races);
+    }
+    matchup
+}
+
+/// Most played first, then by key.
+pub fn breakdowns(records: HashMap<String, WinRecord>) -> Vec<Breakdown> {
+    let mut breakdowns: Vec<Breakdown> = records
+        .into_iter()
+        .map(|(key, record)| Breakdown { key, record })
+        .collect();
+    breakdowns.sort_by(|a, b| {
+        b.record
+            .games
+            .cmp(&a.record.games)
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    breakdowns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        replay_parser::{builder::ReplayBuilder, game_info::Race},
+        test_dir::TestDir,
+    };
+    use std::fs;
+
+    /// Builds a Spawning Pool, for a Zerg player's opening.
+    fn pool() -> Vec<u8> {
+        let mut data = vec![0x1e, 0, 0, 0, 0];
+        data.extend_from_slice(&142u16.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn aggregates_linked_toons_from_their_side() {
+        let dir = TestDir::new("player-stats");
+        let games = [
+            // 2025-01-01: Jaedong (Z) beats Flash (T) on Polypoid, 10 minutes.
+            ReplayBuilder::new("Polypoid")
+                .frames(14_286)
+                .start_time(1_735_689_600)
+                .player("Flash", Race::Terran)
+                .player("Jaedong", Race::Zerg)
+                .command(1_000, 1, 0x0c, &pool())
+                .leave(14_000, 0),
+            // 2025-02-01: Jaedong's smurf loses to Bisu (P) on Polypoid, 20 minutes.
+            ReplayBuilder::new("Polypoid")
+                .frames(28_572)
+                .start_time(1_738_368_000)
+                .player("Bisu", Race::Protoss)
+                .player("JD.smurf", Race::Zerg)
+                .command(1_000, 1, 0x0c, &pool())
+                .leave(28_000, 1),
+            // 2025-03-01: Jaedong beats Flash on Eclipse, no build.
+            ReplayBuilder::new("Eclipse")
+                .frames(14_286)
+                .start_time(1_740_787_200)
+                .player("Jaedong", Race::Zerg)
+                .player("Flash", Race::Terran)
+                .leave(14_000, 1),
+            // A game without Jaedong.
+            ReplayBuilder::new("Eclipse")
+                .frames(14_286)
+                .start_time(1_740_800_000)
+                .player("Bisu", Race::Protoss)
+                .player("Flash", Race::Terran)
+                .leave(14_000, 1),
+        ];
+        for (i, game) in games.iter().enumerate() {
+            fs::write(dir.join(format!("{i}.rep")), game.build()).unwrap();
+        }
+        let library = ReplayLibrary::open_in_memory().unwrap();
+        library.rescan(&[dir.to_path_buf()]).unwrap();
+
+        let mut filter = StatsFilter {
+            names: vec!["jaedong".to_string(), "JD.smurf".to_string()],
+            ..StatsFilter::default()
+        };
+        let stats = player_stats(&library, &filter).unwrap();
+        assert_eq!(
+            (
+                stats.overall.games,
+                stats.overall.wins,
+                stats.overall.losses
+            ),
+            (3, 2, 1)
+        );
+        assert_eq!(stats.by_matchup[0].key, "ZvT");
+        assert_eq!(stats.by_matchup[0].record.win_rate, 1.0);
+        assert_eq!(stats.by_matchup[1].key, "ZvP");
+        assert_eq!(stats.by_map[0].key, "Polypoid");
+        assert_eq!(stats.by_map[0].record.win_rate, 0.5);
+        assert_eq!(stats.openings[0].key, "4/5 Pool");
+        assert_eq!(stats.openings[0].record.games, 2);
+        assert_eq!(stats.average_duration_ms, (600_012 * 2 + 1_200_024) / 3);
+
+        let recent: Vec<(&str, Outcome)> = stats
+            .recent_form
+            .iter()
+            .map(|g| (g.opponents[0].as_str(), g.outcome))
+            .collect();
+        assert_eq!(
+            recent,
+            vec![
+                ("Flash", Outcome::Win),
+                ("Bisu", Outcome::Loss),
+                ("Flash", Outcome::Win)
+            ]
+        );
+
+        filter.after_ms = Some(1_738_368_000_000);
+        filter.before_ms = Some(1_740_787_200_000);
+        let stats = player_stats(&library, &filter).unwrap();
+        assert_eq!(stats.overall.games, 1);
+        assert_eq!(stats.by_map[0].key, "Polypoid");
+    }
+}
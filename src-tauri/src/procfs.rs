@@ -0,0 +1,300 @@
+//! Linux process detection through `/proc`, which also sees StarCraft running
+//! under Wine or Proton: there the process is a Linux binary (often
+//! `wine64-preloader`) and only the command line names `StarCraft.exe`.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    http::HttpClient,
+    scr_process::{probe_web_api, ListeningSocket, ProcessProbe, ScrProcess, STARCRAFT_EXE},
+};
+
+/// Kernel clock ticks per second, used by `/proc/<pid>/stat` start times.
+/// Fixed at 100 on every mainstream architecture.
+const CLOCK_TICKS_PER_SECOND: u64 = 100;
+const TCP_LISTEN: &str = "0A";
+
+/// [`ProcessProbe`] reading a procfs tree rooted at `root` (normally `/proc`).
+pub struct ProcfsProbe {
+    root: PathBuf,
+    http: Arc<HttpClient>,
+}
+
+impl ProcfsProbe {
+    pub fn new(root: impl Into<PathBuf>, http: Arc<HttpClient>) -> Self {
+        Self {
+            root: root.into(),
+            http,
+        }
+    }
+}
+
+impl ProcessProbe for ProcfsProbe {
+    fn processes(&mut self) -> Vec<ScrProcess> {
+        read_processes(&self.root)
+    }
+
+    fn listening_sockets(&mut self) -> Vec<ListeningSocket> {
+        read_listening_sockets(&self.root)
+    }
+
+    fn probe_web_api(&mut self, port: u16) -> Option<u16> {
+        probe_web_api(&self.http, port)
+    }
+}
+
+fn pid_dirs(root: &Path) -> Vec<(u32, PathBuf)> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            Some((pid, entry.path()))
+        })
+        .collect()
+}
+
+fn read_processes(root: &Path) -> Vec<ScrProcess> {
+    let boot_time = read_boot_time(root).unwrap_or_default();
+
+    pid_dirs(root)
+        .into_iter()
+        .filter_map(|(pid, dir)| {
+            // Processes can exit between listing and reading; skip them.
+            let cmdline = fs::read(dir.join("cmdline")).ok()?;
+            let args: Vec<String> = cmdline
+                .split(|&b| b == 0)
+                .filter(|a| !a.is_empty())
+                .map(|a| String::from_utf8_lossy(a).to_string())
+                .collect();
+            let started_at = read_start_ticks(&dir)
+                .map(|ticks| boot_time + ticks / CLOCK_TICKS_PER_SECOND)
+                .unwrap_or_default();
+
+            let (name, exe_path) = match windows_exe(&args) {
+                Some(exe) => (STARCRAFT_EXE.to_string(), Some(exe.clone())),
+                None => {
+                    let comm = fs::read_to_string(dir.join("comm")).unwrap_or_default();
+                    let exe = fs::read_link(dir.join("exe"))
+                        .ok()
+                        .map(|p| p.to_string_lossy().to_string());
+                    (comm.trim().to_string(), exe)
+                }
+            };
+
+            Some(ScrProcess {
+                pid,
+                name,
+                started_at,
+                exe_path,
+            })
+        })
+        .collect()
+}
+
+/// The StarCraft executable named on a Wine/Proton command line. Wine puts
+/// the Windows program in argv[0], or argv[1] behind a loader or preloader.
+fn windows_exe(args: &[String]) -> Option<&String> {
+    args.iter().take(2).find(|arg| {
+        arg.rsplit(['/', '\\'])
+            .next()
+            .is_some_and(|name| name.eq_ignore_ascii_case(STARCRAFT_EXE))
+    })
+}
+
+/// `btime` from `/proc/stat`: boot time in seconds since the Unix epoch.
+fn read_boot_time(root: &Path) -> Option<u64> {
+    let stat = fs::read_to_string(root.join("stat")).ok()?;
+    stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// Field 22 of `/proc/<pid>/stat`: start time in clock ticks after boot.
+fn read_start_ticks(dir: &Path) -> Option<u64> {
+    let stat = fs::read_to_string(dir.join("stat")).ok()?;
+    // The command name in parentheses may contain spaces; count from after it.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+fn read_listening_sockets(root: &Path) -> Vec<ListeningSocket> {
+    let mut ports_by_inode: HashMap<u64, u16> = HashMap::new();
+    for table in ["net/tcp", "net/tcp6"] {
+        let Ok(content) = fs::read_to_string(root.join(table)) else {
+            continue;
+        };
+        for line in content.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 || fields[3] != TCP_LISTEN {
+                continue;
+            }
+            let port = fields[1]
+                .rsplit(':')
+                .next()
+                .and_then(|p| u16::from_str_radix(p, 16).ok());
+            if let (Some(port), Ok(inode)) = (port, fields[9].parse::<u64>()) {
+                ports_by_inode.insert(inode, port);
+            }
+        }
+    }
+    if ports_by_inode.is_empty() {
+        return Vec::new();
+    }
+
+    let mut sockets = Vec::new();
+    for (pid, dir) in pid_dirs(root) {
+        let Ok(fds) = fs::read_dir(dir.join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            let inode = target
+                .to_str()
+                .and_then(|t| t.strip_prefix("socket:["))
+                .and_then(|t| t.strip_suffix(']'))
+                .and_then(|t| t.parse::<u64>().ok());
+            if let Some(port) = inode.and_then(|i| ports_by_inode.get(&i)) {
+                sockets.push(ListeningSocket { pid, port: *port });
+            }
+        }
+    }
+    sockets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use std::os::unix::fs::symlink;
+
+    /// Builds a throwaway procfs-like tree.
+    struct FakeProcfs {
+        root: TestDir,
+    }
+
+    impl FakeProcfs {
+        fn new(name: &str) -> Self {
+            let root = TestDir::new(&format!("procfs-{name}"));
+            fs::create_dir_all(root.join("net")).unwrap();
+            fs::write(root.join("stat"), "cpu  1 2 3\nbtime 1700000000\n").unwrap();
+            Self { root }
+        }
+
+        fn process(&self, pid: u32, comm: &str, args: &[&str], start_ticks: u64, sockets: &[u64]) {
+            let dir = self.root.join(pid.to_string());
+            fs::create_dir_all(dir.join("fd")).unwrap();
+            fs::write(dir.join("comm"), format!("{comm}\n")).unwrap();
+            fs::write(dir.join("cmdline"), format!("{}\0", args.join("\0"))).unwrap();
+            fs::write(
+                dir.join("stat"),
+                format!("{pid} ({comm}) S 1 {pid} {pid} 0 -1 4194560 1 0 0 0 0 0 0 0 20 0 1 0 {start_ticks} 0 0"),
+            )
+            .unwrap();
+            for (fd, inode) in sockets.iter().enumerate() {
+                symlink(
+                    format!("socket:[{inode}]"),
+                    dir.join("fd").join((fd + 3).to_string()),
+                )
+                .unwrap();
+            }
+        }
+
+        fn tcp(&self, table: &str, rows: &[(&str, &str, u64)]) {
+            let mut content = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n".to_string();
+            for (i, (local, state, inode)) in rows.iter().enumerate() {
+                content.push_str(&format!(
+                    "   {i}: {local} 00000000:0000 {state} 00000000:00000000 00:00000000 00000000  1000        0 {inode} 1 0000000000000000 100 0 0 10 0\n"
+                ));
+            }
+            fs::write(self.root.join("net").join(table), content).unwrap();
+        }
+    }
+
+    #[test]
+    fn recognizes_starcraft_under_wine_and_proton() {
+        let procfs = FakeProcfs::new("wine");
+        procfs.process(10, "bash", &["/bin/bash"], 100, &[]);
+        procfs.process(
+            20,
+            "StarCraft.exe",
+            &[
+                "C:\\Program Files (x86)\\StarCraft\\x86_64\\StarCraft.exe",
+                "-launch",
+            ],
+            12_345,
+            &[],
+        );
+        procfs.process(
+            30,
+            "wine64-preloade",
+            &[
+                "/home/me/.steam/steam/steamapps/common/Proton/files/bin/wine64-preloader",
+                "Z:\\home\\me\\Games\\StarCraft\\x86_64\\starcraft.exe",
+            ],
+            500,
+            &[],
+        );
+
+        let mut processes = read_processes(&procfs.root);
+        processes.sort_by_key(|p| p.pid);
+
+        assert_eq!(processes[0].name, "bash");
+        assert_eq!(processes[1].name, STARCRAFT_EXE);
+        assert_eq!(
+            processes[1].exe_path.as_deref(),
+            Some("C:\\Program Files (x86)\\StarCraft\\x86_64\\StarCraft.exe")
+        );
+        assert_eq!(processes[1].started_at, 1_700_000_000 + 123);
+        assert_eq!(processes[2].name, STARCRAFT_EXE);
+        assert_eq!(processes[2].started_at, 1_700_000_005);
+    }
+
+    #[test]
+    fn maps_listening_sockets_to_pids_through_fds() {
+        let procfs = FakeProcfs::new("sockets");
+        procfs.tcp(
+            "tcp",
+            &[
+                // 127.0.0.1:57549 listening
+                ("0100007F:E0CD", "0A", 111),
+                // established, ignored
+                ("0100007F:E0CE", "01", 222),
+                // 0.0.0.0:6112 listening, owned by nobody we can see
+                ("00000000:17E0", "0A", 333),
+            ],
+        );
+        procfs.tcp(
+            "tcp6",
+            &[("00000000000000000000000001000000:1F90", "0A", 444)],
+        );
+        procfs.process(20, "StarCraft.exe", &["StarCraft.exe"], 1, &[111, 222, 555]);
+        procfs.process(40, "python3", &["python3"], 1, &[444]);
+
+        let mut sockets = read_listening_sockets(&procfs.root);
+        sockets.sort_by_key(|s| s.port);
+
+        assert_eq!(
+            sockets,
+            vec![
+                ListeningSocket {
+                    pid: 40,
+                    port: 8080
+                },
+                ListeningSocket {
+                    pid: 20,
+                    port: 57549
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tauri_plugin_http::reqwest::Url;
+
+/// Counters are reported at most this often; `RateLimiter::counters` has
+/// the latest in between.
+pub const COUNTERS_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Requests per second allowed for each kind of host; 0 means unlimited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RateLimitConfig {
+    /// The StarCraft client's local web API.
+    pub local_api_tps: f64,
+    /// Everything else, in practice the replay CDNs.
+    pub remote_tps: f64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            local_api_tps: 10.0,
+            remote_tps: 2.0,
+        }
+    }
+}
+
+/// Requests let through since startup, by kind of host.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitCounters {
+    pub local_api_requests: u64,
+    pub remote_requests: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HostClass {
+    LocalApi,
+    Remote,
+}
+
+impl HostClass {
+    fn of(host: &str) -> Self {
+        match host {
+            "localhost" | "127.0.0.1" | "[::1]" | "::1" => HostClass::LocalApi,
+            _ => HostClass::Remote,
+        }
+    }
+
+    fn tps(self, config: &RateLimitConfig) -> f64 {
+        match self {
+            HostClass::LocalApi => config.local_api_tps,
+            HostClass::Remote => config.remote_tps,
+        }
+    }
+}
+
+/// Token bucket holding up to one second's worth of requests. Tokens may go
+/// negative: each caller reserves its slot immediately and sleeps off the
+/// debt, so concurrent callers queue up in arrival order.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(tps: f64, now: Instant) -> Self {
+        Self {
+            tokens: tps.max(1.0),
+            last: now,
+        }
+    }
+
+    /// Takes a token and returns how long the caller must wait before using it.
+    fn reserve(&mut self, tps: f64, now: Instant) -> Duration {
+        if tps <= 0.0 {
+            return Duration::ZERO;
+        }
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * tps).min(tps.max(1.0));
+        self.last = now;
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / tps)
+        }
+    }
+}
+
+struct LimiterState {
+    config: RateLimitConfig,
+    /// Keyed by `host:port`.
+    buckets: HashMap<String, TokenBucket>,
+    counters: RateLimitCounters,
+    last_reported: Option<Instant>,
+}
+
+/// Per-host rate limiting for every outbound request the app makes, both
+/// from the backend and (through `acquire_request_slot`) from the frontend.
+pub struct RateLimiter {
+    state: Mutex<LimiterState>,
+    on_counters: Box<dyn Fn(RateLimitCounters) + Send + Sync>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        config: RateLimitConfig,
+        on_counters: impl Fn(RateLimitCounters) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                config,
+                buckets: HashMap::new(),
+                counters: RateLimitCounters::default(),
+                last_reported: None,
+            }),
+            on_counters: Box::new(on_counters),
+        }
+    }
+
+    pub fn config(&self) -> RateLimitConfig {
+        self.state.lock().unwrap().config.clone()
+    }
+
+    /// Applies new rates; buckets start over so a lowered limit takes effect
+    /// without waiting out debt accrued under the old one.
+    pub fn configure(&self, config: RateLimitConfig) {
+        let mut state = self.state.lock().unwrap();
+        state.config = config;
+        state.buckets.clear();
+    }
+
+    pub fn counters(&self) -> RateLimitCounters {
+        self.state.lock().unwrap().counters
+    }
+
+    /// Waits until a request to `url` is allowed and counts it. The counters
+    /// are passed to `on_counters` unless they were within the last
+    /// `COUNTERS_REPORT_INTERVAL`.
+    pub async fn acquire(&self, url: &str) -> Result<(), String> {
+        let wait = self.reserve(url, Instant::now())?;
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    fn reserve(&self, url: &str, now: Instant) -> Result<Duration, String> {
+        let parsed = Url::parse(url).map_err(|e| format!("Invalid URL {url}: {e}"))?;
+        let host = parsed.host_str().unwrap_or_default().to_string();
+        let class = HostClass::of(&host);
+        let key = format!("{host}:{}", parsed.port_or_known_default().unwrap_or(0));
+
+        let (wait, counters) = {
+            let mut state = self.state.lock().unwrap();
+            let tps = class.tps(&state.config);
+            let wait = state
+                .buckets
+                .entry(key)
+                .or_insert_with(|| TokenBucket::new(tps, now))
+                .reserve(tps, now);
+            match class {
+                HostClass::LocalApi => state.counters.local_api_requests += 1,
+                HostClass::Remote => state.counters.remote_requests += 1,
+            }
+            let due = state
+                .last_reported
+                .is_none_or(|last| now.saturating_duration_since(last) >= COUNTERS_REPORT_INTERVAL);
+            if due {
+                state.last_reported = Some(now);
+            }
+            (wait, due.then_some(state.counters))
+        };
+
+        if let Some(counters) = counters {
+            (self.on_counters)(counters);
+        }
+        Ok(wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn limiter(local_api_tps: f64, remote_tps: f64) -> RateLimiter {
+        RateLimiter::new(
+            RateLimitConfig {
+                local_api_tps,
+                remote_tps,
+            },
+            |_| {},
+        )
+    }
+
+    #[test]
+    fn bursts_up_to_one_second_then_spaces_requests() {
+        let limiter = limiter(2.0, 0.0);
+        let now = Instant::now();
+        let url = "http://localhost:57421/web-api/v1/leaderboard";
+
+        assert_eq!(limiter.reserve(url, now).unwrap(), Duration::ZERO);
+        assert_eq!(limiter.reserve(url, now).unwrap(), Duration::ZERO);
+        assert_eq!(
+            limiter.reserve(url, now).unwrap(),
+            Duration::from_millis(500)
+        );
+        assert_eq!(limiter.reserve(url, now).unwrap(), Duration::from_secs(1));
+        // Tokens refill with time.
+        assert_eq!(
+            limiter.reserve(url, now + Duration::from_secs(1)).unwrap(),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn hosts_have_separate_buckets() {
+        let limiter = limiter(1.0, 1.0);
+        let now = Instant::now();
+
+        assert_eq!(
+            limiter.reserve("http://localhost:1000/a", now).unwrap(),
+            Duration::ZERO
+        );
+        assert_eq!(
+            limiter.reserve("http://localhost:2000/a", now).unwrap(),
+            Duration::ZERO
+        );
+        assert_eq!(
+            limiter
+                .reserve("https://cdn.example.com/r.rep", now)
+                .unwrap(),
+            Duration::ZERO
+        );
+        assert!(!limiter
+            .reserve("https://cdn.example.com/s.rep", now)
+            .unwrap()
+            .is_zero());
+
+        let counters = limiter.counters();
+        assert_eq!(counters.local_api_requests, 2);
+        assert_eq!(counters.remote_requests, 2);
+    }
+
+    #[test]
+    fn reports_counters_at_most_once_per_interval() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let limiter = RateLimiter::new(RateLimitConfig::default(), move |c| {
+            sink.lock().unwrap().push(c.local_api_requests)
+        });
+        let now = Instant::now();
+        let url = "http://localhost:57421/web-api/v1/gateway";
+
+        for ms in [0, 10, 500, 999, 1_000, 1_500, 2_500] {
+            limiter
+                .reserve(url, now + Duration::from_millis(ms))
+                .unwrap();
+        }
+
+        assert_eq!(*reported.lock().unwrap(), vec![1, 5, 7]);
+        assert_eq!(limiter.counters().local_api_requests, 7);
+    }
+
+    #[test]
+    fn zero_tps_is_unlimited() {
+        let limiter = limiter(0.0, 0.0);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(
+                limiter
+                    .reserve("https://cdn.example.com/r.rep", now)
+                    .unwrap(),
+                Duration::ZERO
+            );
+        }
+    }
+}
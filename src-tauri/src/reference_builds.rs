@@ -0,0 +1,340 @@
+//! Reference builds a team drills, and how closely a game's build order
+//! followed one. Builds are kept in a JSON file in the app data folder.
+//!
+//! Reference steps are matched in order to the earliest unused step of the
+//! same name. A matched step scores by how far it was from its target
+//! supply and time, a missing one scores 0, and steps the player added
+//! before the end of the reference count against the total.
+
+use std::{fs, path::PathBuf, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{persist, replay_parser::build_order::BuildStep};
+
+/// Time off target at which a step scores 0 for timing.
+const TIME_TOLERANCE_MS: u32 = 30_000;
+/// Supply off target at which a step scores 0 for supply.
+const SUPPLY_TOLERANCE: u32 = 4;
+/// How much an extra step weighs against the score, relative to a
+/// reference step.
+const EXTRA_STEP_WEIGHT: f64 = 0.5;
+/// Not reported as extra unless the reference lists them.
+const WORKERS: &[&str] = &["SCV", "Probe", "Drone"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceStep {
+    /// Name as in build orders, e.g. `Spawning Pool`.
+    pub item: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_supply: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_time_ms: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceBuild {
+    /// Assigned by the store; `None` when saving a new build.
+    #[serde(default)]
+    pub id: Option<u64>,
+    pub name: String,
+    pub race: String,
+    #[serde(default)]
+    pub notes: String,
+    pub steps: Vec<ReferenceStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepComparison {
+    pub reference: ReferenceStep,
+    /// The matching step of the game, if the player made it.
+    pub actual: Option<BuildStep>,
+    /// Positive when late.
+    pub time_delta_ms: Option<i64>,
+    /// Positive when at higher supply than the target.
+    pub supply_delta: Option<i32>,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildComparison {
+    pub steps: Vec<StepComparison>,
+    /// Reference steps the player never made.
+    pub missing: Vec<ReferenceStep>,
+    /// Steps the player made that the reference doesn't have, up to the
+    /// last matched step.
+    pub extra: Vec<BuildStep>,
+    /// 0 to 1.
+    pub adherence: f64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PersistedBuilds {
+    next_id: u64,
+    builds: Vec<ReferenceBuild>,
+}
+
+pub struct ReferenceBuildStore {
+    state: Mutex<PersistedBuilds>,
+    store_path: PathBuf,
+}
+
+impl ReferenceBuildStore {
+    pub fn new(store_path: PathBuf) -> Self {
+        let persisted = persist::load_json(&store_path, "reference-builds");
+        Self {
+            state: Mutex::new(persisted),
+            store_path,
+        }
+    }
+
+    pub fn list(&self) -> Vec<ReferenceBuild> {
+        self.state.lock().unwrap().builds.clone()
+    }
+
+    pub fn get(&self, id: u64) -> Option<ReferenceBuild> {
+        self.state
+            .lock()
+            .unwrap()
+            .builds
+            .iter()
+            .find(|b| b.id == Some(id))
+            .cloned()
+    }
+
+    /// Adds `build`, or replaces the one with its id.
+    pub fn save(&self, mut build: ReferenceBuild) -> Result<ReferenceBuild, String> {
+        let mut state = self.state.lock().unwrap();
+        match build.id {
+            Some(id) => {
+                let existing = state
+                    .builds
+                    .iter_mut()
+                    .find(|b| b.id == Some(id))
+                    .ok_or_else(|| format!("No reference build with id {id}"))?;
+                *existing = build.clone();
+            }
+            None => {
+                state.next_id += 1;
+                build.id = Some(state.next_id);
+                state.builds.push(build.clone());
+            }
+        }
+        self.persist(&state)?;
+        Ok(build)
+    }
+
+    pub fn delete(&self, id: u64) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        state.builds.retain(|b| b.id != Some(id));
+        self.persist(&state)
+    }
+
+    fn persist(&self, state: &PersistedBuilds) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+        if let Some(parent) = self.store_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        persist::write_atomically(&self.store_path, content.as_bytes()).map_err(|e| {
+            format!(
+                "Failed to save reference builds to {}: {e}",
+                self.store_path.display()
+            )
+        })
+    }
+}
+
+pub fn compare(reference: &ReferenceBuild, build: &[BuildStep]) -> BuildComparison {
+    let mut used = vec![false; build.len()];
+    let mut steps = Vec::with_capacity(reference.steps.len());
+    let mut missing = Vec::new();
+    let mut last_matched: Option<usize> = None;
+
+    for step in &reference.steps {
+        let found = build
+            .iter()
+            .enumerate()
+            .position(|(i, s)| !used[i] && s.name.eq_ignore_ascii_case(&step.item));
+        let Some(index) = found else {
+            missing.push(step.clone());
+            steps.push(StepComparison {
+                reference: step.clone(),
+                actual: None,
+                time_delta_ms: None,
+                supply_delta: None,
+                score: 0.0,
+            });
+            continue;
+        };
+        used[index] = true;
+        last_matched = last_matched.max(Some(index));
+        let actual = &build[index];
+        let time_delta_ms = step
+            .target_time_ms
+            .map(|target| actual.time_ms as i64 - target as i64);
+        let supply_delta = step
+            .target_supply
+            .map(|target| actual.supply as i32 - target as i32);
+        steps.push(StepComparison {
+            reference: step.clone(),
+            actual: Some(actual.clone()),
+            time_delta_ms,
+            supply_delta,
+            score: step_score(time_delta_ms, supply_delta),
+        });
+    }
+
+    let lists_workers = reference
+        .steps
+        .iter()
+        .any(|s| WORKERS.iter().any(|w| w.eq_ignore_ascii_case(&s.item)));
+    let extra: Vec<BuildStep> = match last_matched {
+        Some(last) => build[..last]
+            .iter()
+            .enumerate()
+            .filter(|(i, s)| !used[*i] && (lists_workers || !WORKERS.contains(&s.name.as_str())))
+            .map(|(_, s)| s.clone())
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let weight = steps.len() as f64 + EXTRA_STEP_WEIGHT * extra.len() as f64;
+    let adherence = if weight > 0.0 {
+        steps.iter().map(|s| s.score).sum::<f64>() / weight
+    } else {
+        0.0
+    };
+    BuildComparison {
+        steps,
+        missing,
+        extra,
+        adherence,
+    }
+}
+
+/// 1 on target, falling linearly to 0 at the tolerances; the average of
+/// the time and supply scores when both are targeted.
+fn step_score(time_delta_ms: Option<i64>, supply_delta: Option<i32>) -> f64 {
+    let scale = |delta: u64, tolerance: u32| (1.0 - delta as f64 / tolerance as f64).max(0.0);
+    let scores: Vec<f64> = [
+        time_delta_ms.map(|d| scale(d.unsigned_abs(), TIME_TOLERANCE_MS)),
+        supply_delta.map(|d| scale(d.unsigned_abs() as u64, SUPPLY_TOLERANCE)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if scores.is_empty() {
+        1.0
+    } else {
+        scores.iter().sum::<f64>() / scores.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{replay_parser::build_order::BuildItemKind, test_dir::TestDir};
+
+    fn step(item: &str, target_supply: Option<u32>, target_time_ms: Option<u32>) -> ReferenceStep {
+        ReferenceStep {
+            item: item.to_string(),
+            target_supply,
+            target_time_ms,
+        }
+    }
+
+    fn built(time_ms: u32, supply: u32, name: &str) -> BuildStep {
+        BuildStep {
+            frame: time_ms / 42,
+            time_ms,
+            supply,
+            kind: BuildItemKind::Building,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn compares_against_reference() {
+        let reference = ReferenceBuild {
+            id: None,
+            name: "12 Hatch".to_string(),
+            race: "Zerg".to_string(),
+            notes: String::new(),
+            steps: vec![
+                step("Hatchery", Some(12), Some(60_000)),
+                step("Spawning Pool", Some(11), None),
+                step("Extractor", None, Some(90_000)),
+                step("Lair", None, None),
+            ],
+        };
+        let build = vec![
+            built(30_000, 9, "Drone"),
+            built(60_000, 12, "Hatchery"),
+            built(70_000, 11, "Evolution Chamber"),
+            // 2 supply over: half marks.
+            built(75_000, 13, "Spawning Pool"),
+            // 45s late: no marks.
+            built(135_000, 12, "Extractor"),
+            built(200_000, 20, "Hydralisk Den"),
+        ];
+        let comparison = compare(&reference, &build);
+
+        let deltas: Vec<(Option<i64>, Option<i32>, f64)> = comparison
+            .steps
+            .iter()
+            .map(|s| (s.time_delta_ms, s.supply_delta, s.score))
+            .collect();
+        assert_eq!(
+            deltas,
+            vec![
+                (Some(0), Some(0), 1.0),
+                (None, Some(2), 0.5),
+                (Some(45_000), None, 0.0),
+                (None, None, 0.0),
+            ]
+        );
+        assert_eq!(comparison.missing, vec![step("Lair", None, None)]);
+        // The drone isn't extra, nor is the den after the last matched step.
+        let extra: Vec<&str> = comparison.extra.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(extra, vec!["Evolution Chamber"]);
+        assert_eq!(comparison.adherence, 1.5 / 4.5);
+    }
+
+    #[test]
+    fn persists_builds() {
+        let dir = TestDir::new("reference-builds");
+        let path = dir.join("reference-builds.json");
+        let store = ReferenceBuildStore::new(path.clone());
+        let mut build = store
+            .save(ReferenceBuild {
+                id: None,
+                name: "BBS".to_string(),
+                race: "Terran".to_string(),
+                notes: String::new(),
+                steps: vec![step("Barracks", Some(8), None)],
+            })
+            .unwrap();
+        assert_eq!(build.id, Some(1));
+        build.notes = "Proxy".to_string();
+        store.save(build.clone()).unwrap();
+
+        let reopened = ReferenceBuildStore::new(path.clone());
+        assert_eq!(reopened.list(), vec![build.clone()]);
+        reopened.delete(1).unwrap();
+        assert!(ReferenceBuildStore::new(path.clone()).list().is_empty());
+
+        // A file that no longer parses is set aside, not overwritten.
+        fs::write(&path, "{\"builds\": [").unwrap();
+        let store = ReferenceBuildStore::new(path.clone());
+        assert!(store.list().is_empty());
+        store.save(ReferenceBuild { id: None, ..build }).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("reference-builds.json.corrupt")).unwrap(),
+            "{\"builds\": ["
+        );
+    }
+}
@@ -0,0 +1,935 @@
+//! Personal replay library: every downloaded, cached or ingested replay is
+//! indexed into SQLite. A game is identified by its fingerprint, so the same
+//! game saved in several places is one entry with several files.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+    time::UNIX_EPOCH,
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::{
+    clock,
+    openings::{OpeningBook, OpeningDefinition},
+    replay_parser::{build_order::BuildStep, ParsedReplay, ReplayParser, MS_PER_FRAME},
+    replay_search::{self, ChatHit, ChatSearch, ReplaySearch, SearchResults},
+};
+
+/// Bumped whenever `SCHEMA` changes. The library only mirrors replays on
+/// disk, so an outdated one is dropped and filled again by the next rescan.
+const SCHEMA_VERSION: i32 = 5;
+
+/// Coarsest mtime resolution of the filesystems replays live on: FAT keeps
+/// two seconds.
+const MTIME_GRANULARITY_MS: i64 = 2_000;
+
+const DROP_SCHEMA: &str = "
+    DROP TABLE IF EXISTS chat_fts;
+    DROP TABLE IF EXISTS chat;
+    DROP TABLE IF EXISTS files;
+    DROP TABLE IF EXISTS players;
+    DROP TABLE IF EXISTS games;
+";
+
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS games (
+        id INTEGER PRIMARY KEY,
+        fingerprint TEXT NOT NULL UNIQUE,
+        map_name TEXT NOT NULL,
+        matchup TEXT NOT NULL,
+        matchup_key TEXT NOT NULL,
+        start_time_ms INTEGER NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        indexed_at_ms INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS players (
+        game_id INTEGER NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+        slot INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        race TEXT NOT NULL,
+        side INTEGER NOT NULL,
+        outcome TEXT NOT NULL,
+        apm INTEGER NOT NULL,
+        -- JSON array of `BuildStep`s.
+        build TEXT NOT NULL,
+        opening TEXT,
+        opening_confidence REAL,
+        PRIMARY KEY (game_id, slot)
+    );
+    CREATE INDEX IF NOT EXISTS players_name ON players(name COLLATE NOCASE);
+    CREATE TABLE IF NOT EXISTS files (
+        path TEXT PRIMARY KEY,
+        game_id INTEGER NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+        digest TEXT NOT NULL,
+        mtime_ms INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        indexed_at_ms INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS files_game ON files(game_id);
+    CREATE TABLE IF NOT EXISTS chat (
+        id INTEGER PRIMARY KEY,
+        game_id INTEGER NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+        seq INTEGER NOT NULL,
+        frame INTEGER NOT NULL,
+        sender TEXT NOT NULL,
+        message TEXT NOT NULL,
+        UNIQUE (game_id, seq)
+    );
+    CREATE INDEX IF NOT EXISTS chat_sender ON chat(sender COLLATE NOCASE);
+    -- Full-text index over `chat`, kept in sync by the triggers below.
+    CREATE VIRTUAL TABLE IF NOT EXISTS chat_fts USING fts5(
+        message, content = 'chat', content_rowid = 'id'
+    );
+    CREATE TRIGGER IF NOT EXISTS chat_fts_insert AFTER INSERT ON chat BEGIN
+        INSERT INTO chat_fts (rowid, message) VALUES (new.id, new.message);
+    END;
+    CREATE TRIGGER IF NOT EXISTS chat_fts_delete AFTER DELETE ON chat BEGIN
+        INSERT INTO chat_fts (chat_fts, rowid, message) VALUES ('delete', old.id, old.message);
+    END;
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Unknown,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Win => "Win",
+            Outcome::Loss => "Loss",
+            Outcome::Unknown => "Unknown",
+        }
+    }
+
+    /// From a player's `result` in web API match history.
+    pub fn from_ladder_result(result: &str) -> Self {
+        match result.to_lowercase().as_str() {
+            "win" => Outcome::Win,
+            "loss" => Outcome::Loss,
+            _ => Outcome::Unknown,
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "Win" => Outcome::Win,
+            "Loss" => Outcome::Loss,
+            _ => Outcome::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryPlayer {
+    pub name: String,
+    pub race: String,
+    /// Index of the player's side in the game, as in `ParsedReplay::teams`.
+    pub side: u32,
+    pub outcome: Outcome,
+    pub apm: u32,
+    /// Best match in the opening book, e.g. `12 Hatch`.
+    pub opening: Option<String>,
+    pub opening_confidence: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryReplay {
+    pub id: i64,
+    pub fingerprint: String,
+    pub map_name: String,
+    pub matchup: String,
+    pub start_time_ms: u64,
+    pub duration_ms: u32,
+    pub players: Vec<LibraryPlayer>,
+    /// Every file on disk holding this game.
+    pub paths: Vec<String>,
+}
+
+/// A file as it was last indexed.
+struct KnownFile {
+    digest: String,
+    mtime_ms: i64,
+    size: i64,
+    /// When it was last read, to tell whether a same-mtime rewrite could
+    /// have gone unnoticed.
+    indexed_at_ms: i64,
+}
+
+/// What indexing a file did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum IndexOutcome {
+    Added,
+    Updated,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RescanSummary {
+    pub added: u32,
+    pub updated: u32,
+    pub unchanged: u32,
+    pub removed: u32,
+    pub failed: u32,
+}
+
+pub struct ReplayLibrary {
+    conn: Mutex<Connection>,
+    /// Taken after `conn` when both are needed.
+    openings: RwLock<OpeningBook>,
+}
+
+impl ReplayLibrary {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {e}"))?;
+        }
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open replay library {}: {e}", path.display()))?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        let version: i32 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read replay library version: {e}"))?;
+        if version != SCHEMA_VERSION {
+            conn.execute_batch(DROP_SCHEMA)
+                .map_err(|e| format!("Failed to reset replay library: {e}"))?;
+        }
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to create replay library schema: {e}"))?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+            .map_err(|e| format!("Failed to set replay library version: {e}"))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            openings: RwLock::new(OpeningBook::default()),
+        })
+    }
+
+    pub fn opening_definitions(&self) -> Vec<OpeningDefinition> {
+        self.openings.read().unwrap().definitions().to_vec()
+    }
+
+    /// Classifies openings with `book` from now on, reclassifying every
+    /// indexed build.
+    pub fn set_openings(&self, book: OpeningBook) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to reclassify openings: {e}"))?;
+        let db_err = |e: rusqlite::Error| format!("Failed to reclassify openings: {e}");
+        let players: Vec<(i64, i64, String, String)> = {
+            let mut stmt = tx
+                .prepare("SELECT game_id, slot, race, build FROM players")
+                .map_err(db_err)?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                })
+                .map_err(db_err)?;
+            rows.flatten().collect()
+        };
+        for (game_id, slot, race, build) in players {
+            let build: Vec<BuildStep> = serde_json::from_str(&build).unwrap_or_default();
+            let opening = book.classify(&race, &build);
+            tx.execute(
+                "UPDATE players SET opening = ?1, opening_confidence = ?2
+                 WHERE game_id = ?3 AND slot = ?4",
+                params![
+                    opening.as_ref().map(|o| &o.name),
+                    opening.as_ref().map(|o| o.confidence),
+                    game_id,
+                    slot
+                ],
+            )
+            .map_err(db_err)?;
+        }
+        tx.commit().map_err(db_err)?;
+        // Swapped before `conn` is released, so no replay indexed in between
+        // is classified with the old book.
+        *self.openings.write().unwrap() = book;
+        drop(conn);
+        Ok(())
+    }
+
+    /// Indexes the replay at `path`, skipping the parse when its size and
+    /// mtime, or failing that its digest, match what was indexed before.
+    pub fn index_file(&self, path: &Path) -> Result<IndexOutcome, String> {
+        let (mtime_ms, size) = stat(path)?;
+        let key = path.to_string_lossy().to_string();
+
+        let known = self.file_state(&key)?;
+        if let Some(file) = &known {
+            // A file rewritten within the mtime granularity of when it was
+            // read, as the game does with LastReplay.rep, can keep both size
+            // and mtime, so only later reads are trusted without a digest.
+            let settled = file.indexed_at_ms - file.mtime_ms > MTIME_GRANULARITY_MS;
+            if settled && file.mtime_ms == mtime_ms && file.size == size {
+                return Ok(IndexOutcome::Unchanged);
+            }
+        }
+
+        let bytes = fs::read(path).map_err(|e| format!("Failed to read replay: {e}"))?;
+        let digest = digest(&bytes);
+        if let Some(file) = &known {
+            if file.digest == digest {
+                self.conn
+                    .lock()
+                    .unwrap()
+                    .execute(
+                        "UPDATE files SET mtime_ms = ?1, size = ?2, indexed_at_ms = ?3 WHERE path = ?4",
+                        params![mtime_ms, size, clock::now_ms() as i64, key],
+                    )
+                    .map_err(|e| format!("Failed to update replay file: {e}"))?;
+                return Ok(IndexOutcome::Unchanged);
+            }
+        }
+
+        let parsed = ReplayParser::new(&bytes)
+            .parse()
+            .map_err(|e| format!("Failed to parse replay: {e}"))?;
+        self.insert(&key, &digest, mtime_ms, size, &parsed)?;
+        Ok(if known.is_some() {
+            IndexOutcome::Updated
+        } else {
+            IndexOutcome::Added
+        })
+    }
+
+    /// Walks `roots` for `.rep` files, indexing new and changed ones and
+    /// dropping entries for files under `roots` that no longer exist.
+    pub fn rescan(&self, roots: &[PathBuf]) -> Result<RescanSummary, String> {
+        let mut summary = RescanSummary::default();
+        let mut found = Vec::new();
+        for root in roots {
+            collect_replays(root, &mut found);
+        }
+        // Roots may nest, e.g. the download folder inside the replay folder.
+        found.sort();
+        found.dedup();
+
+        for path in &found {
+            match self.index_file(path) {
+                Ok(IndexOutcome::Added) => summary.added += 1,
+                Ok(IndexOutcome::Updated) => summary.updated += 1,
+                Ok(IndexOutcome::Unchanged) => summary.unchanged += 1,
+                Err(e) => {
+                    println!("[replay-library] Skipping {}: {e}", path.display());
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        let indexed: Vec<String> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT path FROM files")
+                .map_err(|e| format!("Failed to list replay files: {e}"))?;
+            let rows = stmt
+                .query_map([], |row| row.get(0))
+                .map_err(|e| format!("Failed to list replay files: {e}"))?;
+            rows.flatten().collect()
+        };
+        for path in indexed {
+            let path = PathBuf::from(path);
+            let in_roots = roots.iter().any(|root| path.starts_with(root));
+            if in_roots && !path.exists() {
+                self.remove_file(&path)?;
+                summary.removed += 1;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Forgets a file, and its game once no other file holds it.
+    pub fn remove_file(&self, path: &Path) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        let key = path.to_string_lossy();
+        let game_id: Option<i64> = conn
+            .query_row("SELECT game_id FROM files WHERE path = ?1", [&key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|e| format!("Failed to look up replay file: {e}"))?;
+        conn.execute("DELETE FROM files WHERE path = ?1", [&key])
+            .map_err(|e| format!("Failed to remove replay file: {e}"))?;
+        if let Some(game_id) = game_id {
+            delete_orphaned_game(&conn, game_id)?;
+        }
+        Ok(())
+    }
+
+    pub fn count(&self) -> Result<u64, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM games", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to count replays: {e}"))
+    }
+
+    /// Games newest first.
+    pub fn list(&self, offset: u32, limit: u32) -> Result<Vec<LibraryReplay>, String> {
+        let ids: Vec<i64> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id FROM games ORDER BY start_time_ms DESC, id DESC LIMIT ?1 OFFSET ?2",
+                )
+                .map_err(|e| format!("Failed to list replays: {e}"))?;
+            let rows = stmt
+                .query_map(params![limit, offset], |row| row.get(0))
+                .map_err(|e| format!("Failed to list replays: {e}"))?;
+            rows.flatten().collect()
+        };
+        self.get_many(&ids)
+    }
+
+    /// Games matching `search`, in its order, with the total number of matches.
+    pub fn search(
+        &self,
+        search: &ReplaySearch,
+        offset: u32,
+        limit: u32,
+    ) -> Result<SearchResults, String> {
+        let (filter, mut values) = search.to_sql();
+        let (total, ids) = {
+            let conn = self.conn.lock().unwrap();
+            let total: u64 = conn
+                .query_row(
+                    &format!("SELECT COUNT(*) FROM games g WHERE {filter}"),
+                    rusqlite::params_from_iter(&values),
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Failed to search replays: {e}"))?;
+
+            values.push(limit.into());
+            values.push(offset.into());
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT g.id FROM games g WHERE {filter} ORDER BY {} LIMIT ? OFFSET ?",
+                    search.order_by()
+                ))
+                .map_err(|e| format!("Failed to search replays: {e}"))?;
+            let ids: Vec<i64> = stmt
+                .query_map(rusqlite::params_from_iter(&values), |row| row.get(0))
+                .map_err(|e| format!("Failed to search replays: {e}"))?
+                .flatten()
+                .collect();
+            (total, ids)
+        };
+        Ok(SearchResults {
+            total,
+            replays: self.get_many(&ids)?,
+        })
+    }
+
+    /// Games any of `names` played in, newest first, optionally limited to
+    /// those starting in `[after_ms, before_ms)`.
+    pub fn games_of(
+        &self,
+        names: &[String],
+        after_ms: Option<u64>,
+        before_ms: Option<u64>,
+    ) -> Result<Vec<LibraryReplay>, String> {
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+        let placeholders = vec!["?"; names.len()].join(", ");
+        let mut values: Vec<rusqlite::types::Value> =
+            names.iter().map(|n| n.clone().into()).collect();
+        values.push((after_ms.unwrap_or(0) as i64).into());
+        values.push(before_ms.map_or(i64::MAX, |b| b as i64).into());
+
+        let ids: Vec<i64> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT g.id FROM games g
+                     WHERE EXISTS (SELECT 1 FROM players p WHERE p.game_id = g.id
+                                   AND p.name COLLATE NOCASE IN ({placeholders}))
+                     AND g.start_time_ms >= ? AND g.start_time_ms < ?
+                     ORDER BY g.start_time_ms DESC, g.id DESC"
+                ))
+                .map_err(|e| format!("Failed to list player games: {e}"))?;
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(&values), |row| row.get(0))
+                .map_err(|e| format!("Failed to list player games: {e}"))?;
+            rows.flatten().collect()
+        };
+        self.get_many(&ids)
+    }
+
+    /// Chat lines matching `search`, best matches first.
+    pub fn search_chat(
+        &self,
+        search: &ChatSearch,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<ChatHit>, String> {
+        let mut conditions = vec!["1"];
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        let text = search.text.trim();
+        // Without text there is nothing to rank by, so the sender's lines
+        // come newest game first.
+        let (from, order) = if text.is_empty() {
+            ("chat c", "g.start_time_ms DESC, c.seq")
+        } else {
+            conditions.push("chat_fts MATCH ?");
+            values.push(text.to_string().into());
+            (
+                "chat_fts JOIN chat c ON c.id = chat_fts.rowid",
+                "chat_fts.rank, g.start_time_ms DESC, c.seq",
+            )
+        };
+        if let Some(sender) = &search.sender {
+            conditions.push("c.sender = ? COLLATE NOCASE");
+            values.push(sender.clone().into());
+        }
+        values.push(limit.into());
+        values.push(offset.into());
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT c.game_id, g.map_name, g.start_time_ms, c.frame, c.sender, c.message
+                 FROM {from} JOIN games g ON g.id = c.game_id
+                 WHERE {}
+                 ORDER BY {order}
+                 LIMIT ? OFFSET ?",
+                conditions.join(" AND ")
+            ))
+            .map_err(|e| format!("Failed to search chat: {e}"))?;
+        let hits = stmt
+            .query_map(rusqlite::params_from_iter(&values), |row| {
+                let frame: u32 = row.get(3)?;
+                Ok(ChatHit {
+                    game_id: row.get(0)?,
+                    map_name: row.get(1)?,
+                    start_time_ms: row.get(2)?,
+                    frame,
+                    game_time_ms: frame * MS_PER_FRAME,
+                    sender: row.get(4)?,
+                    message: row.get(5)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to search chat: {e}"))?;
+        Ok(hits)
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<LibraryReplay>, String> {
+        Ok(self.get_many(&[id])?.into_iter().next())
+    }
+
+    /// The build order `player` made in game `id`, if they played in it.
+    pub fn build_of(&self, id: i64, player: &str) -> Result<Option<Vec<BuildStep>>, String> {
+        let conn = self.conn.lock().unwrap();
+        let build: Option<String> = conn
+            .query_row(
+                "SELECT build FROM players WHERE game_id = ?1 AND name = ?2 COLLATE NOCASE",
+                params![id, player],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to load build order: {e}"))?;
+        build
+            .map(|b| serde_json::from_str(&b))
+            .transpose()
+            .map_err(|e| format!("Failed to load build order: {e}"))
+    }
+
+    /// Loads games by id, in the order given.
+    pub fn get_many(&self, ids: &[i64]) -> Result<Vec<LibraryReplay>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut game_stmt = conn
+            .prepare(
+                "SELECT fingerprint, map_name, matchup, start_time_ms, duration_ms
+                 FROM games WHERE id = ?1",
+            )
+            .map_err(|e| format!("Failed to load replay: {e}"))?;
+        let mut player_stmt = conn
+            .prepare(
+                "SELECT name, race, side, outcome, apm, opening, opening_confidence FROM players
+                 WHERE game_id = ?1 ORDER BY slot",
+            )
+            .map_err(|e| format!("Failed to load replay players: {e}"))?;
+        let mut file_stmt = conn
+            .prepare("SELECT path FROM files WHERE game_id = ?1 ORDER BY path")
+            .map_err(|e| format!("Failed to load replay files: {e}"))?;
+
+        let mut replays = Vec::new();
+        for &id in ids {
+            let game = game_stmt
+                .query_row([id], |row| {
+                    Ok(LibraryReplay {
+                        id,
+                        fingerprint: row.get(0)?,
+                        map_name: row.get(1)?,
+                        matchup: row.get(2)?,
+                        start_time_ms: row.get(3)?,
+                        duration_ms: row.get(4)?,
+                        players: Vec::new(),
+                        paths: Vec::new(),
+                    })
+                })
+                .optional()
+                .map_err(|e| format!("Failed to load replay: {e}"))?;
+            let Some(mut game) = game else {
+                continue;
+            };
+            game.players = player_stmt
+                .query_map([id], |row| {
+                    Ok(LibraryPlayer {
+                        name: row.get(0)?,
+                        race: row.get(1)?,
+                        side: row.get(2)?,
+                        outcome: Outcome::parse(&row.get::<_, String>(3)?),
+                        apm: row.get(4)?,
+                        opening: row.get(5)?,
+                        opening_confidence: row.get(6)?,
+                    })
+                })
+                .map_err(|e| format!("Failed to load replay players: {e}"))?
+                .flatten()
+                .collect();
+            game.paths = file_stmt
+                .query_map([id], |row| row.get(0))
+                .map_err(|e| format!("Failed to load replay files: {e}"))?
+                .flatten()
+                .collect();
+            replays.push(game);
+        }
+        Ok(replays)
+    }
+
+    fn file_state(&self, path: &str) -> Result<Option<KnownFile>, String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT digest, mtime_ms, size, indexed_at_ms FROM files WHERE path = ?1",
+                [path],
+                |row| {
+                    Ok(KnownFile {
+                        digest: row.get(0)?,
+                        mtime_ms: row.get(1)?,
+                        size: row.get(2)?,
+                        indexed_at_ms: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| format!("Failed to look up replay file: {e}"))
+    }
+
+    fn insert(
+        &self,
+        path: &str,
+        digest: &str,
+        mtime_ms: i64,
+        size: i64,
+        parsed: &ParsedReplay,
+    ) -> Result<(), String> {
+        let fingerprint = fingerprint(parsed);
+        let start_time_ms = parsed
+            .game_info
+            .start_time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+
+        let mut conn = self.conn.lock().unwrap();
+        let openings = self.openings.read().unwrap();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to index replay: {e}"))?;
+        let db_err = |e: rusqlite::Error| format!("Failed to index replay: {e}");
+
+        let previous_game: Option<i64> = tx
+            .query_row("SELECT game_id FROM files WHERE path = ?1", [path], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(db_err)?;
+
+        let existing: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM games WHERE fingerprint = ?1",
+                [&fingerprint],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_err)?;
+        let game_id = match existing {
+            Some(id) => id,
+            None => {
+                tx.execute(
+                    "INSERT INTO games (fingerprint, map_name, matchup, matchup_key, start_time_ms, duration_ms, indexed_at_ms)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        fingerprint,
+                        parsed.game_info.map_name,
+                        parsed.matchup(),
+                        replay_search::matchup_key(&parsed.matchup()),
+                        start_time_ms,
+                        parsed.duration_ms(),
+                        clock::now_ms() as i64
+                    ],
+                )
+                .map_err(db_err)?;
+                let id = tx.last_insert_rowid();
+
+                let winners: Vec<u8> = parsed.winners().iter().map(|p| p.id).collect();
+                for (side, team) in parsed.teams().iter().enumerate() {
+                    for player in team {
+                        let outcome = match (winners.is_empty(), winners.contains(&player.id)) {
+                            (true, _) => Outcome::Unknown,
+                            (false, true) => Outcome::Win,
+                            (false, false) => Outcome::Loss,
+                        };
+                        let race = format!("{:?}", player.race);
+                        let build = parsed.build_order(player.id);
+                        let opening = openings.classify(&race, &build);
+                        tx.execute(
+                            "INSERT INTO players (game_id, slot, name, race, side, outcome, apm,
+                                                  build, opening, opening_confidence)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                            params![
+                                id,
+                                player.slot_id,
+                                player.name,
+                                race,
+                                side as u32,
+                                outcome.as_str(),
+                                parsed.apm(player.id),
+                                serde_json::to_string(&build).map_err(|e| e.to_string())?,
+                                opening.as_ref().map(|o| &o.name),
+                                opening.as_ref().map(|o| o.confidence)
+                            ],
+                        )
+                        .map_err(db_err)?;
+                    }
+                }
+                for (seq, chat) in parsed.chat_messages().iter().enumerate() {
+                    tx.execute(
+                        "INSERT INTO chat (game_id, seq, frame, sender, message)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![id, seq, chat.frame_number, chat.sender_name, chat.message],
+                    )
+                    .map_err(db_err)?;
+                }
+                id
+            }
+        };
+
+        tx.execute(
+            "INSERT INTO files (path, game_id, digest, mtime_ms, size, indexed_at_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(path) DO UPDATE SET
+                game_id = ?2, digest = ?3, mtime_ms = ?4, size = ?5, indexed_at_ms = ?6",
+            params![
+                path,
+                game_id,
+                digest,
+                mtime_ms,
+                size,
+                clock::now_ms() as i64
+            ],
+        )
+        .map_err(db_err)?;
+        if let Some(previous) = previous_game.filter(|&p| p != game_id) {
+            delete_orphaned_game(&tx, previous)?;
+        }
+        tx.commit().map_err(db_err)
+    }
+}
+
+fn delete_orphaned_game(conn: &Connection, game_id: i64) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM games WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM files WHERE game_id = ?1)",
+        [game_id],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("Failed to remove replay: {e}"))
+}
+
+fn stat(path: &Path) -> Result<(i64, i64), String> {
+    let metadata = fs::metadata(path).map_err(|e| format!("Failed to stat replay: {e}"))?;
+    let mtime_ms = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default();
+    Ok((mtime_ms, metadata.len() as i64))
+}
+
+fn collect_replays(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_replays(&path, out);
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("rep"))
+        {
+            out.push(path);
+        }
+    }
+}
+
+/// 64-bit FNV-1a, stable across builds (unlike `DefaultHasher`), as hex.
+fn fnv1a(parts: &[&[u8]]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        for &b in *part {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+/// Content digest of a replay file; also names partial downloads.
+pub fn digest(bytes: &[u8]) -> String {
+    fnv1a(&[bytes])
+}
+
+/// Identifies a game independently of who saved it: every participant's
+/// replay of a game has the same start time, length, map and players.
+fn fingerprint(parsed: &ParsedReplay) -> String {
+    let start = parsed
+        .game_info
+        .start_time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+        .to_le_bytes();
+    let frames = parsed.game_info.frames.to_le_bytes();
+    let mut players: Vec<&str> = parsed.players().iter().map(|p| p.name.as_str()).collect();
+    players.sort_unstable();
+    let players = players.join("\0");
+    fnv1a(&[
+        &start,
+        &frames,
+        parsed.game_info.map_name.as_bytes(),
+        players.as_bytes(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        replay_parser::{builder::ReplayBuilder, game_info::Race},
+        test_dir::TestDir,
+    };
+    use std::sync::Arc;
+
+    fn game(start_time: u32, map: &str) -> Vec<u8> {
+        ReplayBuilder::new(map)
+            .frames(12_000)
+            .start_time(start_time)
+            .player("Flash", Race::Terran)
+            .player("Jaedong", Race::Zerg)
+            .leave(11_000, 1)
+            .build()
+    }
+
+    #[test]
+    fn indexes_games_once_across_copies() {
+        let dir = TestDir::new("replay-library-copies");
+        let library = ReplayLibrary::open_in_memory().unwrap();
+        fs::write(dir.join("a.rep"), game(1_700_000_000, "Polypoid")).unwrap();
+        fs::write(dir.join("b.rep"), game(1_700_000_000, "Polypoid")).unwrap();
+        fs::write(dir.join("c.rep"), game(1_700_100_000, "Eclipse")).unwrap();
+
+        let summary = library.rescan(&[dir.to_path_buf()]).unwrap();
+        assert_eq!(summary.added, 3);
+        assert_eq!(library.count().unwrap(), 2);
+
+        let games = library.list(0, 10).unwrap();
+        assert_eq!(games[0].map_name, "Eclipse");
+        assert_eq!(games[1].paths.len(), 2);
+        assert_eq!(games[1].matchup, "TvZ");
+        assert_eq!(games[1].start_time_ms, 1_700_000_000_000);
+        assert_eq!(games[1].players[0].name, "Flash");
+        assert_eq!(games[1].players[0].outcome, Outcome::Win);
+        assert_eq!(games[1].players[1].outcome, Outcome::Loss);
+
+        let fetched = library.get(games[1].id).unwrap().unwrap();
+        assert_eq!(fetched.fingerprint, games[1].fingerprint);
+        assert!(library.get(-1).unwrap().is_none());
+    }
+
+    #[test]
+    fn rescans_incrementally() {
+        let dir = TestDir::new("replay-library-rescan");
+        let library = ReplayLibrary::open_in_memory().unwrap();
+        let changing = dir.join("LastReplay.rep");
+        fs::write(&changing, game(1_700_000_000, "Polypoid")).unwrap();
+        fs::write(dir.join("kept.rep"), game(1_700_200_000, "Vermeer")).unwrap();
+        library.rescan(&[dir.to_path_buf()]).unwrap();
+
+        let summary = library.rescan(&[dir.to_path_buf()]).unwrap();
+        assert_eq!((summary.added, summary.unchanged), (0, 2));
+
+        // LastReplay.rep is overwritten by the next game; the old game goes
+        // away with its only file.
+        fs::write(&changing, game(1_700_300_000, "Radeon")).unwrap();
+        let summary = library.rescan(&[dir.to_path_buf()]).unwrap();
+        assert_eq!(summary.updated, 1);
+        let maps: Vec<String> = library
+            .list(0, 10)
+            .unwrap()
+            .into_iter()
+            .map(|g| g.map_name)
+            .collect();
+        assert_eq!(maps, vec!["Radeon", "Vermeer"]);
+
+        fs::remove_file(dir.join("kept.rep")).unwrap();
+        let summary = library.rescan(&[dir.to_path_buf()]).unwrap();
+        assert_eq!(summary.removed, 1);
+        assert_eq!(library.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn reclassifies_while_indexing() {
+        let dir = TestDir::new("replay-library-openings");
+        let library = Arc::new(ReplayLibrary::open_in_memory().unwrap());
+        let reclassifying = library.clone();
+        let reloads = std::thread::spawn(move || {
+            for _ in 0..100 {
+                reclassifying.set_openings(OpeningBook::default()).unwrap();
+            }
+        });
+
+        for i in 0..100 {
+            let path = dir.join(format!("{i}.rep"));
+            fs::write(&path, game(1_700_000_000 + i, "Polypoid")).unwrap();
+            library.index_file(&path).unwrap();
+        }
+        reloads.join().unwrap();
+        assert_eq!(library.count().unwrap(), 100);
+    }
+}
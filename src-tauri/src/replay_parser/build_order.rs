@@ -0,0 +1,385 @@
+//! Build orders and APM from the command stream. Steps are the commands as
+//! issued: a train that failed for lack of minerals still shows up, and
+//! supply is counted from what was ordered rather than what finished.
+
+use serde::{Deserialize, Serialize};
+
+use crate::replay_parser::{game_info::Race, ParsedReplay, MS_PER_FRAME};
+
+/// Supply every race starts with: four workers.
+const STARTING_SUPPLY: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BuildItemKind {
+    Unit,
+    Building,
+    Upgrade,
+    Tech,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildStep {
+    pub frame: u32,
+    pub time_ms: u32,
+    /// Supply in use when the step was ordered, as in "9 pool".
+    pub supply: u32,
+    pub kind: BuildItemKind,
+    pub name: String,
+}
+
+/// Trainable units: id, name and supply. Zerglings and scourge count as the
+/// pair a larva hatches into; morphs count only the supply they add.
+const UNITS: &[(u16, &str, u32)] = &[
+    (0, "Marine", 1),
+    (1, "Ghost", 1),
+    (2, "Vulture", 2),
+    (3, "Goliath", 2),
+    (5, "Siege Tank", 2),
+    (7, "SCV", 1),
+    (8, "Wraith", 2),
+    (9, "Science Vessel", 2),
+    (11, "Dropship", 2),
+    (12, "Battlecruiser", 6),
+    (32, "Firebat", 1),
+    (34, "Medic", 1),
+    (58, "Valkyrie", 3),
+    (37, "Zergling", 1),
+    (38, "Hydralisk", 1),
+    (39, "Ultralisk", 4),
+    (41, "Drone", 1),
+    (42, "Overlord", 0),
+    (43, "Mutalisk", 2),
+    (44, "Guardian", 0),
+    (45, "Queen", 2),
+    (46, "Defiler", 2),
+    (47, "Scourge", 1),
+    (62, "Devourer", 0),
+    (103, "Lurker", 1),
+    (60, "Corsair", 2),
+    (61, "Dark Templar", 2),
+    (64, "Probe", 1),
+    (65, "Zealot", 2),
+    (66, "Dragoon", 2),
+    (67, "High Templar", 2),
+    (69, "Shuttle", 2),
+    (70, "Scout", 3),
+    (71, "Arbiter", 4),
+    (72, "Carrier", 6),
+    (83, "Reaver", 4),
+    (84, "Observer", 1),
+];
+
+const BUILDINGS: &[(u16, &str)] = &[
+    (106, "Command Center"),
+    (107, "Comsat Station"),
+    (108, "Nuclear Silo"),
+    (109, "Supply Depot"),
+    (110, "Refinery"),
+    (111, "Barracks"),
+    (112, "Academy"),
+    (113, "Factory"),
+    (114, "Starport"),
+    (115, "Control Tower"),
+    (116, "Science Facility"),
+    (117, "Covert Ops"),
+    (118, "Physics Lab"),
+    (120, "Machine Shop"),
+    (122, "Engineering Bay"),
+    (123, "Armory"),
+    (124, "Missile Turret"),
+    (125, "Bunker"),
+    (131, "Hatchery"),
+    (132, "Lair"),
+    (133, "Hive"),
+    (134, "Nydus Canal"),
+    (135, "Hydralisk Den"),
+    (136, "Defiler Mound"),
+    (137, "Greater Spire"),
+    (138, "Queen's Nest"),
+    (139, "Evolution Chamber"),
+    (140, "Ultralisk Cavern"),
+    (141, "Spire"),
+    (142, "Spawning Pool"),
+    (143, "Creep Colony"),
+    (144, "Spore Colony"),
+    (146, "Sunken Colony"),
+    (149, "Extractor"),
+    (154, "Nexus"),
+    (155, "Robotics Facility"),
+    (156, "Pylon"),
+    (157, "Assimilator"),
+    (159, "Observatory"),
+    (160, "Gateway"),
+    (162, "Photon Cannon"),
+    (163, "Citadel of Adun"),
+    (164, "Cybernetics Core"),
+    (165, "Templar Archives"),
+    (166, "Forge"),
+    (167, "Stargate"),
+    (169, "Fleet Beacon"),
+    (170, "Arbiter Tribunal"),
+    (171, "Robotics Support Bay"),
+    (172, "Shield Battery"),
+];
+
+const UPGRADES: &[(u8, &str)] = &[
+    (0, "Terran Infantry Armor"),
+    (1, "Terran Vehicle Plating"),
+    (2, "Terran Ship Plating"),
+    (3, "Zerg Carapace"),
+    (4, "Zerg Flyer Carapace"),
+    (5, "Protoss Ground Armor"),
+    (6, "Protoss Air Armor"),
+    (7, "Terran Infantry Weapons"),
+    (8, "Terran Vehicle Weapons"),
+    (9, "Terran Ship Weapons"),
+    (10, "Zerg Melee Attacks"),
+    (11, "Zerg Missile Attacks"),
+    (12, "Zerg Flyer Attacks"),
+    (13, "Protoss Ground Weapons"),
+    (14, "Protoss Air Weapons"),
+    (15, "Protoss Plasma Shields"),
+    (16, "U-238 Shells"),
+    (17, "Ion Thrusters"),
+    (19, "Titan Reactor"),
+    (20, "Ocular Implants"),
+    (21, "Moebius Reactor"),
+    (22, "Apollo Reactor"),
+    (23, "Colossus Reactor"),
+    (24, "Ventral Sacs"),
+    (25, "Antennae"),
+    (26, "Pneumatized Carapace"),
+    (27, "Metabolic Boost"),
+    (28, "Adrenal Glands"),
+    (29, "Muscular Augments"),
+    (30, "Grooved Spines"),
+    (31, "Gamete Meiosis"),
+    (32, "Metasynaptic Node"),
+    (33, "Singularity Charge"),
+    (34, "Leg Enhancements"),
+    (35, "Scarab Damage"),
+    (36, "Reaver Capacity"),
+    (37, "Gravitic Drive"),
+    (38, "Sensor Array"),
+    (39, "Gravitic Boosters"),
+    (40, "Khaydarin Amulet"),
+    (41, "Apial Sensors"),
+    (42, "Gravitic Thrusters"),
+    (43, "Carrier Capacity"),
+    (44, "Khaydarin Core"),
+    (47, "Argus Jewel"),
+    (49, "Argus Talisman"),
+    (51, "Caduceus Reactor"),
+    (52, "Chitinous Plating"),
+    (53, "Anabolic Synthesis"),
+    (54, "Charon Boosters"),
+];
+
+const TECHS: &[(u8, &str)] = &[
+    (0, "Stim Packs"),
+    (1, "Lockdown"),
+    (2, "EMP Shockwave"),
+    (3, "Spider Mines"),
+    (5, "Tank Siege Mode"),
+    (7, "Irradiate"),
+    (8, "Yamato Gun"),
+    (9, "Cloaking Field"),
+    (10, "Personnel Cloaking"),
+    (11, "Burrowing"),
+    (13, "Spawn Broodlings"),
+    (15, "Plague"),
+    (16, "Consume"),
+    (17, "Ensnare"),
+    (19, "Psionic Storm"),
+    (20, "Hallucination"),
+    (21, "Recall"),
+    (22, "Stasis Field"),
+    (24, "Restoration"),
+    (25, "Disruption Web"),
+    (27, "Mind Control"),
+    (30, "Optical Flare"),
+    (31, "Maelstrom"),
+    (32, "Lurker Aspect"),
+];
+
+fn lookup<K: PartialEq, V: Copy>(table: &[(K, V)], id: K) -> Option<V> {
+    table.iter().find(|(k, _)| *k == id).map(|(_, v)| *v)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Whether a command counts towards APM. Chat, syncs, leaving and lobby
+/// traffic don't.
+fn is_action(command_type: u8) -> bool {
+    matches!(
+        command_type,
+        0x09..=0x0c | 0x13..=0x15 | 0x18..=0x36 | 0x58 | 0x5a | 0x60..=0x65
+    )
+}
+
+impl ParsedReplay {
+    /// What the player with `player_id` built, trained and researched, in
+    /// order.
+    pub fn build_order(&self, player_id: u8) -> Vec<BuildStep> {
+        let race = self
+            .players()
+            .iter()
+            .find(|p| p.id == player_id)
+            .map_or(Race::Unknown, |p| p.race);
+        let mut supply = STARTING_SUPPLY;
+        let mut steps = Vec::new();
+
+        for frame in &self.frames {
+            for command in frame.commands.iter().filter(|c| c.player_id == player_id) {
+                let data = &command.data;
+                let (kind, name, added): (BuildItemKind, String, i32) = match command.command_type {
+                    // Build: order, x, y, unit type.
+                    0x0c => {
+                        let Some(unit) = u16_at(data, 5) else {
+                            continue;
+                        };
+                        let Some(name) = lookup(BUILDINGS, unit) else {
+                            continue;
+                        };
+                        // A drone becomes the building.
+                        let consumed = if race == Race::Zerg { -1 } else { 0 };
+                        (BuildItemKind::Building, name.to_string(), consumed)
+                    }
+                    // Train, and larva, hydralisk and mutalisk morphs.
+                    0x1f | 0x23 => {
+                        let Some(unit) = u16_at(data, 0) else {
+                            continue;
+                        };
+                        let Some((name, cost)) = UNITS
+                            .iter()
+                            .find(|(id, _, _)| *id == unit)
+                            .map(|(_, name, cost)| (*name, *cost))
+                        else {
+                            continue;
+                        };
+                        (BuildItemKind::Unit, name.to_string(), cost as i32)
+                    }
+                    // Lair, hive, greater spire and colony morphs.
+                    0x35 => {
+                        let Some(name) = u16_at(data, 0).and_then(|u| lookup(BUILDINGS, u)) else {
+                            continue;
+                        };
+                        (BuildItemKind::Building, name.to_string(), 0)
+                    }
+                    0x30 => {
+                        let Some(name) = data.first().and_then(|&t| lookup(TECHS, t)) else {
+                            continue;
+                        };
+                        (BuildItemKind::Tech, name.to_string(), 0)
+                    }
+                    0x32 => {
+                        let Some(name) = data.first().and_then(|&u| lookup(UPGRADES, u)) else {
+                            continue;
+                        };
+                        (BuildItemKind::Upgrade, name.to_string(), 0)
+                    }
+                    _ => continue,
+                };
+
+                steps.push(BuildStep {
+                    frame: frame.frame_number,
+                    time_ms: frame.frame_number * MS_PER_FRAME,
+                    supply,
+                    kind,
+                    name,
+                });
+                supply = supply.saturating_add_signed(added);
+            }
+        }
+        steps
+    }
+
+    /// Actions per minute of the player with `player_id`, up to when they
+    /// left the game.
+    pub fn apm(&self, player_id: u8) -> u32 {
+        let mut actions = 0u32;
+        let mut last_frame = self.game_info.frames;
+        for frame in &self.frames {
+            for command in frame.commands.iter().filter(|c| c.player_id == player_id) {
+                if command.command_type == 0x57 {
+                    last_frame = frame.frame_number;
+                } else if is_action(command.command_type) {
+                    actions += 1;
+                }
+            }
+        }
+        let minutes = (last_frame * MS_PER_FRAME) as f64 / 60_000.0;
+        if minutes > 0.0 {
+            (actions as f64 / minutes).round() as u32
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_parser::{builder::ReplayBuilder, ReplayParser};
+
+    fn build(unit: u16) -> Vec<u8> {
+        let mut data = vec![0x1e, 0, 0, 0, 0];
+        data.extend_from_slice(&unit.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn extracts_build_orders_and_apm() {
+        let mut replay = ReplayBuilder::new("Polypoid")
+            .frames(1_429 * 2)
+            .player("Flash", Race::Terran)
+            .player("Jaedong", Race::Zerg);
+        for i in 0..5 {
+            replay = replay.command(100 + i * 200, 1, 0x23, &41u16.to_le_bytes());
+        }
+        let bytes = replay
+            .command(1_200, 1, 0x0c, &build(142))
+            .command(1_300, 1, 0x23, &41u16.to_le_bytes())
+            .command(1_400, 1, 0x0c, &build(149))
+            .command(1_500, 1, 0x35, &132u16.to_le_bytes())
+            .command(1_600, 1, 0x32, &[27])
+            .command(200, 0, 0x1f, &7u16.to_le_bytes())
+            // Selections and hotkeys count towards APM but aren't builds.
+            .command(300, 0, 0x09, &[2, 1, 0, 2, 0])
+            .command(310, 0, 0x13, &[0, 1])
+            .command(320, 0, 0x13, &[1, 1])
+            .chat(400, 0, "gl hf")
+            .build();
+        let parsed = ReplayParser::new(&bytes).parse().unwrap();
+
+        let build = parsed.build_order(1);
+        let steps: Vec<(u32, &str)> = build.iter().map(|s| (s.supply, s.name.as_str())).collect();
+        assert_eq!(
+            steps,
+            vec![
+                (4, "Drone"),
+                (5, "Drone"),
+                (6, "Drone"),
+                (7, "Drone"),
+                (8, "Drone"),
+                (9, "Spawning Pool"),
+                (8, "Drone"),
+                (9, "Extractor"),
+                (8, "Lair"),
+                (8, "Metabolic Boost"),
+            ]
+        );
+        assert_eq!(build[5].time_ms, 1_200 * MS_PER_FRAME);
+        assert_eq!(build[5].kind, BuildItemKind::Building);
+
+        // Four actions in two minutes; chat doesn't count.
+        assert_eq!(parsed.build_order(0).len(), 1);
+        assert_eq!(parsed.apm(0), 2);
+        assert_eq!(parsed.apm(1), 5);
+    }
+}
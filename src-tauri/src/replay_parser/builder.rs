@@ -0,0 +1,262 @@
+//! Writes small, uncompressed replays in the layout `ReplayParser` reads.
+//! Used by the mock web API and by tests that need replay bytes without
+//! shipping real `.rep` files.
+
+use crate::replay_parser::game_info::Race;
+
+/// Player slots in the game info section.
+const MAX_PLAYERS: usize = 12;
+
+#[derive(Debug, Clone)]
+struct BuilderPlayer {
+    name: String,
+    race: Race,
+}
+
+#[derive(Debug, Clone)]
+struct BuilderCommand {
+    frame: u32,
+    player_id: u8,
+    command_type: u8,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayBuilder {
+    map_name: String,
+    frames: u32,
+    start_time: u32,
+    players: Vec<BuilderPlayer>,
+    commands: Vec<BuilderCommand>,
+}
+
+impl ReplayBuilder {
+    pub fn new(map_name: &str) -> Self {
+        Self {
+            map_name: map_name.to_string(),
+            frames: 0,
+            start_time: 0,
+            players: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Game length in frames (42 ms each).
+    pub fn frames(mut self, frames: u32) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// Seconds since the Unix epoch.
+    pub fn start_time(mut self, start_time: u32) -> Self {
+        self.start_time = start_time;
+        self
+    }
+
+    /// Adds a player in the next slot; its slot and player id are both the
+    /// number of players added before it. Everyone shares a team, as in melee.
+    pub fn player(mut self, name: &str, race: Race) -> Self {
+        assert!(self.players.len() < MAX_PLAYERS, "too many players");
+        self.players.push(BuilderPlayer {
+            name: name.to_string(),
+            race,
+        });
+        self
+    }
+
+    /// Adds a raw command; `data` must be the length the parser expects for
+    /// `command_type`.
+    pub fn command(mut self, frame: u32, player_id: u8, command_type: u8, data: &[u8]) -> Self {
+        self.commands.push(BuilderCommand {
+            frame,
+            player_id,
+            command_type,
+            data: data.to_vec(),
+        });
+        self
+    }
+
+    /// A chat message from the player in `sender_slot`.
+    pub fn chat(self, frame: u32, sender_slot: u8, message: &str) -> Self {
+        let mut data = vec![sender_slot];
+        let mut text = message.as_bytes().to_vec();
+        text.resize(80, 0);
+        data.extend_from_slice(&text);
+        self.command(frame, sender_slot, 0x5c, &data)
+    }
+
+    /// The player leaving the game, which decides the winner.
+    pub fn leave(self, frame: u32, player_id: u8) -> Self {
+        self.command(frame, player_id, 0x57, &[1])
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        // Replay id; the parser reads a size field after it.
+        section(&mut out, &[b"seRS"]);
+        out.extend_from_slice(&0u32.to_le_bytes());
+
+        section(&mut out, &[&self.game_info()]);
+
+        let commands = self.commands_data();
+        section(&mut out, &[&(commands.len() as u32).to_le_bytes()]);
+        section(&mut out, &[&commands]);
+
+        let chk = self.chk();
+        section(&mut out, &[&(chk.len() as u32).to_le_bytes()]);
+        section(&mut out, &[&chk]);
+
+        out
+    }
+
+    fn game_info(&self) -> Vec<u8> {
+        let mut info = Vec::new();
+        info.push(1); // engine: Brood War
+        info.extend_from_slice(&self.frames.to_le_bytes());
+        info.extend_from_slice(&[0; 3]);
+        info.extend_from_slice(&self.start_time.to_le_bytes());
+        info.extend_from_slice(&[0; 12]);
+        info.extend_from_slice(&fixed(&self.map_name, 28)); // game title
+        info.extend_from_slice(&128u16.to_le_bytes()); // map width
+        info.extend_from_slice(&128u16.to_le_bytes()); // map height
+        info.push(0);
+        info.push(self.players.len() as u8); // available slots
+        info.push(6); // speed: fastest
+        info.push(0);
+        info.extend_from_slice(&2u16.to_le_bytes()); // game type: melee
+        info.extend_from_slice(&0u16.to_le_bytes());
+        info.extend_from_slice(&[0; 8]);
+        let host = self.players.first().map(|p| p.name.as_str()).unwrap_or("");
+        info.extend_from_slice(&fixed(host, 24));
+        info.push(0);
+        info.extend_from_slice(&fixed(&self.map_name, 26));
+        info.extend_from_slice(&[0; 38]);
+
+        for slot in 0..MAX_PLAYERS {
+            let player = self.players.get(slot);
+            info.extend_from_slice(&(slot as u16).to_le_bytes());
+            info.extend_from_slice(&[0; 2]);
+            info.push(if player.is_some() { slot as u8 } else { 0xff });
+            info.extend_from_slice(&[0; 3]);
+            info.push(if player.is_some() { 2 } else { 0 }); // type: human
+            info.push(player.map_or(6, |p| race_value(p.race)));
+            info.push(0); // team
+            info.extend_from_slice(&fixed(player.map_or("", |p| &p.name), 25));
+        }
+        for color in 0..8u32 {
+            info.extend_from_slice(&color.to_le_bytes());
+        }
+        info
+    }
+
+    fn commands_data(&self) -> Vec<u8> {
+        let mut commands = self.commands.clone();
+        commands.sort_by_key(|c| c.frame);
+
+        let mut data = Vec::new();
+        for frame in commands.chunk_by(|a, b| a.frame == b.frame) {
+            let mut block = Vec::new();
+            for command in frame {
+                block.push(command.player_id);
+                block.push(command.command_type);
+                block.extend_from_slice(&command.data);
+            }
+            assert!(
+                block.len() <= u8::MAX as usize,
+                "too many commands in one frame"
+            );
+            data.extend_from_slice(&frame[0].frame.to_le_bytes());
+            data.push(block.len() as u8);
+            data.extend_from_slice(&block);
+        }
+        data
+    }
+
+    /// Minimal scenario.chk: a Brood War version and the map name as the
+    /// scenario title.
+    fn chk(&self) -> Vec<u8> {
+        let mut strings = Vec::new();
+        strings.extend_from_slice(&1u16.to_le_bytes()); // string count
+        strings.extend_from_slice(&4u16.to_le_bytes()); // offset of string 1
+        strings.extend_from_slice(self.map_name.as_bytes());
+        strings.push(0);
+
+        let mut chk = Vec::new();
+        chk_section(&mut chk, b"VER ", &205u16.to_le_bytes());
+        chk_section(&mut chk, b"SPRP", &[1, 0, 0, 0]);
+        chk_section(&mut chk, b"STR ", &strings);
+        chk
+    }
+}
+
+fn race_value(race: Race) -> u8 {
+    match race {
+        Race::Zerg => 0,
+        Race::Terran => 1,
+        Race::Protoss => 2,
+        Race::Unknown => 6,
+    }
+}
+
+/// Null-padded string field of `len` bytes, truncated if needed.
+fn fixed(s: &str, len: usize) -> Vec<u8> {
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.truncate(len - 1);
+    bytes.resize(len, 0);
+    bytes
+}
+
+/// A section of length-prefixed chunks with a zero checksum.
+fn section(out: &mut Vec<u8>, chunks: &[&[u8]]) {
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    for chunk in chunks {
+        out.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+}
+
+fn chk_section(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(name);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_parser::{map, ReplayParser};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn builds_replays_the_parser_reads_back() {
+        let bytes = ReplayBuilder::new("Polypoid 1.65")
+            .frames(24 * 60 * 10)
+            .start_time(1_735_689_600)
+            .player("Flash", Race::Terran)
+            .player("Jaedong", Race::Zerg)
+            .chat(100, 1, "gl hf")
+            .leave(14_000, 1)
+            .build();
+
+        let parsed = ReplayParser::new(&bytes).parse().unwrap();
+        assert_eq!(parsed.game_info.map_name, "Polypoid 1.65");
+        assert_eq!(
+            parsed.game_info.start_time,
+            UNIX_EPOCH + Duration::from_secs(1_735_689_600)
+        );
+        assert_eq!(parsed.matchup(), "TvZ");
+        assert_eq!(parsed.winners()[0].name, "Flash");
+
+        let chat = parsed.chat_messages();
+        assert_eq!(chat[0].sender_name, "Jaedong");
+        assert_eq!(chat[0].message, "gl hf");
+        assert_eq!(chat[0].frame_number, 100);
+
+        let chk = ReplayParser::new(&bytes).map_data().unwrap();
+        let info = map::parse_chk(&chk).unwrap();
+        assert_eq!(info.title.as_deref(), Some("Polypoid 1.65"));
+        assert_eq!(info.extension(), "scx");
+    }
+}
@@ -13,6 +13,7 @@ pub struct Frame {
 
 #[derive(Debug)]
 pub struct Command {
+    pub player_id: u8,
     pub command_type: u8,
     pub data: Vec<u8>,
 }
@@ -98,41 +99,52 @@ fn parse_commands(data: &[u8]) -> Result<Vec<Command>, ParseError> {
 }
 
 fn parse_command(input: &[u8]) -> Result<(&[u8], Command), ParseError> {
-    let (input, _player_id) = le_u8(input)?;
+    let (input, player_id) = le_u8(input)?;
     let (input, command_type) = le_u8(input)?;
 
-    let (input, data) = match command_type {
-        // Commands with no additional data
-        0x18 | 0x19 | 0x10 | 0x11 | 0x08 | 0x5a | 0x5b | 0x1b | 0x1c | 0x1d | 0x27 | 0x2a
-        | 0x2e | 0x31 | 0x33 | 0x34 | 0x36 | 0x38 | 0x39 | 0x3c | 0x54 => (input, Vec::new()),
-        // Commands with 1 byte
-        0x0f | 0x20 | 0x21 | 0x22 | 0x30 | 0x32 | 0x55 | 0x57 => {
-            let (input, byte) = le_u8(input)?;
-            (input, vec![byte])
-        }
-        // Commands with 2 bytes
-        0x29 | 0x62 => {
-            let (input, data) = take(2usize)(input)?;
-            (input, data.to_vec())
-        }
-        0x5c => {
-            let (input, sender) = le_u8(input)?;
-            let (input, message_data) = take(80usize)(input)?;
-            let mut data = vec![sender];
-            data.extend_from_slice(message_data);
-            (input, data)
-        }
-        _ => {
-            if input.is_empty() {
-                (input, Vec::new())
-            } else {
-                let (input, byte) = le_u8(input)?;
-                (input, vec![byte])
-            }
-        }
-    };
+    // An unknown command can't be skipped, so it takes the rest of the block.
+    let length = command_length(command_type, input).unwrap_or(input.len());
+    let (input, data) = take(length)(input)?;
+
+    Ok((
+        input,
+        Command {
+            player_id,
+            command_type,
+            data: data.to_vec(),
+        },
+    ))
+}
 
-    Ok((input, Command { command_type, data }))
+/// Length of the data following a command's type byte, or `None` for an
+/// unknown type. `input` is that data, for the variable-length commands:
+/// selections are a unit count followed by that many unit tags, and saves
+/// carry a file name.
+fn command_length(command_type: u8, input: &[u8]) -> Option<usize> {
+    let length = match command_type {
+        0x05 | 0x08 | 0x10 | 0x11 | 0x18 | 0x19 | 0x1b | 0x1c | 0x1d | 0x27 | 0x2a | 0x2e
+        | 0x31 | 0x33 | 0x34 | 0x36 | 0x38 | 0x39 | 0x3c | 0x54 | 0x5a | 0x5b => 0,
+        0x0f | 0x1a | 0x1e | 0x21 | 0x22 | 0x25 | 0x26 | 0x28 | 0x2b | 0x2c | 0x2d | 0x30
+        | 0x32 | 0x3a | 0x3b | 0x3d | 0x42 | 0x43 | 0x55 | 0x57 => 1,
+        0x0d | 0x13 | 0x1f | 0x20 | 0x23 | 0x29 | 0x35 | 0x41 | 0x44 | 0x45 => 2,
+        0x0e | 0x12 | 0x2f | 0x58 | 0x62 => 4,
+        0x3e => 5,
+        0x37 => 6,
+        0x0c | 0x3f => 7,
+        0x14 | 0x56 => 9,
+        0x15 => 10,
+        0x60 => 11,
+        0x48 | 0x61 => 12,
+        0x40 => 17,
+        0x5c => 81,
+        // Select, shift select and shift deselect, before and after 1.21.
+        0x09..=0x0b => 1 + *input.first()? as usize * 2,
+        0x63..=0x65 => 1 + *input.first()? as usize * 4,
+        // Save and load game.
+        0x06 | 0x07 => 4 + input.get(4..)?.iter().position(|&b| b == 0)? + 1,
+        _ => return None,
+    };
+    Some(length)
 }
 
 fn decompress_zlib_chunk(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
@@ -144,3 +156,89 @@ fn decompress_zlib_chunk(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Err
     decoder.read_to_end(&mut decompressed)?;
     Ok(decompressed)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(player_id, command_type, data)` of each command in `block`.
+    fn commands(block: &[u8]) -> Vec<(u8, u8, Vec<u8>)> {
+        parse_commands(block)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.player_id, c.command_type, c.data))
+            .collect()
+    }
+
+    #[test]
+    fn sizes_selections_by_their_unit_count() {
+        let block = [
+            0, 0x09, 2, 1, 0, 2, 0, // select two units
+            0, 0x0a, 1, 3, 0, // shift select one
+            1, 0x0b, 0, // shift deselect none
+            1, 0x63, 1, 4, 0, 0, 0, // 1.21 select, four bytes per unit
+            1, 0x64, 2, 5, 0, 0, 0, 6, 0, 0, 0, // 1.21 shift select
+            1, 0x65, 0, // 1.21 shift deselect
+            0, 0x1a, 7, // followed by a fixed-length command
+        ];
+        assert_eq!(
+            commands(&block),
+            vec![
+                (0, 0x09, vec![2, 1, 0, 2, 0]),
+                (0, 0x0a, vec![1, 3, 0]),
+                (1, 0x0b, vec![0]),
+                (1, 0x63, vec![1, 4, 0, 0, 0]),
+                (1, 0x64, vec![2, 5, 0, 0, 0, 6, 0, 0, 0]),
+                (1, 0x65, vec![0]),
+                (0, 0x1a, vec![7]),
+            ]
+        );
+    }
+
+    #[test]
+    fn sizes_saves_by_their_file_name() {
+        let mut block = vec![0, 0x06, 1, 2, 3, 4];
+        block.extend_from_slice(b"game\0");
+        block.extend_from_slice(&[0, 0x07, 0, 0, 0, 0, 0]);
+        block.extend_from_slice(&[0, 0x08]);
+        let mut save = vec![1, 2, 3, 4];
+        save.extend_from_slice(b"game\0");
+        assert_eq!(
+            commands(&block),
+            vec![
+                (0, 0x06, save),
+                (0, 0x07, vec![0, 0, 0, 0, 0]),
+                (0, 0x08, Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_chat_as_a_sender_and_80_bytes() {
+        let mut block = vec![0, 0x5c, 1];
+        block.extend_from_slice(&[b'x'; 80]);
+        block.extend_from_slice(&[1, 0x08]);
+        let parsed = commands(&block);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!((parsed[0].1, parsed[0].2.len()), (0x5c, 81));
+        assert_eq!(parsed[1], (1, 0x08, Vec::new()));
+    }
+
+    #[test]
+    fn unknown_and_truncated_commands_end_the_block() {
+        // Nothing after an unknown command can be located.
+        let block = [0, 0x08, 0, 0xee, 1, 2, 0, 0x08];
+        assert_eq!(
+            commands(&block),
+            vec![(0, 0x08, Vec::new()), (0, 0xee, vec![1, 2, 0, 0x08])]
+        );
+
+        // A selection claiming more units than the block holds is dropped.
+        let block = [0, 0x08, 0, 0x09, 3, 1, 0];
+        assert_eq!(commands(&block), vec![(0, 0x08, Vec::new())]);
+        // A save whose name is never terminated runs to the end, like an
+        // unknown command.
+        let block = [0, 0x06, 0, 0, 0, 0, b'a'];
+        assert_eq!(commands(&block), vec![(0, 0x06, vec![0, 0, 0, 0, b'a'])]);
+    }
+}
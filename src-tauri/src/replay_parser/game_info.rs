@@ -11,16 +11,30 @@ use std::time::{SystemTime, UNIX_EPOCH};
 pub struct GameInfo {
     pub frames: u32,
     pub start_time: SystemTime,
+    pub title: String,
+    pub map_name: String,
     pub player_structs: Vec<PlayerStruct>,
 }
 
 #[derive(Debug)]
 pub struct PlayerStruct {
     pub slot_id: u16,
+    /// Player id referenced by commands in the frames section.
+    pub id: u8,
+    pub player_type: u8,
+    pub race: Race,
+    pub team: u8,
     pub name: String,
 }
 
-#[derive(Debug)]
+impl PlayerStruct {
+    /// Whether the slot is occupied by a human or computer player.
+    pub fn is_active(&self) -> bool {
+        matches!(self.player_type, 1 | 2) && !self.name.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Race {
     Zerg,
     Terran,
@@ -39,6 +53,17 @@ impl From<u8> for Race {
     }
 }
 
+impl Race {
+    pub fn initial(&self) -> char {
+        match self {
+            Race::Zerg => 'Z',
+            Race::Terran => 'T',
+            Race::Protoss => 'P',
+            Race::Unknown => 'U',
+        }
+    }
+}
+
 pub fn parse_game_info_section(input: &[u8]) -> Result<(&[u8], GameInfo), ParseError> {
     let (input, _crc) = le_u32(input)?;
     let (input, num_chunks) = le_u32(input)?;
@@ -70,7 +95,7 @@ fn parse_game_info_data(input: &[u8]) -> Result<(&[u8], GameInfo), ParseError> {
     let (input, _) = take(12usize)(input)?;
 
     let (input, title_bytes) = take(28usize)(input)?;
-    let _title = parse_null_terminated_string(title_bytes);
+    let title = parse_null_terminated_string(title_bytes);
 
     let (input, _map_width) = le_u16(input)?;
     let (input, _map_height) = le_u16(input)?;
@@ -88,7 +113,7 @@ fn parse_game_info_data(input: &[u8]) -> Result<(&[u8], GameInfo), ParseError> {
     let (input, _) = take(1usize)(input)?;
 
     let (input, map_bytes) = take(26usize)(input)?;
-    let _map = parse_null_terminated_string(map_bytes);
+    let map_name = parse_null_terminated_string(map_bytes);
 
     let (input, _) = take(38usize)(input)?;
 
@@ -112,6 +137,8 @@ fn parse_game_info_data(input: &[u8]) -> Result<(&[u8], GameInfo), ParseError> {
         GameInfo {
             frames,
             start_time,
+            title,
+            map_name,
             player_structs,
         },
     ))
@@ -120,17 +147,27 @@ fn parse_game_info_data(input: &[u8]) -> Result<(&[u8], GameInfo), ParseError> {
 fn parse_player_struct(input: &[u8]) -> IResult<&[u8], PlayerStruct> {
     let (input, slot_id) = le_u16(input)?;
     let (input, _) = take(2usize)(input)?;
-    let (input, _id) = le_u8(input)?;
+    let (input, id) = le_u8(input)?;
     let (input, _) = take(3usize)(input)?;
-    let (input, _player_type) = le_u8(input)?;
+    let (input, player_type) = le_u8(input)?;
     let (input, race_value) = le_u8(input)?;
-    let (input, _team) = le_u8(input)?;
+    let (input, team) = le_u8(input)?;
     let (input, name_bytes) = take(25usize)(input)?;
 
-    let _race = Race::from(race_value);
+    let race = Race::from(race_value);
     let name = parse_null_terminated_string(name_bytes);
 
-    Ok((input, PlayerStruct { slot_id, name }))
+    Ok((
+        input,
+        PlayerStruct {
+            slot_id,
+            id,
+            player_type,
+            race,
+            team,
+            name,
+        },
+    ))
 }
 
 fn parse_player_color(input: &[u8]) -> IResult<&[u8], u32> {
@@ -0,0 +1,209 @@
+use crate::replay_parser::ParseError;
+
+use nom::{
+    bytes::complete::take,
+    number::complete::{le_u16, le_u32},
+};
+
+/// Details read from an embedded scenario.chk.
+#[derive(Debug)]
+pub struct MapInfo {
+    /// `VER ` section value: 59/63 for original StarCraft, 205+ for Brood War.
+    pub version: u16,
+    pub title: Option<String>,
+}
+
+impl MapInfo {
+    /// File extension StarCraft expects for this map version.
+    pub fn extension(&self) -> &'static str {
+        if self.version < 205 {
+            "scm"
+        } else {
+            "scx"
+        }
+    }
+}
+
+/// Reads a section made of length-prefixed chunks and concatenates them,
+/// inflating the ones that are zlib-compressed.
+pub fn parse_chunked_section(input: &[u8]) -> Result<(&[u8], Vec<u8>), ParseError> {
+    let (input, _crc) = le_u32(input)?;
+    let (input, num_chunks) = le_u32(input)?;
+
+    let mut data = Vec::new();
+    let mut input = input;
+    for _ in 0..num_chunks {
+        let (new_input, chunk_size) = le_u32(input)?;
+        let (new_input, chunk) = take(chunk_size)(new_input)?;
+        match decompress_chunk(chunk) {
+            Ok(decompressed) => data.extend_from_slice(&decompressed),
+            Err(_) => data.extend_from_slice(chunk),
+        }
+        input = new_input;
+    }
+
+    Ok((input, data))
+}
+
+/// Walks the CHK sections for the format version and scenario name.
+pub fn parse_chk(chk: &[u8]) -> Result<MapInfo, ParseError> {
+    let mut version = None;
+    let mut scenario_name_index = None;
+    let mut strings: Option<(&[u8], bool)> = None;
+
+    let mut input = chk;
+    while input.len() >= 8 {
+        let (rest, name) = take(4usize)(input)?;
+        let (rest, size) = le_u32(rest)?;
+        // Protected maps use bogus section sizes; stop rather than fail.
+        let Ok((rest, data)) = take::<_, _, nom::error::Error<&[u8]>>(size)(rest) else {
+            break;
+        };
+
+        match name {
+            b"VER " => version = Some(le_u16(data)?.1),
+            b"SPRP" => scenario_name_index = Some(le_u16(data)?.1),
+            b"STR " if strings.is_none() => strings = Some((data, false)),
+            b"STRx" => strings = Some((data, true)),
+            _ => {}
+        }
+        input = rest;
+    }
+
+    let version =
+        version.ok_or_else(|| ParseError::InvalidData("Map has no VER section".to_string()))?;
+    let title = match (scenario_name_index, strings) {
+        (Some(index), Some((data, extended))) if index > 0 => {
+            lookup_string(data, index as usize, extended)
+        }
+        _ => None,
+    };
+
+    Ok(MapInfo { version, title })
+}
+
+fn lookup_string(data: &[u8], index: usize, extended: bool) -> Option<String> {
+    let width = if extended { 4 } else { 2 };
+    let offset_at = width * index;
+    let offset_bytes = data.get(offset_at..offset_at + width)?;
+    let offset = if extended {
+        u32::from_le_bytes(offset_bytes.try_into().ok()?) as usize
+    } else {
+        u16::from_le_bytes(offset_bytes.try_into().ok()?) as usize
+    };
+
+    let bytes = data.get(offset..)?;
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+}
+
+fn decompress_chunk(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    let mut decoder = ZlibDecoder::new(data);
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(name);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+
+    /// A string table holding `strings`, with 16-bit offsets or, when
+    /// `extended`, 32-bit ones.
+    fn strings(strings: &[&str], extended: bool) -> Vec<u8> {
+        let width = if extended { 4 } else { 2 };
+        let mut offsets = Vec::new();
+        let mut text = Vec::new();
+        let mut offset = width * (strings.len() + 1);
+        for s in strings {
+            if extended {
+                offsets.extend_from_slice(&(offset as u32).to_le_bytes());
+            } else {
+                offsets.extend_from_slice(&(offset as u16).to_le_bytes());
+            }
+            text.extend_from_slice(s.as_bytes());
+            text.push(0);
+            offset += s.len() + 1;
+        }
+        let mut data = vec![0; width];
+        data[0] = strings.len() as u8;
+        data.extend(offsets);
+        data.extend(text);
+        data
+    }
+
+    #[test]
+    fn reads_the_version_and_scenario_name() {
+        let mut chk = Vec::new();
+        section(&mut chk, b"VER ", &59u16.to_le_bytes());
+        section(&mut chk, b"SPRP", &[2, 0, 1, 0]);
+        section(
+            &mut chk,
+            b"STR ",
+            &strings(&["Untitled", "Lost Temple"], false),
+        );
+        let info = parse_chk(&chk).unwrap();
+        assert_eq!(info.version, 59);
+        assert_eq!(info.title.as_deref(), Some("Lost Temple"));
+        assert_eq!(info.extension(), "scm");
+
+        // Extended strings win over the legacy table, whatever the order.
+        section(&mut chk, b"STRx", &strings(&["Fighting Spirit"], true));
+        section(&mut chk, b"VER ", &206u16.to_le_bytes());
+        chk[18..20].copy_from_slice(&1u16.to_le_bytes());
+        let info = parse_chk(&chk).unwrap();
+        assert_eq!(info.title.as_deref(), Some("Fighting Spirit"));
+        assert_eq!(info.extension(), "scx");
+    }
+
+    #[test]
+    fn tolerates_protected_and_untitled_maps() {
+        let mut chk = Vec::new();
+        section(&mut chk, b"VER ", &205u16.to_le_bytes());
+        section(&mut chk, b"SPRP", &[0, 0, 0, 0]);
+        section(&mut chk, b"STR ", &strings(&["Unused"], false));
+        assert_eq!(parse_chk(&chk).unwrap().title, None);
+
+        // A section claiming more bytes than remain ends the walk.
+        chk.extend_from_slice(b"MTXM");
+        chk.extend_from_slice(&u32::MAX.to_le_bytes());
+        chk.extend_from_slice(&[0; 16]);
+        assert_eq!(parse_chk(&chk).unwrap().version, 205);
+
+        assert!(parse_chk(&chk[8 + 2..]).is_err());
+        assert!(parse_chk(&[]).is_err());
+    }
+
+    #[test]
+    fn joins_plain_and_compressed_chunks() {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[7; 100]).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut input = vec![0; 4];
+        input.extend_from_slice(&2u32.to_le_bytes());
+        for chunk in [&b"plain"[..], &compressed] {
+            input.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            input.extend_from_slice(chunk);
+        }
+        input.extend_from_slice(b"rest");
+
+        let (rest, data) = parse_chunked_section(&input).unwrap();
+        assert_eq!(rest, b"rest");
+        assert_eq!(&data[..5], b"plain");
+        assert_eq!(&data[5..], &[7; 100]);
+        assert!(parse_chunked_section(&input[..input.len() - 10]).is_err());
+    }
+}
@@ -1,9 +1,13 @@
 use nom::{bytes::complete::take, number::complete::le_u32, IResult};
 
+pub mod build_order;
+#[cfg(any(test, feature = "mock"))]
+pub mod builder;
 pub mod error;
 pub mod frames;
 pub mod game_info;
 pub mod header;
+pub mod map;
 
 pub use error::ParseError;
 pub use frames::{Command, Frame};
@@ -28,14 +32,51 @@ impl<'a> ReplayParser<'a> {
         Self { input }
     }
 
-    pub fn parse(&self) -> Result<ParsedReplay, ParseError> {
+    /// Checks that every section decodes, so a replay cut short anywhere,
+    /// even one whose game info is intact, is rejected.
+    pub fn validate(&self) -> Result<(), ParseError> {
+        self.parse()?;
+        self.map_data().map(|_| ())
+    }
+
+    /// Parses only the game info section.
+    pub fn game_info(&self) -> Result<GameInfo, ParseError> {
+        self.parse_game_info().map(|(_, game_info)| game_info)
+    }
+
+    fn parse_game_info(&self) -> Result<(&'a [u8], GameInfo), ParseError> {
         let (remaining, header) = header::parse_header(self.input)?;
 
         if header.replay_version != "seRS" {
             return Err(ParseError::UnsupportedVersion(header.replay_version));
         }
 
-        let (remaining, game_info) = game_info::parse_game_info_section(remaining)?;
+        game_info::parse_game_info_section(remaining)
+    }
+
+    /// Returns the embedded scenario.chk, skipping over the command stream
+    /// without decoding it.
+    pub fn map_data(&self) -> Result<Vec<u8>, ParseError> {
+        let (remaining, _) = self.parse_game_info()?;
+
+        let mut remaining = remaining;
+        for section in ["commands length", "commands", "map data length"] {
+            remaining = skip_section(remaining)
+                .map_err(|e| ParseError::InvalidData(format!("Failed to skip {section}: {e:?}")))?
+                .0;
+        }
+
+        let (_, chk) = map::parse_chunked_section(remaining)?;
+        if chk.is_empty() {
+            return Err(ParseError::InvalidData(
+                "Replay has no map data".to_string(),
+            ));
+        }
+        Ok(chk)
+    }
+
+    pub fn parse(&self) -> Result<ParsedReplay, ParseError> {
+        let (remaining, game_info) = self.parse_game_info()?;
 
         let (remaining, _) = skip_section(remaining).map_err(|e| {
             ParseError::InvalidData(format!("Failed to skip section size section: {e:?}"))
@@ -47,6 +88,9 @@ impl<'a> ReplayParser<'a> {
     }
 }
 
+/// Length of a frame at the fastest game speed.
+pub const MS_PER_FRAME: u32 = 42;
+
 #[derive(Debug)]
 pub struct ParsedReplay {
     pub game_info: GameInfo,
@@ -55,7 +99,79 @@ pub struct ParsedReplay {
 
 impl ParsedReplay {
     pub fn duration_ms(&self) -> u32 {
-        self.game_info.frames * 42 // 42 ms per frame
+        self.game_info.frames * MS_PER_FRAME
+    }
+
+    /// Players occupying a slot, in slot order.
+    pub fn players(&self) -> Vec<&PlayerStruct> {
+        self.game_info
+            .player_structs
+            .iter()
+            .filter(|p| p.is_active())
+            .collect()
+    }
+
+    /// Players grouped by team. Melee games report every player on the same
+    /// team, in which case each player is their own side.
+    pub fn teams(&self) -> Vec<Vec<&PlayerStruct>> {
+        let players = self.players();
+        let single_team = players.windows(2).all(|w| w[0].team == w[1].team);
+        if single_team {
+            return players.into_iter().map(|p| vec![p]).collect();
+        }
+
+        let mut teams: Vec<Vec<&PlayerStruct>> = Vec::new();
+        for player in players {
+            match teams.iter_mut().find(|t| t[0].team == player.team) {
+                Some(team) => team.push(player),
+                None => teams.push(vec![player]),
+            }
+        }
+        teams
+    }
+
+    /// Race initials per side, e.g. `PvZ` or `PTvZZ`.
+    pub fn matchup(&self) -> String {
+        self.teams()
+            .iter()
+            .map(|team| team.iter().map(|p| p.race.initial()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("v")
+    }
+
+    /// The side left standing once every other side has left the game, or an
+    /// empty list if that can't be determined from the leave commands.
+    pub fn winners(&self) -> Vec<&PlayerStruct> {
+        let teams = self.teams();
+        if teams.len() < 2 {
+            return Vec::new();
+        }
+
+        let mut remaining: Vec<Vec<u8>> = teams
+            .iter()
+            .map(|t| t.iter().map(|p| p.id).collect())
+            .collect();
+        let leaves = self
+            .frames
+            .iter()
+            .flat_map(|f| &f.commands)
+            .filter(|c| c.command_type == 0x57);
+
+        for command in leaves {
+            for ids in remaining.iter_mut() {
+                ids.retain(|&id| id != command.player_id);
+            }
+            let standing: Vec<usize> = (0..remaining.len())
+                .filter(|&i| !remaining[i].is_empty())
+                .collect();
+            match standing.as_slice() {
+                [winner] => return teams[*winner].clone(),
+                [] => break,
+                _ => {}
+            }
+        }
+
+        Vec::new()
     }
 
     pub fn chat_messages(&self) -> Vec<ChatMessage> {
@@ -0,0 +1,601 @@
+//! Filters over the replay library, and the compact query syntax the library
+//! search box accepts:
+//!
+//! ```text
+//! player:Flash mu:TvZ map:"Polypoid" after:2025-01-01 minlen:10m sort:duration
+//! ```
+//!
+//! Words without a key are player names. Later filters of the same key
+//! replace earlier ones, except `player:`, which may be given several times.
+//! Player names also match the other names their account was seen using; see
+//! [`ReplaySearch::add_aliases`].
+
+use std::collections::HashMap;
+
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::{calendar, replay_library::LibraryReplay};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchSort {
+    #[default]
+    Date,
+    Duration,
+    Map,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReplaySearch {
+    /// Players who must all be in the game.
+    pub players: Vec<String>,
+    /// Must be on a different side than the first of `players`, if any.
+    pub opponent: Option<String>,
+    /// Races per side, like `TvZ`; either side may be listed first. With a
+    /// player filter, the first side is that player's.
+    pub matchup: Option<String>,
+    /// Part of the map name.
+    pub map: Option<String>,
+    /// Games starting at or after this time.
+    pub after_ms: Option<u64>,
+    /// Games starting before this time.
+    pub before_ms: Option<u64>,
+    pub min_duration_ms: Option<u32>,
+    pub max_duration_ms: Option<u32>,
+    pub winner: Option<String>,
+    /// Part of any chat message.
+    pub chat: Option<String>,
+    pub sort: SearchSort,
+    /// Defaults to newest/longest first, and A-Z for maps.
+    pub ascending: Option<bool>,
+    /// Other names of the players, opponent and winner, by lowercased name;
+    /// any of them matches in place of the name.
+    #[serde(skip)]
+    pub aliases: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    /// Matches across all pages.
+    pub total: u64,
+    pub replays: Vec<LibraryReplay>,
+}
+
+/// A chat search. `text` uses SQLite FTS5 query syntax: words must all
+/// appear, `"gg wp"` is a phrase, `gg OR wp` either word and `noo*` a prefix.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChatSearch {
+    /// Empty to list every line from `sender`.
+    pub text: String,
+    pub sender: Option<String>,
+}
+
+/// A chat line matching a `ChatSearch`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatHit {
+    /// The `LibraryReplay` id of the game.
+    pub game_id: i64,
+    pub map_name: String,
+    pub start_time_ms: u64,
+    pub frame: u32,
+    /// Time into the game the line was sent.
+    pub game_time_ms: u32,
+    pub sender: String,
+    pub message: String,
+}
+
+impl ReplaySearch {
+    /// Fills in `aliases` for every name in the filters from `names_of`,
+    /// which returns a name along with the others its account used.
+    pub fn add_aliases(
+        &mut self,
+        names_of: impl Fn(&str) -> Result<Vec<String>, String>,
+    ) -> Result<(), String> {
+        let names: Vec<String> = self
+            .players
+            .iter()
+            .chain(&self.opponent)
+            .chain(&self.winner)
+            .cloned()
+            .collect();
+        for name in names {
+            let aliases = names_of(&name)?;
+            if aliases.len() > 1 {
+                self.aliases.insert(name.to_lowercase(), aliases);
+            }
+        }
+        Ok(())
+    }
+
+    /// `name` and its aliases as an `IN` list for `column`, pushing the names
+    /// onto `values`.
+    fn name_in(&self, column: &str, name: &str, values: &mut Vec<Value>) -> String {
+        let names = match self.aliases.get(&name.to_lowercase()) {
+            Some(aliases) => aliases.clone(),
+            None => vec![name.to_string()],
+        };
+        let placeholders = vec!["?"; names.len()].join(", ");
+        values.extend(names.into_iter().map(Value::from));
+        format!("{column} COLLATE NOCASE IN ({placeholders})")
+    }
+
+    /// A `WHERE` clause over `games g`, with its positional parameters.
+    pub fn to_sql(&self) -> (String, Vec<Value>) {
+        let mut conditions = vec!["1".to_string()];
+        let mut values: Vec<Value> = Vec::new();
+        let has_player = "EXISTS (SELECT 1 FROM players p WHERE p.game_id = g.id AND";
+
+        for player in &self.players {
+            let name = self.name_in("p.name", player, &mut values);
+            conditions.push(format!("{has_player} {name})"));
+        }
+        if let Some(opponent) = &self.opponent {
+            match self.players.first() {
+                Some(player) => {
+                    let player = self.name_in("a.name", player, &mut values);
+                    let opponent = self.name_in("b.name", opponent, &mut values);
+                    conditions.push(format!(
+                        "EXISTS (SELECT 1 FROM players a JOIN players b
+                                 ON b.game_id = a.game_id AND b.side <> a.side
+                                 WHERE a.game_id = g.id AND {player} AND {opponent})"
+                    ));
+                }
+                None => {
+                    let name = self.name_in("p.name", opponent, &mut values);
+                    conditions.push(format!("{has_player} {name})"));
+                }
+            }
+        }
+        if let Some(matchup) = &self.matchup {
+            conditions.push("g.matchup_key = ?".to_string());
+            values.push(matchup_key(matchup).into());
+
+            let first_side = matchup.split(['v', 'V']).next().unwrap_or_default();
+            if let (Some(player), 1) = (self.players.first(), first_side.len()) {
+                let name = self.name_in("p.name", player, &mut values);
+                conditions.push(format!("{has_player} {name} AND substr(p.race, 1, 1) = ?)"));
+                values.push(first_side.to_ascii_uppercase().into());
+            }
+        }
+        if let Some(map) = &self.map {
+            conditions.push("g.map_name LIKE ? ESCAPE '\\'".to_string());
+            values.push(contains_pattern(map).into());
+        }
+        if let Some(after) = self.after_ms {
+            conditions.push("g.start_time_ms >= ?".to_string());
+            values.push((after as i64).into());
+        }
+        if let Some(before) = self.before_ms {
+            conditions.push("g.start_time_ms < ?".to_string());
+            values.push((before as i64).into());
+        }
+        if let Some(min) = self.min_duration_ms {
+            conditions.push("g.duration_ms >= ?".to_string());
+            values.push(min.into());
+        }
+        if let Some(max) = self.max_duration_ms {
+            conditions.push("g.duration_ms <= ?".to_string());
+            values.push(max.into());
+        }
+        if let Some(winner) = &self.winner {
+            let name = self.name_in("p.name", winner, &mut values);
+            conditions.push(format!("{has_player} {name} AND p.outcome = 'Win')"));
+        }
+        if let Some(chat) = &self.chat {
+            conditions.push(
+                "EXISTS (SELECT 1 FROM chat c WHERE c.game_id = g.id
+                         AND c.message LIKE ? ESCAPE '\\')"
+                    .to_string(),
+            );
+            values.push(contains_pattern(chat).into());
+        }
+
+        (conditions.join(" AND "), values)
+    }
+
+    /// `ORDER BY` terms over `games g`.
+    pub fn order_by(&self) -> String {
+        let ascending = self.ascending.unwrap_or(self.sort == SearchSort::Map);
+        let direction = if ascending { "ASC" } else { "DESC" };
+        let column = match self.sort {
+            SearchSort::Date => "g.start_time_ms",
+            SearchSort::Duration => "g.duration_ms",
+            SearchSort::Map => "g.map_name COLLATE NOCASE",
+        };
+        format!("{column} {direction}, g.id {direction}")
+    }
+}
+
+/// Parses the query syntax described in the module docs.
+pub fn parse_query(query: &str) -> Result<ReplaySearch, String> {
+    let mut search = ReplaySearch::default();
+    for (key, value) in tokenize(query)? {
+        let Some(key) = key else {
+            search.players.push(value);
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "player" | "p" | "toon" => search.players.push(value),
+            "opponent" | "vs" => search.opponent = Some(value),
+            "mu" | "matchup" => {
+                if !is_matchup(&value) {
+                    return Err(format!("Invalid matchup \"{value}\", expected e.g. TvZ"));
+                }
+                search.matchup = Some(value);
+            }
+            "map" => search.map = Some(value),
+            "after" | "since" => search.after_ms = Some(parse_date(&value)?),
+            "before" | "until" => search.before_ms = Some(parse_date(&value)?),
+            "minlen" => search.min_duration_ms = Some(parse_duration(&value)?),
+            "maxlen" => search.max_duration_ms = Some(parse_duration(&value)?),
+            "winner" | "won" => search.winner = Some(value),
+            "chat" => search.chat = Some(value),
+            "sort" => {
+                search.sort = match value.to_ascii_lowercase().as_str() {
+                    "date" => SearchSort::Date,
+                    "duration" | "length" => SearchSort::Duration,
+                    "map" => SearchSort::Map,
+                    _ => return Err(format!("Unknown sort \"{value}\"")),
+                }
+            }
+            "order" => {
+                search.ascending = match value.to_ascii_lowercase().as_str() {
+                    "asc" => Some(true),
+                    "desc" => Some(false),
+                    _ => return Err(format!("Unknown order \"{value}\", expected asc or desc")),
+                }
+            }
+            _ => return Err(format!("Unknown search filter \"{key}\"")),
+        }
+    }
+    Ok(search)
+}
+
+/// Splits a query into `key:value` and bare terms; values may be quoted.
+fn tokenize(query: &str) -> Result<Vec<(Option<String>, String)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(tokens);
+        }
+
+        let mut key = None;
+        let mut word = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            match c {
+                ':' if key.is_none() && !word.is_empty() => key = Some(std::mem::take(&mut word)),
+                '"' if word.is_empty() => {
+                    word = chars.by_ref().take_while(|&c| c != '"').collect();
+                    if word.is_empty() {
+                        return Err("Empty quoted search term".to_string());
+                    }
+                    break;
+                }
+                c => word.push(c),
+            }
+        }
+        if word.is_empty() {
+            return Err(format!(
+                "Missing value for \"{}:\"",
+                key.unwrap_or_default()
+            ));
+        }
+        tokens.push((key, word));
+    }
+}
+
+fn is_matchup(matchup: &str) -> bool {
+    let sides: Vec<&str> = matchup.split(['v', 'V']).collect();
+    sides.len() >= 2
+        && sides.iter().all(|side| {
+            !side.is_empty()
+                && side
+                    .chars()
+                    .all(|c| matches!(c.to_ascii_uppercase(), 'T' | 'Z' | 'P' | 'U'))
+        })
+}
+
+/// Races per side with each side's races sorted and the sides sorted, so
+/// `TvZ`, `ZvT`, `PTvZZ` and `ZZvTP` each have one key.
+pub fn matchup_key(matchup: &str) -> String {
+    let mut sides: Vec<String> = matchup
+        .split(['v', 'V'])
+        .map(|side| {
+            let mut races: Vec<char> = side.chars().map(|c| c.to_ascii_uppercase()).collect();
+            races.sort_unstable();
+            races.into_iter().collect()
+        })
+        .collect();
+    sides.sort_unstable();
+    sides.join("v")
+}
+
+/// A `LIKE` pattern matching `text` anywhere, with wildcards escaped.
+fn contains_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+/// Midnight UTC of a `YYYY-MM-DD` date, in ms since the Unix epoch.
+fn parse_date(date: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid date \"{date}\", expected YYYY-MM-DD");
+    let mut parts = date.splitn(3, '-').map(|p| p.parse::<u32>());
+    let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    Ok(calendar::days_from_civil(year as i64, month, day) as u64 * 86_400_000)
+}
+
+/// A game length like `90s`, `12m`, `1h5m` or `12:30`; a bare number is
+/// minutes.
+fn parse_duration(duration: &str) -> Result<u32, String> {
+    let invalid = || format!("Invalid duration \"{duration}\", expected e.g. 12m or 12:30");
+    let number = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+    // Lengths past u32::MAX milliseconds are as invalid as typos.
+    let scaled = |n: u32, unit: u32| n.checked_mul(unit).ok_or_else(invalid);
+
+    let secs = if let Some((minutes, seconds)) = duration.split_once(':') {
+        scaled(number(minutes)?, 60)?
+            .checked_add(number(seconds)?)
+            .ok_or_else(invalid)?
+    } else if duration.chars().all(|c| c.is_ascii_digit()) {
+        scaled(number(duration)?, 60)?
+    } else {
+        let mut secs = 0;
+        let mut digits = String::new();
+        for c in duration.chars() {
+            let unit = match c.to_ascii_lowercase() {
+                c if c.is_ascii_digit() => {
+                    digits.push(c);
+                    continue;
+                }
+                'h' => 3600,
+                'm' => 60,
+                's' => 1,
+                _ => return Err(invalid()),
+            };
+            let value = scaled(number(&std::mem::take(&mut digits))?, unit)?;
+            secs = value.checked_add(secs).ok_or_else(invalid)?;
+        }
+        if !digits.is_empty() {
+            return Err(invalid());
+        }
+        secs
+    };
+    scaled(secs, 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        replay_library::ReplayLibrary,
+        replay_parser::{builder::ReplayBuilder, game_info::Race},
+        test_dir::TestDir,
+    };
+    use std::fs;
+
+    #[test]
+    fn parses_queries() {
+        let search = parse_query(
+            r#"Flash mu:tvz map:"Fighting Spirit" after:2025-01-01 before:2025-02-01
+               minlen:10m maxlen:1h2m30s winner:Flash chat:gg sort:duration order:asc"#,
+        )
+        .unwrap();
+        assert_eq!(
+            search,
+            ReplaySearch {
+                players: vec!["Flash".to_string()],
+                matchup: Some("tvz".to_string()),
+                map: Some("Fighting Spirit".to_string()),
+                after_ms: Some(1_735_689_600_000),
+                before_ms: Some(1_738_368_000_000),
+                min_duration_ms: Some(600_000),
+                max_duration_ms: Some(3_750_000),
+                winner: Some("Flash".to_string()),
+                chat: Some("gg".to_string()),
+                sort: SearchSort::Duration,
+                ascending: Some(true),
+                ..ReplaySearch::default()
+            }
+        );
+        assert_eq!(parse_query("  ").unwrap(), ReplaySearch::default());
+        assert_eq!(parse_duration("12:30"), Ok(750_000));
+        assert_eq!(matchup_key("ZZvTP"), matchup_key("PTvZZ"));
+
+        for bad in [
+            "mu:TvX",
+            "after:2025-13-01",
+            "minlen:abc",
+            "minlen:100000",
+            "maxlen:99999999:0",
+            "maxlen:1194h",
+            "color:red",
+            "map:",
+            "\"\"",
+        ] {
+            assert!(parse_query(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn searches_the_library() {
+        let dir = TestDir::new("replay-search");
+        let games = [
+            // Flash (T) beats Jaedong (Z) on 2025-01-01, 10 minutes.
+            ReplayBuilder::new("Polypoid")
+                .frames(14_286)
+                .start_time(1_735_689_600)
+                .player("Flash", Race::Terran)
+                .player("Jaedong", Race::Zerg)
+                .chat(100, 1, "gl hf")
+                .leave(14_000, 1),
+            // Jaedong (Z) beats Flash (T) on 2025-02-01, 20 minutes.
+            ReplayBuilder::new("Fighting Spirit")
+                .frames(28_572)
+                .start_time(1_738_368_000)
+                .player("Jaedong", Race::Zerg)
+                .player("Flash", Race::Terran)
+                .chat(28_000, 1, "gg 100%")
+                .leave(28_000, 1),
+            // Bisu (P) beats Flash (T) on 2025-03-01, 15 minutes.
+            ReplayBuilder::new("Polypoid")
+                .frames(21_429)
+                .start_time(1_740_787_200)
+                .player("Bisu", Race::Protoss)
+                .player("Flash", Race::Terran)
+                .leave(21_000, 1),
+        ];
+        for (i, game) in games.iter().enumerate() {
+            fs::write(dir.join(format!("{i}.rep")), game.build()).unwrap();
+        }
+        let library = ReplayLibrary::open_in_memory().unwrap();
+        library.rescan(&[dir.to_path_buf()]).unwrap();
+
+        let maps = |query: &str| -> Vec<String> {
+            let results = library.search(&parse_query(query).unwrap(), 0, 10).unwrap();
+            assert_eq!(results.total as usize, results.replays.len(), "{query}");
+            results.replays.into_iter().map(|r| r.map_name).collect()
+        };
+
+        assert_eq!(
+            maps("flash"),
+            vec!["Polypoid", "Fighting Spirit", "Polypoid"]
+        );
+        assert_eq!(maps("mu:ZvT"), vec!["Fighting Spirit", "Polypoid"]);
+        // From Jaedong's side only ZvT matches.
+        assert_eq!(maps("Jaedong mu:TvZ"), Vec::<String>::new());
+        assert_eq!(maps("player:Flash vs:Bisu"), vec!["Polypoid"]);
+        assert_eq!(maps("map:spirit"), vec!["Fighting Spirit"]);
+        assert_eq!(
+            maps("after:2025-01-15 before:2025-03-01"),
+            vec!["Fighting Spirit"]
+        );
+        assert_eq!(maps("minlen:12m maxlen:16m"), vec!["Polypoid"]);
+        assert_eq!(maps("winner:flash"), vec!["Polypoid"]);
+        // `%` is literal, not a wildcard.
+        assert_eq!(maps("chat:100%"), vec!["Fighting Spirit"]);
+        assert_eq!(maps("chat:g%hf"), Vec::<String>::new());
+        assert_eq!(
+            maps("sort:duration order:asc"),
+            vec!["Polypoid", "Polypoid", "Fighting Spirit"]
+        );
+        assert_eq!(maps("sort:map")[0], "Fighting Spirit");
+
+        // Jaedong used to play as JD; Flash never changed names.
+        let aliased = |query: &str| -> Vec<String> {
+            let mut search = parse_query(query).unwrap();
+            search
+                .add_aliases(|name| {
+                    Ok(match name.to_lowercase().as_str() {
+                        "jd" | "jaedong" => vec![name.to_string(), "Jaedong".to_string()],
+                        _ => vec![name.to_string()],
+                    })
+                })
+                .unwrap();
+            let results = library.search(&search, 0, 10).unwrap();
+            results.replays.into_iter().map(|r| r.map_name).collect()
+        };
+        assert_eq!(maps("toon:JD"), Vec::<String>::new());
+        assert_eq!(aliased("toon:JD"), vec!["Fighting Spirit", "Polypoid"]);
+        assert_eq!(
+            aliased("flash vs:jd mu:TvZ"),
+            vec!["Fighting Spirit", "Polypoid"]
+        );
+        assert_eq!(aliased("winner:JD"), vec!["Fighting Spirit"]);
+
+        let page = library
+            .search(&parse_query("flash").unwrap(), 1, 1)
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.replays[0].map_name, "Fighting Spirit");
+    }
+
+    #[test]
+    fn searches_chat() {
+        let dir = TestDir::new("chat-search");
+        let game = |start_time: u32, last_words: &str| {
+            ReplayBuilder::new("Polypoid")
+                .frames(10_000)
+                .start_time(start_time)
+                .player("Flash", Race::Terran)
+                .player("Jaedong", Race::Zerg)
+                .chat(240, 0, "gl hf")
+                .chat(9_000, 1, last_words)
+                .leave(9_000, 1)
+                .build()
+        };
+        fs::write(
+            dir.join("old.rep"),
+            game(1_735_689_600, "good game well played"),
+        )
+        .unwrap();
+        let last_replay = dir.join("LastReplay.rep");
+        fs::write(
+            &last_replay,
+            game(1_738_368_000, "well that was a good try"),
+        )
+        .unwrap();
+        let library = ReplayLibrary::open_in_memory().unwrap();
+        library.rescan(&[dir.to_path_buf()]).unwrap();
+
+        let search = |text: &str, sender: Option<&str>| -> Vec<ChatHit> {
+            let search = ChatSearch {
+                text: text.to_string(),
+                sender: sender.map(str::to_string),
+            };
+            library.search_chat(&search, 0, 10).unwrap()
+        };
+
+        let hits = search("\"good game\"", None);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].sender, "Jaedong");
+        assert_eq!(hits[0].message, "good game well played");
+        assert_eq!(hits[0].start_time_ms, 1_735_689_600_000);
+        assert_eq!((hits[0].frame, hits[0].game_time_ms), (9_000, 378_000));
+        let replay = library.get(hits[0].game_id).unwrap().unwrap();
+        assert!(replay.paths[0].ends_with("old.rep"));
+
+        assert_eq!(search("good well", None).len(), 2);
+        assert_eq!(search("hf", Some("flash")).len(), 2);
+        assert!(search("hf", Some("Jaedong")).is_empty());
+        // Every line from a sender, newest game first.
+        let lines = search("", Some("Jaedong"));
+        assert_eq!(lines[0].message, "well that was a good try");
+        let everything = library.search_chat(&ChatSearch::default(), 0, 10);
+        assert_eq!(everything.unwrap().len(), 4);
+        let unbalanced = ChatSearch {
+            text: "\"unbalanced".to_string(),
+            sender: None,
+        };
+        assert!(library.search_chat(&unbalanced, 0, 10).is_err());
+
+        // Replacing a game drops its chat from the index, even when the
+        // rewrite keeps both size (chat is padded) and mtime.
+        let mtime = fs::metadata(&last_replay).unwrap().modified().unwrap();
+        fs::write(&last_replay, game(1_740_787_200, "nice")).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&last_replay)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        library.rescan(&[dir.to_path_buf()]).unwrap();
+        assert!(search("try", None).is_empty());
+        assert_eq!(search("nice", None).len(), 1);
+    }
+}
@@ -0,0 +1,309 @@
+//! Watches StarCraft's `Maps/Replays` tree for replays written after a game
+//! (`LastReplay.rep`, autosaves) and ingests each one: it is parsed, copied
+//! under the filename template and reported to the frontend.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    filename_template::{self, FilenameTemplate},
+    replay_parser::ReplayParser,
+};
+
+/// How long a replay must go without changes before it is read. The game
+/// writes replays in several steps.
+const SETTLE_TIME: Duration = Duration::from_millis(1500);
+/// Used when the configured template is empty.
+const DEFAULT_PATTERN: &str = "{date}_{time}_{p1}({r1})_vs_{p2}({r2})";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayWatcherConfig {
+    /// StarCraft's `Maps/Replays` folder, watched recursively.
+    pub watch_path: String,
+    /// Where ingested copies go. Ignored by the watcher if inside `watch_path`.
+    pub destination_path: String,
+    pub template: FilenameTemplate,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestedPlayer {
+    pub name: String,
+    pub race: String,
+}
+
+/// Summary of an ingested replay, sent with `replay-ingested`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestedReplay {
+    pub source_path: String,
+    /// The templated copy.
+    pub path: String,
+    pub map_name: String,
+    pub matchup: String,
+    pub players: Vec<IngestedPlayer>,
+    pub winners: Vec<String>,
+    pub duration_ms: u32,
+    pub start_time_ms: u64,
+}
+
+struct Running {
+    config: ReplayWatcherConfig,
+    /// Dropping the watcher disconnects the worker's channel, ending it.
+    watcher: RecommendedWatcher,
+    worker: JoinHandle<()>,
+}
+
+pub struct ReplayWatcher {
+    running: Mutex<Option<Running>>,
+    on_ingest: Arc<dyn Fn(IngestedReplay) + Send + Sync>,
+}
+
+impl ReplayWatcher {
+    pub fn new(on_ingest: impl Fn(IngestedReplay) + Send + Sync + 'static) -> Self {
+        Self {
+            running: Mutex::new(None),
+            on_ingest: Arc::new(on_ingest),
+        }
+    }
+
+    pub fn config(&self) -> Option<ReplayWatcherConfig> {
+        self.running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|r| r.config.clone())
+    }
+
+    /// Starts watching with `config`, replacing any previous watch; `None`
+    /// stops watching.
+    pub fn configure(&self, config: Option<ReplayWatcherConfig>) -> Result<(), String> {
+        let mut running = self.running.lock().unwrap();
+        if let Some(previous) = running.take() {
+            drop(previous.watcher);
+            let _ = previous.worker.join();
+        }
+        let Some(config) = config else {
+            return Ok(());
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)
+            .map_err(|e| format!("Failed to create replay watcher: {e}"))?;
+        watcher
+            .watch(Path::new(&config.watch_path), RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {e}", config.watch_path))?;
+        println!("[replay-watcher] Watching {}", config.watch_path);
+
+        let worker_config = config.clone();
+        let on_ingest = self.on_ingest.clone();
+        let worker = std::thread::spawn(move || watch(worker_config, receiver, on_ingest));
+
+        *running = Some(Running {
+            config,
+            watcher,
+            worker,
+        });
+        Ok(())
+    }
+}
+
+impl Drop for ReplayWatcher {
+    fn drop(&mut self) {
+        let _ = self.configure(None);
+    }
+}
+
+/// Collects changed replays and ingests each once it has settled.
+fn watch(
+    config: ReplayWatcherConfig,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    on_ingest: Arc<dyn Fn(IngestedReplay) + Send + Sync>,
+) {
+    let destination = PathBuf::from(&config.destination_path);
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    let mut seen = HashSet::new();
+
+    loop {
+        match events.recv_timeout(SETTLE_TIME / 3) {
+            Ok(Ok(event)) => {
+                if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    continue;
+                }
+                for path in event.paths {
+                    if is_replay(&path) && !path.starts_with(&destination) {
+                        pending.insert(path, Instant::now());
+                    }
+                }
+            }
+            Ok(Err(e)) => println!("[replay-watcher] Watch error: {e}"),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            pending.remove(&path);
+            match ingest(&config, &path, &mut seen) {
+                Ok(Some(replay)) => {
+                    println!(
+                        "[replay-watcher] Ingested {} -> {}",
+                        replay.source_path, replay.path
+                    );
+                    on_ingest(replay);
+                }
+                Ok(None) => {}
+                Err(e) => println!("[replay-watcher] Failed to ingest {}: {e}", path.display()),
+            }
+        }
+    }
+}
+
+fn is_replay(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("rep"))
+}
+
+/// Parses and copies the replay at `path`. Returns `None` for a replay whose
+/// contents were already ingested, e.g. the autosave of the game that was
+/// just written to `LastReplay.rep`.
+fn ingest(
+    config: &ReplayWatcherConfig,
+    path: &Path,
+    seen: &mut HashSet<u64>,
+) -> Result<Option<IngestedReplay>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read replay: {e}"))?;
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    if !seen.insert(hasher.finish()) {
+        return Ok(None);
+    }
+
+    let parsed = ReplayParser::new(&bytes)
+        .parse()
+        .map_err(|e| format!("Failed to parse replay: {e}"))?;
+
+    let mut template = config.template.clone();
+    if template.pattern.trim().is_empty() {
+        template.pattern = DEFAULT_PATTERN.to_string();
+    }
+    let relative = filename_template::render(&template, &parsed)?;
+    let copy =
+        filename_template::unique_path(Path::new(&config.destination_path), &relative, &bytes)?;
+    if !copy.exists() {
+        if let Some(parent) = copy.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {e}"))?;
+        }
+        fs::write(&copy, &bytes).map_err(|e| format!("Failed to write file: {e}"))?;
+    }
+
+    Ok(Some(IngestedReplay {
+        source_path: path.to_string_lossy().to_string(),
+        path: copy.to_string_lossy().to_string(),
+        map_name: parsed.game_info.map_name.clone(),
+        matchup: parsed.matchup(),
+        players: parsed
+            .players()
+            .iter()
+            .map(|p| IngestedPlayer {
+                name: p.name.clone(),
+                race: format!("{:?}", p.race),
+            })
+            .collect(),
+        winners: parsed.winners().iter().map(|p| p.name.clone()).collect(),
+        duration_ms: parsed.duration_ms(),
+        start_time_ms: parsed
+            .game_info
+            .start_time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        replay_parser::{builder::ReplayBuilder, game_info::Race},
+        test_dir::TestDir,
+    };
+
+    fn config(root: &Path) -> ReplayWatcherConfig {
+        ReplayWatcherConfig {
+            watch_path: root.to_string_lossy().to_string(),
+            destination_path: root.join("CWAL").to_string_lossy().to_string(),
+            template: FilenameTemplate {
+                pattern: "{matchup}/{p1}_vs_{p2}".to_string(),
+                game_id: None,
+                player: None,
+            },
+        }
+    }
+
+    fn replay() -> Vec<u8> {
+        ReplayBuilder::new("Polypoid")
+            .frames(10_000)
+            .start_time(1_735_689_600)
+            .player("Flash", Race::Terran)
+            .player("Jaedong", Race::Zerg)
+            .leave(9_000, 1)
+            .build()
+    }
+
+    #[test]
+    fn copies_under_the_template_once_per_game() {
+        let root = TestDir::new("replay-watcher-ingest");
+        let config = config(&root);
+        let last_replay = root.join("LastReplay.rep");
+        let autosave = root.join("AutoSave").join("2025-01-01.rep");
+        fs::create_dir_all(autosave.parent().unwrap()).unwrap();
+        fs::write(&last_replay, replay()).unwrap();
+        fs::write(&autosave, replay()).unwrap();
+
+        let mut seen = HashSet::new();
+        let ingested = ingest(&config, &last_replay, &mut seen).unwrap().unwrap();
+        assert_eq!(
+            PathBuf::from(&ingested.path),
+            root.join("CWAL").join("TvZ").join("Flash_vs_Jaedong.rep")
+        );
+        assert_eq!(fs::read(&ingested.path).unwrap(), replay());
+        assert_eq!(ingested.winners, vec!["Flash"]);
+        assert_eq!(ingested.players[1].race, "Zerg");
+
+        assert!(ingest(&config, &autosave, &mut seen).unwrap().is_none());
+    }
+
+    #[test]
+    fn ingests_replays_written_into_the_watched_tree() {
+        let root = TestDir::new("replay-watcher-watch");
+        let (sender, receiver) = mpsc::channel();
+        let watcher = ReplayWatcher::new(move |replay| sender.send(replay).unwrap());
+        watcher.configure(Some(config(&root))).unwrap();
+
+        fs::write(root.join("LastReplay.rep"), replay()).unwrap();
+        let ingested = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(ingested.matchup, "TvZ");
+        assert!(ingested.source_path.ends_with("LastReplay.rep"));
+
+        // The copy lands inside the watched tree and must not be picked up.
+        assert!(receiver.recv_timeout(SETTLE_TIME * 2).is_err());
+
+        watcher.configure(None).unwrap();
+    }
+}
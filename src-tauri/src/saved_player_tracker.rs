@@ -0,0 +1,306 @@
+//! Follows the players saved in `saved_players.json`: every few minutes each
+//! of their toons' ladder histories is fetched from the local web API, and
+//! finished games not seen before are recorded in `LadderHistory` and
+//! reported. Each game also records the toon as one the player's account
+//! used at the time. Requests go through the shared `HttpClient`, so they
+//! are paced by the local API rate limit along with everything else.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tauri::async_runtime::JoinHandle;
+
+use crate::{
+    ladder_history::{LadderHistory, SavedPlayerMatch},
+    replay_library::Outcome,
+    sc_api::{MatchHistoryRequest, MatchmakerGame, ScApiClient},
+    session::{current_season, LADDER_1V1},
+};
+
+pub const SAVED_PLAYER_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Recent games fetched per toon and check.
+const GAMES_PER_CHECK: u32 = 10;
+
+/// A toon of a saved player, as the frontend writes it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedProfile {
+    pub toon: String,
+    pub gateway: u16,
+}
+
+/// Entry of `saved_players.json`; fields the backend doesn't use are skipped.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedPlayer {
+    pub aurora_id: u64,
+    pub alias: String,
+    #[serde(default)]
+    pub profiles: Vec<SavedProfile>,
+}
+
+/// The saved players in `path`; none if it is missing or unreadable.
+pub fn load_saved_players(path: &Path) -> Vec<SavedPlayer> {
+    fs::read_to_string(path)
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .and_then(|s| match serde_json::from_str(&s) {
+            Ok(players) => Some(players),
+            Err(e) => {
+                println!("[saved-players] Failed to parse {}: {e}", path.display());
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+pub struct SavedPlayerTracker {
+    api: Arc<ScApiClient>,
+    history: Arc<LadderHistory>,
+    players_path: PathBuf,
+    last_error: Mutex<Option<String>>,
+    on_match: Box<dyn Fn(SavedPlayerMatch) + Send + Sync>,
+}
+
+impl SavedPlayerTracker {
+    pub fn new(
+        api: Arc<ScApiClient>,
+        history: Arc<LadderHistory>,
+        players_path: PathBuf,
+        on_match: impl Fn(SavedPlayerMatch) + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            api,
+            history,
+            players_path,
+            last_error: Mutex::new(None),
+            on_match: Box::new(on_match),
+        })
+    }
+
+    /// Checks every `interval` until the returned task is aborted. Checks are
+    /// skipped while the game isn't running.
+    pub fn start(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let tracker = self.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                if tracker.api.is_available() {
+                    let result = tracker.check().await;
+                    tracker.log_error(result.err());
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// Logs a failed check once rather than on every check until it clears.
+    fn log_error(&self, error: Option<String>) {
+        let mut last_error = self.last_error.lock().unwrap();
+        if let Some(e) = error.as_ref().filter(|e| last_error.as_ref() != Some(*e)) {
+            println!("[saved-players] Failed to check for new matches: {e}");
+        }
+        *last_error = error;
+    }
+
+    /// Checks each saved toon once, returning how many new matches were
+    /// found. A toon that fails is skipped until the next check.
+    pub async fn check(&self) -> Result<usize, String> {
+        let players = load_saved_players(&self.players_path);
+        if players.iter().all(|p| p.profiles.is_empty()) {
+            return Ok(0);
+        }
+        let leaderboards = self.api.leaderboards().await?;
+        let season = current_season(&leaderboards)
+            .ok_or("No 1v1 leaderboard found to determine the season".to_string())?;
+
+        let mut found = 0;
+        for player in &players {
+            for profile in &player.profiles {
+                let games = match self
+                    .api
+                    .match_history(&MatchHistoryRequest {
+                        toon: profile.toon.clone(),
+                        gateway: profile.gateway,
+                        gamemode: LADDER_1V1,
+                        season,
+                        offset: 0,
+                        limit: GAMES_PER_CHECK,
+                    })
+                    .await
+                {
+                    Ok(games) => games,
+                    Err(e) => {
+                        println!(
+                            "[saved-players] Failed to fetch matches of {}: {e}",
+                            profile.toon
+                        );
+                        continue;
+                    }
+                };
+                let matches: Vec<SavedPlayerMatch> = games
+                    .iter()
+                    .filter_map(|g| finished_match(player, profile, g))
+                    .collect();
+                let new = self.record(player.aurora_id, profile, matches).await?;
+                found += new.len();
+                for m in new {
+                    (self.on_match)(m);
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Records `matches` of `profile`, returning those not seen before. Runs
+    /// off the async runtime, as it writes to the database.
+    async fn record(
+        &self,
+        aurora_id: u64,
+        profile: &SavedProfile,
+        matches: Vec<SavedPlayerMatch>,
+    ) -> Result<Vec<SavedPlayerMatch>, String> {
+        let history = self.history.clone();
+        let profile = profile.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            for m in &matches {
+                history.observe_toons(
+                    aurora_id,
+                    &[(m.toon.clone(), m.gateway)],
+                    m.match_created_ms,
+                )?;
+            }
+            history.record_matches(&profile.toon, profile.gateway, &matches)
+        })
+        .await
+        .map_err(|e| format!("Failed to record matches: {e}"))?
+    }
+}
+
+/// `game` from the side of `profile`, if it has a result yet.
+fn finished_match(
+    player: &SavedPlayer,
+    profile: &SavedProfile,
+    game: &MatchmakerGame,
+) -> Option<SavedPlayerMatch> {
+    let me = game
+        .players
+        .iter()
+        .find(|p| p.toon.eq_ignore_ascii_case(&profile.toon) && p.gateway_id == profile.gateway)?;
+    let outcome = Outcome::from_ladder_result(&me.result);
+    if outcome == Outcome::Unknown {
+        return None;
+    }
+    let opponent = game.players.iter().find(|p| !std::ptr::eq(*p, me))?;
+    Some(SavedPlayerMatch {
+        aurora_id: player.aurora_id,
+        alias: player.alias.clone(),
+        toon: me.toon.clone(),
+        gateway: me.gateway_id,
+        match_guid: game.match_guid.clone(),
+        // Seconds since the Unix epoch.
+        match_created_ms: game.match_created.parse::<u64>().unwrap_or_default() * 1000,
+        map: game.map.clone(),
+        race: me.race.clone(),
+        opponent: opponent.toon.clone(),
+        opponent_gateway: opponent.gateway_id,
+        opponent_race: opponent.race.clone(),
+        outcome,
+        points: me.points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_dir::TestDir,
+        test_server::{api_client, TestResponse, TestServer},
+    };
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn reports_matches_after_the_first_check() {
+        let dir = TestDir::new("saved-players");
+        let players_path = dir.join("saved_players.json");
+        fs::write(
+            &players_path,
+            r#"[{"auroraId": 7, "alias": "Flash", "createdAt": 1,
+                 "profiles": [{"toon": "Flash", "gateway": 30, "race": "terran"}]}]"#,
+        )
+        .unwrap();
+
+        let played = Arc::new(AtomicBool::new(false));
+        let new_game = played.clone();
+        let server = TestServer::start(move |req| {
+            let path = req.path.as_str();
+            let body = if path == "/web-api/v1/leaderboard" {
+                r#"{"leaderboards": {"1": {"id": 1, "gamemode_id": 1, "season_id": 17}}}"#
+                    .to_string()
+            } else if path.starts_with("/web-api/v1/matchmaker-gameinfo-by-toon/Flash/30/1/17?") {
+                let game = |guid: &str, created: u32, result: &str| {
+                    format!(
+                        r#"{{"match_guid": "{guid}", "match_created": "{created}", "map": "Polypoid",
+                            "players": [{{"toon": "Flash", "gateway_id": 30, "race": "terran",
+                                          "result": "{result}", "points": 2000}},
+                                        {{"toon": "Jaedong", "gateway_id": 10, "race": "zerg",
+                                          "result": ""}}]}}"#
+                    )
+                };
+                let mut games = vec![game("old", 100, "win")];
+                if new_game.load(Ordering::SeqCst) {
+                    games.push(game("new", 200, "loss"));
+                    games.push(game("live", 300, ""));
+                }
+                format!("[{}]", games.join(","))
+            } else {
+                return TestResponse::status(404);
+            };
+            TestResponse::ok(body.into_bytes())
+        });
+
+        let api = api_client(&server);
+        let history = Arc::new(LadderHistory::open_in_memory().unwrap());
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let tracker = SavedPlayerTracker::new(api, history.clone(), players_path, move |m| {
+            sink.lock().unwrap().push(m)
+        });
+
+        // What was already played isn't news.
+        assert_eq!(tauri::async_runtime::block_on(tracker.check()).unwrap(), 0);
+        played.store(true, Ordering::SeqCst);
+        assert_eq!(tauri::async_runtime::block_on(tracker.check()).unwrap(), 1);
+        assert_eq!(tauri::async_runtime::block_on(tracker.check()).unwrap(), 0);
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 1);
+        let m = &reported[0];
+        assert_eq!(
+            (m.match_guid.as_str(), m.alias.as_str(), m.opponent.as_str()),
+            ("new", "Flash", "Jaedong")
+        );
+        assert_eq!((m.outcome, m.match_created_ms), (Outcome::Loss, 200_000));
+
+        let feed = history.saved_player_matches(Some(7), 0, 10).unwrap();
+        let guids: Vec<&str> = feed.iter().map(|m| m.match_guid.as_str()).collect();
+        assert_eq!(guids, vec!["new", "old"]);
+        assert!(history
+            .saved_player_matches(Some(8), 0, 10)
+            .unwrap()
+            .is_empty());
+        let aliases = history.toon_aliases(7).unwrap();
+        assert_eq!(
+            (
+                aliases.len(),
+                aliases[0].first_seen_ms,
+                aliases[0].last_seen_ms
+            ),
+            (1, 100_000, 200_000)
+        );
+    }
+}
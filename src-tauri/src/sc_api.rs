@@ -0,0 +1,517 @@
+//! Typed client for the web API StarCraft: Remastered serves on localhost.
+//!
+//! The API is undocumented, so response types name the fields the app relies
+//! on and keep everything else in `extra`, which is passed through to the
+//! frontend untouched.
+
+use std::{collections::HashMap, sync::Arc};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::http::HttpClient;
+
+/// `request_flags` understood by aurora-profile-by-toon, which select how
+/// much of the profile is returned.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum ProfileFlags {
+    #[default]
+    #[serde(rename = "scr_profile")]
+    Profile,
+    #[serde(rename = "scr_mmgameloading")]
+    MmGameLoading,
+    #[serde(rename = "scr_mmtooninfo")]
+    MmToonInfo,
+    #[serde(rename = "scr_tooninfo")]
+    ToonInfo,
+}
+
+impl ProfileFlags {
+    fn as_str(self) -> &'static str {
+        match self {
+            ProfileFlags::Profile => "scr_profile",
+            ProfileFlags::MmGameLoading => "scr_mmgameloading",
+            ProfileFlags::MmToonInfo => "scr_mmtooninfo",
+            ProfileFlags::ToonInfo => "scr_tooninfo",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToonRequest {
+    pub toon: String,
+    pub gateway: u16,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardRequest {
+    pub leaderboard_id: u32,
+    #[serde(default)]
+    pub offset: u32,
+    #[serde(default = "default_length")]
+    pub length: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchHistoryRequest {
+    pub toon: String,
+    pub gateway: u16,
+    pub gamemode: u32,
+    pub season: u32,
+    #[serde(default)]
+    pub offset: u32,
+    #[serde(default = "default_length")]
+    pub limit: u32,
+}
+
+fn default_length() -> u32 {
+    100
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct ToonProfile {
+    pub toon: String,
+    pub toon_guid: u64,
+    pub title: String,
+    pub private: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct MatchmakedStats {
+    pub toon: String,
+    pub gateway_id: u16,
+    pub leaderboard_id: u32,
+    pub season_id: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub disconnects: u32,
+    pub highest_points: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct ProfileReplay {
+    pub link: String,
+    pub url: String,
+    pub md5: String,
+    pub create_time: u64,
+    /// Game metadata such as `map_title`, `game_id` and `replay_player_names`.
+    pub attributes: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct AuroraProfile {
+    pub aurora_id: u64,
+    pub battle_tag: String,
+    pub country_code: String,
+    pub profiles: Vec<ToonProfile>,
+    pub matchmaked_stats: Vec<MatchmakedStats>,
+    pub replays: Vec<ProfileReplay>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct LeaderboardInfo {
+    pub id: u32,
+    pub name: String,
+    pub season_id: u32,
+    pub gamemode_id: u32,
+    pub gateway_id: u16,
+    pub last_update_time: String,
+    pub next_update_time: String,
+}
+
+/// Index of every leaderboard (`/web-api/v1/leaderboard`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct Leaderboards {
+    pub leaderboards: HashMap<String, LeaderboardInfo>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardEntry {
+    pub rank: u32,
+    pub last_rank: u32,
+    pub gateway_id: u16,
+    pub points: i64,
+    pub wins: u32,
+    pub losses: u32,
+    pub disconnects: u32,
+    pub toon: String,
+    pub battletag: String,
+    pub avatar: String,
+    pub feature_stat: String,
+    /// Remaining columns, by column name.
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardPage {
+    pub leaderboard_id: u32,
+    pub offset: u32,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// Leaderboard pages arrive as a column list plus positional rows.
+#[derive(Deserialize)]
+struct RawLeaderboard {
+    #[serde(default)]
+    columns: Vec<String>,
+    #[serde(default)]
+    rows: Vec<Vec<Value>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct MatchPlayer {
+    pub toon: String,
+    pub gateway_id: u16,
+    pub race: String,
+    pub result: String,
+    pub points: i64,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct MatchmakerGame {
+    pub match_guid: String,
+    pub match_created: String,
+    pub map: String,
+    pub players: Vec<MatchPlayer>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct MatchmakerPlayerStat {
+    pub toon: String,
+    pub gateway_id: u16,
+    pub wins: u32,
+    pub losses: u32,
+    pub disconnects: u32,
+    pub points: i64,
+    pub rating: i64,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct MapStat {
+    pub wins: u32,
+    pub losses: u32,
+    pub games: u32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Gamemode -> season -> race -> map md5 -> results.
+pub type MapStatTree = HashMap<String, HashMap<String, HashMap<String, HashMap<String, MapStat>>>>;
+
+/// Per-map results for a toon.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all(serialize = "camelCase"))]
+pub struct MapStats {
+    pub current_season: u32,
+    pub map_stat: MapStatTree,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The account the session follows. The web API has no endpoint naming
+/// the signed-in account, so it is the toon picked in settings, with the
+/// account details from its profile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentAccount {
+    pub aurora_id: u64,
+    pub battle_tag: String,
+    pub toon: String,
+    pub gateway_id: u16,
+}
+
+/// Client for the local web API. The port comes from `port`, normally the
+/// one confirmed by `ScrProcessEventProvider`, and is looked up per request
+/// so a restarted game is picked up without reconnecting.
+pub struct ScApiClient {
+    http: Arc<HttpClient>,
+    port: Box<dyn Fn() -> Option<u16> + Send + Sync>,
+}
+
+impl ScApiClient {
+    pub fn new(
+        http: Arc<HttpClient>,
+        port: impl Fn() -> Option<u16> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            http,
+            port: Box::new(port),
+        }
+    }
+
+    /// Whether the game's web API port is currently known.
+    pub fn is_available(&self) -> bool {
+        (self.port)().is_some()
+    }
+
+    fn url(&self, path: &str) -> Result<String, String> {
+        let port = (self.port)().ok_or("StarCraft web API is not running".to_string())?;
+        Ok(format!("http://localhost:{port}{path}"))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        let url = self.url(path)?;
+        let body = self.http.get_text(&url).await?;
+        // Errors sometimes come back as an HTML page with a 200.
+        let trimmed = body.trim_start().to_lowercase();
+        if trimmed.starts_with("<!doctype") || trimmed.starts_with("internal") {
+            return Err(format!(
+                "StarCraft web API returned an error page for {path}"
+            ));
+        }
+        serde_json::from_str(&body).map_err(|e| format!("Failed to parse {path}: {e}"))
+    }
+
+    pub async fn aurora_profile(
+        &self,
+        request: &ToonRequest,
+        flags: ProfileFlags,
+    ) -> Result<AuroraProfile, String> {
+        self.get(&format!(
+            "/web-api/v2/aurora-profile-by-toon/{}/{}?request_flags={}",
+            encode(&request.toon),
+            request.gateway,
+            flags.as_str()
+        ))
+        .await
+    }
+
+    pub async fn leaderboards(&self) -> Result<Leaderboards, String> {
+        self.get("/web-api/v1/leaderboard").await
+    }
+
+    pub async fn leaderboard(
+        &self,
+        request: &LeaderboardRequest,
+    ) -> Result<LeaderboardPage, String> {
+        let raw: RawLeaderboard = self
+            .get(&format!(
+                "/web-api/v1/leaderboard/{}?offset={}&length={}",
+                request.leaderboard_id, request.offset, request.length
+            ))
+            .await?;
+        Ok(LeaderboardPage {
+            leaderboard_id: request.leaderboard_id,
+            offset: request.offset,
+            entries: raw
+                .rows
+                .into_iter()
+                .map(|row| leaderboard_entry(&raw.columns, row))
+                .collect(),
+        })
+    }
+
+    pub async fn match_history(
+        &self,
+        request: &MatchHistoryRequest,
+    ) -> Result<Vec<MatchmakerGame>, String> {
+        self.get(&format!(
+            "/web-api/v1/matchmaker-gameinfo-by-toon/{}/{}/{}/{}?offset={}&limit={}",
+            encode(&request.toon),
+            request.gateway,
+            request.gamemode,
+            request.season,
+            request.offset,
+            request.limit
+        ))
+        .await
+    }
+
+    pub async fn player_stats(
+        &self,
+        request: &ToonRequest,
+        gamemode: u32,
+        season: u32,
+    ) -> Result<Vec<MatchmakerPlayerStat>, String> {
+        self.get(&format!(
+            "/web-api/v1/matchmaker-player-stat-by-toon/{}/{}/{gamemode}/{season}",
+            encode(&request.toon),
+            request.gateway
+        ))
+        .await
+    }
+
+    pub async fn map_stats(&self, request: &ToonRequest) -> Result<MapStats, String> {
+        self.get(&format!(
+            "/web-api/v1/map-stats-by-toon/{}/{}",
+            encode(&request.toon),
+            request.gateway
+        ))
+        .await
+    }
+}
+
+fn leaderboard_entry(columns: &[String], row: Vec<Value>) -> LeaderboardEntry {
+    let mut entry = LeaderboardEntry::default();
+    for (column, value) in columns.iter().zip(row) {
+        let number = value
+            .as_i64()
+            .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+            .unwrap_or_default();
+        let text = value
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| value.to_string());
+        match column.as_str() {
+            "rank" => entry.rank = number as u32,
+            "last_rank" => entry.last_rank = number as u32,
+            "gateway_id" => entry.gateway_id = number as u16,
+            "points" => entry.points = number,
+            "wins" => entry.wins = number as u32,
+            "losses" => entry.losses = number as u32,
+            "disconnects" => entry.disconnects = number as u32,
+            "toon" => entry.toon = text,
+            "battletag" => entry.battletag = text,
+            "avatar" => entry.avatar = text,
+            "feature_stat" => entry.feature_stat = text,
+            _ => {
+                entry.extra.insert(column.clone(), value);
+            }
+        }
+    }
+    entry
+}
+
+/// Percent-encodes a path segment; toons may contain non-ASCII characters.
+fn encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::HttpClientConfig,
+        rate_limit::{RateLimitConfig, RateLimiter},
+        test_server::{api_client, TestResponse, TestServer},
+    };
+
+    #[test]
+    fn maps_leaderboard_rows_by_column_name() {
+        let server = TestServer::start(|req| {
+            assert_eq!(req.path, "/web-api/v1/leaderboard/12960?offset=0&length=2");
+            TestResponse::ok(
+                br#"{
+                    "columns": ["rank", "last_rank", "gateway_id", "points", "wins", "losses",
+                                "disconnects", "toon", "battletag", "avatar", "feature_stat", "race"],
+                    "rows": [
+                        [1, 2, 30, 2950, 120, 40, 1, "Flash", "Flash#1234", "", "", "Terran"],
+                        [2, 1, 30, "2900", 100, 35, 0, "Jaedong", "Jaedong#5678", "", "", "Zerg"]
+                    ]
+                }"#
+                .to_vec(),
+            )
+        });
+
+        let page =
+            tauri::async_runtime::block_on(api_client(&server).leaderboard(&LeaderboardRequest {
+                leaderboard_id: 12960,
+                offset: 0,
+                length: 2,
+            }))
+            .unwrap();
+
+        assert_eq!(page.entries.len(), 2);
+        assert_eq!(page.entries[0].toon, "Flash");
+        assert_eq!(page.entries[0].points, 2950);
+        assert_eq!(page.entries[1].points, 2900);
+        assert_eq!(page.entries[1].last_rank, 1);
+        assert_eq!(page.entries[1].extra["race"], "Zerg");
+    }
+
+    #[test]
+    fn parses_profiles_and_keeps_unknown_fields() {
+        let server = TestServer::start(|req| {
+            assert_eq!(
+                req.path,
+                "/web-api/v2/aurora-profile-by-toon/%ED%99%8D/10?request_flags=scr_profile"
+            );
+            TestResponse::ok(
+                br#"{
+                    "aurora_id": 42,
+                    "battle_tag": "Hong#1",
+                    "profiles": [{"toon": "Hong", "toon_guid": 7, "title": "", "private": false}],
+                    "matchmaked_stats": [{"toon": "Hong", "gateway_id": 10, "leaderboard_id": 12960,
+                                          "season_id": 17, "wins": 3, "losses": 1}],
+                    "replays": [{"link": "l", "url": "u", "create_time": 1700000000,
+                                 "attributes": {"map_title": "Polypoid"}}],
+                    "game_records": {"1": []}
+                }"#
+                .to_vec(),
+            )
+        });
+
+        let profile = tauri::async_runtime::block_on(api_client(&server).aurora_profile(
+            &ToonRequest {
+                toon: "홍".to_string(),
+                gateway: 10,
+            },
+            ProfileFlags::Profile,
+        ))
+        .unwrap();
+
+        assert_eq!(profile.aurora_id, 42);
+        assert_eq!(profile.matchmaked_stats[0].wins, 3);
+        assert_eq!(profile.replays[0].attributes["map_title"], "Polypoid");
+        assert!(profile.extra.contains_key("game_records"));
+    }
+
+    #[test]
+    fn rejects_html_error_pages() {
+        let server =
+            TestServer::start(|_| TestResponse::ok(b"<!DOCTYPE html><html></html>".to_vec()));
+
+        let err = tauri::async_runtime::block_on(api_client(&server).leaderboards()).unwrap_err();
+
+        assert!(err.contains("error page"), "{err}");
+    }
+
+    #[test]
+    fn fails_fast_when_the_game_is_not_running() {
+        let http = Arc::new(
+            HttpClient::new(
+                HttpClientConfig::default(),
+                Arc::new(RateLimiter::new(RateLimitConfig::default(), |_| {})),
+            )
+            .unwrap(),
+        );
+        let client = ScApiClient::new(http, || None);
+
+        let err = tauri::async_runtime::block_on(client.leaderboards()).unwrap_err();
+
+        assert_eq!(err, "StarCraft web API is not running");
+    }
+}
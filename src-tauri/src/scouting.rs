@@ -0,0 +1,409 @@
+//! Scouting report on a ladder opponent, put together when a match starts
+//! from their recent ladder games on the web API and any replays of them in
+//! the library. Builds, and so openings, are only known from replays; game
+//! lengths likewise. Replays are also found under the names the opponent's
+//! account was seen using before.
+
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Serialize;
+
+use crate::{
+    ladder_history::LadderHistory,
+    player_stats::{breakdowns, perspective_matchup, Breakdown, WinRecord},
+    replay_library::{LibraryPlayer, LibraryReplay, Outcome, ReplayLibrary},
+    sc_api::{MatchHistoryRequest, MatchmakerGame, ScApiClient},
+    session::{current_season, LADDER_1V1},
+};
+
+/// Ladder games fetched for the report.
+const LADDER_GAMES: u32 = 25;
+/// Games listed in `ScoutingReport::recent_results`.
+const RECENT_RESULTS: usize = 10;
+/// A replay starting this close to a ladder game against the same player is
+/// taken to be that game, and not counted twice.
+const SAME_GAME_WINDOW_MS: u64 = 15 * 60 * 1000;
+
+/// Who to scout: the opponent of a match that just started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoutTarget {
+    pub toon: String,
+    pub gateway_id: u16,
+    pub map: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GameSource {
+    Ladder,
+    Library,
+}
+
+/// One of the opponent's games, from their side.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoutedGame {
+    pub source: GameSource,
+    pub start_time_ms: u64,
+    pub map_name: String,
+    pub race: String,
+    /// From the opponent's side, e.g. `ZvT`.
+    pub matchup: String,
+    pub opponents: Vec<String>,
+    pub outcome: Outcome,
+    pub duration_ms: Option<u32>,
+    pub opening: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchupOpenings {
+    pub matchup: String,
+    pub openings: Vec<Breakdown>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoutingReport {
+    pub toon: String,
+    pub gateway_id: u16,
+    pub map: String,
+    /// The race played most, if any games were found.
+    pub preferred_race: Option<String>,
+    pub races: Vec<Breakdown>,
+    pub openings_by_matchup: Vec<MatchupOpenings>,
+    /// Games on the map of the match.
+    pub map_record: WinRecord,
+    pub average_duration_ms: Option<u32>,
+    /// Newest first.
+    pub recent_results: Vec<ScoutedGame>,
+    pub ladder_games: u32,
+    pub library_games: u32,
+    /// Why ladder games are missing, e.g. the web API being unavailable.
+    pub ladder_error: Option<String>,
+}
+
+/// Fetches the target's ladder games and library replays and builds the
+/// report. A failing web API leaves the report to the library alone.
+pub async fn scout(
+    api: &ScApiClient,
+    library: Arc<ReplayLibrary>,
+    history: Arc<LadderHistory>,
+    target: ScoutTarget,
+) -> Result<ScoutingReport, String> {
+    let (ladder, ladder_error) = match ladder_games(api, &target).await {
+        Ok(games) => (games, None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+    let (toon, gateway) = (target.toon.clone(), target.gateway_id);
+    let (names, replays) = tauri::async_runtime::spawn_blocking(move || {
+        let names = history.names_of(&toon, Some(gateway))?;
+        let replays = library.games_of(&names, None, None)?;
+        Ok::<_, String>((names, replays))
+    })
+    .await
+    .map_err(|e| format!("Failed to load replays for scouting: {e}"))??;
+    let mut report = build_report(&target, &names, &ladder, &replays);
+    report.ladder_error = ladder_error;
+    Ok(report)
+}
+
+async fn ladder_games(
+    api: &ScApiClient,
+    target: &ScoutTarget,
+) -> Result<Vec<MatchmakerGame>, String> {
+    let leaderboards = api.leaderboards().await?;
+    let season = current_season(&leaderboards)
+        .ok_or("No 1v1 leaderboard found to determine the season".to_string())?;
+    api.match_history(&MatchHistoryRequest {
+        toon: target.toon.clone(),
+        gateway: target.gateway_id,
+        gamemode: LADDER_1V1,
+        season,
+        offset: 0,
+        limit: LADDER_GAMES,
+    })
+    .await
+}
+
+/// `names` are those the target's replays are found under, its toon
+/// included.
+pub fn build_report(
+    target: &ScoutTarget,
+    names: &[String],
+    ladder: &[MatchmakerGame],
+    replays: &[LibraryReplay],
+) -> ScoutingReport {
+    let ladder_games: Vec<ScoutedGame> = ladder
+        .iter()
+        .filter_map(|g| from_ladder(target, g))
+        .collect();
+    let library_games: Vec<ScoutedGame> = replays
+        .iter()
+        .filter_map(|r| from_library(names, r))
+        .collect();
+    let ladder_count = ladder_games.len() as u32;
+    let library_count = library_games.len() as u32;
+
+    let mut games = ladder_games;
+    // A replay of a ladder game adds its build and length to that game.
+    for game in library_games {
+        let same = games.iter_mut().find(|g| {
+            g.source == GameSource::Ladder
+                && g.start_time_ms.abs_diff(game.start_time_ms) <= SAME_GAME_WINDOW_MS
+                && g.opponents.iter().any(|o| {
+                    game.opponents
+                        .iter()
+                        .any(|other| other.eq_ignore_ascii_case(o))
+                })
+        });
+        match same {
+            Some(ladder) => {
+                ladder.duration_ms = game.duration_ms;
+                ladder.opening = game.opening;
+            }
+            None => games.push(game),
+        }
+    }
+    games.sort_by_key(|g| std::cmp::Reverse(g.start_time_ms));
+
+    let mut races: HashMap<String, WinRecord> = HashMap::new();
+    let mut openings: HashMap<String, HashMap<String, WinRecord>> = HashMap::new();
+    let mut map_record = WinRecord::default();
+    let (mut total_duration, mut timed_games) = (0u64, 0u64);
+    for game in &games {
+        races
+            .entry(game.race.clone())
+            .or_default()
+            .add(game.outcome);
+        if let Some(opening) = &game.opening {
+            openings
+                .entry(game.matchup.clone())
+                .or_default()
+                .entry(opening.clone())
+                .or_default()
+                .add(game.outcome);
+        }
+        if same_map(&game.map_name, &target.map) {
+            map_record.add(game.outcome);
+        }
+        if let Some(duration) = game.duration_ms {
+            total_duration += duration as u64;
+            timed_games += 1;
+        }
+    }
+
+    let races = breakdowns(races);
+    let mut openings_by_matchup: Vec<MatchupOpenings> = openings
+        .into_iter()
+        .map(|(matchup, openings)| MatchupOpenings {
+            matchup,
+            openings: breakdowns(openings),
+        })
+        .collect();
+    openings_by_matchup.sort_by(|a, b| a.matchup.cmp(&b.matchup));
+    games.truncate(RECENT_RESULTS);
+
+    ScoutingReport {
+        toon: target.toon.clone(),
+        gateway_id: target.gateway_id,
+        map: target.map.clone(),
+        preferred_race: races.first().map(|r| r.key.clone()),
+        races,
+        openings_by_matchup,
+        map_record,
+        average_duration_ms: (timed_games > 0).then(|| (total_duration / timed_games) as u32),
+        recent_results: games,
+        ladder_games: ladder_count,
+        library_games: library_count,
+        ladder_error: None,
+    }
+}
+
+fn from_ladder(target: &ScoutTarget, game: &MatchmakerGame) -> Option<ScoutedGame> {
+    let me = game
+        .players
+        .iter()
+        .find(|p| p.toon.eq_ignore_ascii_case(&target.toon) && p.gateway_id == target.gateway_id)?;
+    let opponents: Vec<_> = game.players.iter().filter(|p| p.toon != me.toon).collect();
+    let initial = |race: &str| race.chars().next().unwrap_or('U').to_ascii_uppercase();
+    let mut matchup = initial(&me.race).to_string();
+    if !opponents.is_empty() {
+        matchup.push('v');
+        matchup.extend(opponents.iter().map(|p| initial(&p.race)));
+    }
+    Some(ScoutedGame {
+        source: GameSource::Ladder,
+        // Seconds since the Unix epoch.
+        start_time_ms: game.match_created.parse::<u64>().unwrap_or_default() * 1000,
+        map_name: game.map.clone(),
+        race: race_name(&me.race),
+        matchup,
+        opponents: opponents.iter().map(|p| p.toon.clone()).collect(),
+        outcome: Outcome::from_ladder_result(&me.result),
+        duration_ms: None,
+        opening: None,
+    })
+}
+
+fn from_library(names: &[String], replay: &LibraryReplay) -> Option<ScoutedGame> {
+    let me = replay
+        .players
+        .iter()
+        .find(|p| names.iter().any(|n| p.name.eq_ignore_ascii_case(n)))?;
+    let opponents: Vec<&LibraryPlayer> = replay
+        .players
+        .iter()
+        .filter(|p| p.side != me.side)
+        .collect();
+    Some(ScoutedGame {
+        source: GameSource::Library,
+        start_time_ms: replay.start_time_ms,
+        map_name: replay.map_name.clone(),
+        race: race_name(&me.race),
+        matchup: perspective_matchup(me, &opponents),
+        opponents: opponents.iter().map(|p| p.name.clone()).collect(),
+        outcome: me.outcome,
+        duration_ms: Some(replay.duration_ms),
+        opening: me.opening.clone(),
+    })
+}
+
+/// `zerg` and `Zerg` alike as `Zerg`.
+fn race_name(race: &str) -> String {
+    let mut chars = race.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => "Unknown".to_string(),
+    }
+}
+
+/// Map names differ between the web API and replays in versions and
+/// decoration, e.g. `Polypoid 1.65` and `| iCCup | Polypoid`.
+fn same_map(a: &str, b: &str) -> bool {
+    let normalize = |s: &str| -> String {
+        s.chars()
+            .filter(|c| c.is_alphabetic())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (normalize(a), normalize(b));
+    !a.is_empty() && !b.is_empty() && (a.contains(&b) || b.contains(&a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_api::MatchPlayer;
+
+    fn ladder_game(
+        created_s: u64,
+        map: &str,
+        opponent: &str,
+        opponent_race: &str,
+        result: &str,
+    ) -> MatchmakerGame {
+        let player = |toon: &str, gateway_id: u16, race: &str, result: &str| MatchPlayer {
+            toon: toon.to_string(),
+            gateway_id,
+            race: race.to_string(),
+            result: result.to_string(),
+            ..MatchPlayer::default()
+        };
+        MatchmakerGame {
+            match_created: created_s.to_string(),
+            map: map.to_string(),
+            players: vec![
+                player("Jaedong", 10, "zerg", result),
+                player(opponent, 30, opponent_race, ""),
+            ],
+            ..MatchmakerGame::default()
+        }
+    }
+
+    fn replay(
+        start_s: u64,
+        map: &str,
+        opponent: &str,
+        opening: Option<&str>,
+        won: bool,
+    ) -> LibraryReplay {
+        let player =
+            |name: &str, race: &str, side: u32, won: bool, opening: Option<&str>| LibraryPlayer {
+                name: name.to_string(),
+                race: race.to_string(),
+                side,
+                outcome: if won { Outcome::Win } else { Outcome::Loss },
+                apm: 300,
+                opening: opening.map(str::to_string),
+                opening_confidence: opening.map(|_| 1.0),
+            };
+        LibraryReplay {
+            id: start_s as i64,
+            fingerprint: String::new(),
+            map_name: map.to_string(),
+            matchup: "TvZ".to_string(),
+            start_time_ms: start_s * 1000,
+            duration_ms: 600_000,
+            players: vec![
+                player(opponent, "Terran", 0, !won, None),
+                player("Jaedong", "Zerg", 1, won, opening),
+            ],
+            paths: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn combines_ladder_and_library_games() {
+        let target = ScoutTarget {
+            toon: "Jaedong".to_string(),
+            gateway_id: 10,
+            map: "Polypoid 1.65".to_string(),
+        };
+        let ladder = vec![
+            ladder_game(3_000, "Polypoid 1.65", "Flash", "terran", "win"),
+            ladder_game(2_000, "Eclipse", "Bisu", "protoss", "loss"),
+            ladder_game(1_000, "Polypoid 1.65", "Flash", "terran", "loss"),
+        ];
+        let replays = vec![
+            // The replay of the newest ladder game.
+            replay(3_060, "| iCCup | Polypoid", "Flash", Some("12 Hatch"), true),
+            // An older game only in the library.
+            replay(100, "Polypoid", "Light", Some("12 Hatch"), true),
+        ];
+        let names = vec![target.toon.clone()];
+        let report = build_report(&target, &names, &ladder, &replays);
+
+        assert_eq!((report.ladder_games, report.library_games), (3, 2));
+        assert_eq!(report.preferred_race.as_deref(), Some("Zerg"));
+        assert_eq!(report.races[0].record.games, 4);
+        assert_eq!((report.map_record.games, report.map_record.wins), (3, 2));
+        assert_eq!(report.average_duration_ms, Some(600_000));
+        assert_eq!(report.openings_by_matchup.len(), 1);
+        assert_eq!(report.openings_by_matchup[0].matchup, "ZvT");
+        assert_eq!(report.openings_by_matchup[0].openings[0].key, "12 Hatch");
+        assert_eq!(report.openings_by_matchup[0].openings[0].record.games, 2);
+
+        let recent: Vec<(GameSource, &str, Outcome)> = report
+            .recent_results
+            .iter()
+            .map(|g| (g.source, g.opponents[0].as_str(), g.outcome))
+            .collect();
+        assert_eq!(
+            recent,
+            vec![
+                (GameSource::Ladder, "Flash", Outcome::Win),
+                (GameSource::Ladder, "Bisu", Outcome::Loss),
+                (GameSource::Ladder, "Flash", Outcome::Loss),
+                (GameSource::Library, "Light", Outcome::Win),
+            ]
+        );
+        assert_eq!(
+            report.recent_results[0].opening.as_deref(),
+            Some("12 Hatch")
+        );
+        assert_eq!(report.recent_results[1].matchup, "ZvP");
+    }
+}
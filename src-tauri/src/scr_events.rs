@@ -1,40 +1,483 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
 
 use serde::Serialize;
 
-use crate::scr_process::{find_starcraft_api_port, find_starcraft_process};
+use crate::sc_api::CurrentAccount;
+use crate::scr_process::{
+    find_starcraft_listening_ports, find_starcraft_process, ProcessProbe, ScrProcess,
+    WEB_API_PROBE_PATH,
+};
 
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How the web API port was told apart from StarCraft's other listeners.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortVerification {
+    /// Path requested on the port.
+    pub probe_path: String,
+    /// Status code of the answer that confirmed it.
+    pub status: u16,
+    /// Every port the process was listening on at the time.
+    pub candidates: Vec<u16>,
+}
+
+/// Last observed state of the StarCraft process and its web server.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrSnapshot {
+    pub process: Option<ScrProcess>,
+    pub port: Option<u16>,
+    pub verification: Option<PortVerification>,
+    /// False until the first poll completes.
+    pub polled: bool,
+}
+
+impl ScrSnapshot {
+    /// Moves to `next`, returning the events describing what changed.
+    fn transition(&mut self, next: ScrSnapshot) -> Vec<ScrEvent> {
+        let mut events = Vec::new();
+        // The first poll always reports the web server's state, even if
+        // nothing is running, so listeners don't stay undecided.
+        if !self.polled && next.port.is_none() {
+            events.push(ScrEvent::WebServerDown);
+        }
+        let same_process = match (&self.process, &next.process) {
+            (Some(a), Some(b)) => a.pid == b.pid && a.started_at == b.started_at,
+            (None, None) => true,
+            _ => false,
+        };
+
+        if self.port.is_some() && (self.port != next.port || !same_process) {
+            events.push(ScrEvent::WebServerDown);
+        }
+        if self.process.is_some() && !same_process {
+            events.push(ScrEvent::ProcessExited);
+        }
+        if let (Some(process), false) = (&next.process, same_process) {
+            events.push(ScrEvent::ProcessStarted {
+                pid: process.pid,
+                started_at: process.started_at,
+                exe_path: process.exe_path.clone(),
+            });
+        }
+        if let (Some(port), Some(verification)) = (next.port, &next.verification) {
+            if self.port != Some(port) || !same_process {
+                events.push(ScrEvent::WebServerRunning {
+                    port,
+                    verification: verification.clone(),
+                });
+            }
+        }
+
+        *self = next;
+        events
+    }
+}
+
+/// Runs one detection pass against `probe` and applies it to `snapshot`.
+pub fn poll(probe: &mut dyn ProcessProbe, snapshot: &mut ScrSnapshot) -> Vec<ScrEvent> {
+    let process = find_starcraft_process(probe);
+    let confirmed = process
+        .as_ref()
+        .and_then(|p| confirm_api_port(probe, p, snapshot));
+    let (port, verification) = confirmed.unzip();
+
+    snapshot.transition(ScrSnapshot {
+        process,
+        port,
+        verification,
+        polled: true,
+    })
+}
+
+/// Finds the listener of `process` that answers like the web API. A port
+/// confirmed earlier is reused without probing while the same process keeps
+/// listening on it.
+fn confirm_api_port(
+    probe: &mut dyn ProcessProbe,
+    process: &ScrProcess,
+    snapshot: &ScrSnapshot,
+) -> Option<(u16, PortVerification)> {
+    let candidates = find_starcraft_listening_ports(probe, process.pid);
+
+    if snapshot.process.as_ref() == Some(process) {
+        if let (Some(port), Some(verification)) = (snapshot.port, &snapshot.verification) {
+            if candidates.contains(&port) {
+                return Some((port, verification.clone()));
+            }
+        }
+    }
+
+    candidates.iter().find_map(|&port| {
+        let status = probe.probe_web_api(port)?;
+        println!("[scr-events] Port {port} answered {WEB_API_PROBE_PATH} with {status}");
+        Some((
+            port,
+            PortVerification {
+                probe_path: WEB_API_PROBE_PATH.to_string(),
+                status,
+                candidates: candidates.clone(),
+            },
+        ))
+    })
+}
+
+enum Control {
+    Stop,
+    SetInterval(Duration),
+}
+
+struct Worker {
+    control: Sender<Control>,
+    thread: JoinHandle<()>,
+}
+
+type ProbeFactory = Box<dyn Fn() -> Box<dyn ProcessProbe> + Send + Sync>;
+type EventHandler = Arc<dyn Fn(ScrEvent) + Send + Sync>;
+
+/// Polls for StarCraft on a background thread and reports state transitions
+/// only. The thread can be stopped and started again; the snapshot is kept
+/// across restarts so nothing is re-announced.
 pub struct ScrProcessEventProvider {
-    _thread: std::thread::JoinHandle<()>,
+    snapshot: Arc<Mutex<ScrSnapshot>>,
+    make_probe: ProbeFactory,
+    event_handler: EventHandler,
+    interval: Mutex<Duration>,
+    worker: Mutex<Option<Worker>>,
 }
 
 impl ScrProcessEventProvider {
-    pub fn new(event_handler: Arc<Mutex<dyn FnMut(ScrEvent) + Send>>) -> ScrProcessEventProvider {
+    pub fn new(
+        make_probe: impl Fn() -> Box<dyn ProcessProbe> + Send + Sync + 'static,
+        event_handler: impl Fn(ScrEvent) + Send + Sync + 'static,
+    ) -> ScrProcessEventProvider {
         ScrProcessEventProvider {
-            _thread: std::thread::spawn(move || loop {
-                let mut system = sysinfo::System::new();
-                system.refresh_all();
+            snapshot: Arc::new(Mutex::new(ScrSnapshot::default())),
+            make_probe: Box::new(make_probe),
+            event_handler: Arc::new(event_handler),
+            interval: Mutex::new(DEFAULT_POLL_INTERVAL),
+            worker: Mutex::new(None),
+        }
+    }
+
+    pub fn snapshot(&self) -> ScrSnapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
 
-                std::thread::sleep(std::time::Duration::from_secs(1));
+    /// Starts polling; does nothing if already running.
+    pub fn start(&self) {
+        let mut worker = self.worker.lock().unwrap();
+        if worker.is_some() {
+            return;
+        }
+
+        let (control, commands) = mpsc::channel();
+        let mut probe = (self.make_probe)();
+        let snapshot = self.snapshot.clone();
+        let event_handler = self.event_handler.clone();
+        let mut interval = *self.interval.lock().unwrap();
+        // Only this thread changes the snapshot. It polls a copy, which can
+        // take a while, and only locks the shared one to publish the result.
+        let mut current = self.snapshot();
 
-                let pid = find_starcraft_process(&mut system);
+        let thread = std::thread::spawn(move || loop {
+            let events = poll(probe.as_mut(), &mut current);
+            *snapshot.lock().unwrap() = current.clone();
+            for event in events {
+                event_handler(event);
+            }
 
-                if let Some(pid) = pid {
-                    if let Some(port) = find_starcraft_api_port(&pid) {
-                        event_handler.lock().unwrap()(ScrEvent::WebServerRunning { port });
-                        continue;
-                    }
-                }
+            // Sleeping on the channel lets stop() interrupt the wait.
+            match commands.recv_timeout(interval) {
+                Ok(Control::SetInterval(new_interval)) => interval = new_interval,
+                Ok(Control::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        });
 
-                event_handler.lock().unwrap()(ScrEvent::WebServerDown);
-            }),
+        *worker = Some(Worker { control, thread });
+    }
+
+    /// Stops polling and waits for the thread to exit.
+    pub fn stop(&self) {
+        let Some(worker) = self.worker.lock().unwrap().take() else {
+            return;
+        };
+        let _ = worker.control.send(Control::Stop);
+        if worker.thread.join().is_err() {
+            println!("[scr-events] Process monitor thread panicked");
+        }
+    }
+
+    pub fn set_poll_interval(&self, interval: Duration) {
+        *self.interval.lock().unwrap() = interval;
+        if let Some(worker) = self.worker.lock().unwrap().as_ref() {
+            let _ = worker.control.send(Control::SetInterval(interval));
         }
     }
 }
 
-/// SCR events from multiple sources.
-#[derive(Debug, Serialize, Clone)]
+impl Drop for ScrProcessEventProvider {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// SCR events from multiple sources. Only emitted when the state changes.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all_fields = "camelCase")]
 pub enum ScrEvent {
-    WebServerRunning { port: u16 },
+    ProcessStarted {
+        pid: u32,
+        /// Seconds since the Unix epoch.
+        started_at: u64,
+        exe_path: Option<String>,
+    },
+    ProcessExited,
+    WebServerRunning {
+        port: u16,
+        verification: PortVerification,
+    },
     WebServerDown,
+    /// The followed account changed, or the game started or stopped. See
+    /// `session.rs`.
+    UserChanged {
+        user: Option<CurrentAccount>,
+    },
+    /// The followed account's ladder game began; fields describe the
+    /// opponent.
+    MatchStarted {
+        opponent: String,
+        map: String,
+        race: String,
+        gateway_id: u16,
+    },
+    MatchEnded,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scr_process::ListeningSocket;
+
+    #[derive(Default)]
+    struct World {
+        processes: Vec<ScrProcess>,
+        sockets: Vec<ListeningSocket>,
+        /// Ports that answer the web API probe.
+        api_ports: Vec<u16>,
+        probes: Vec<u16>,
+    }
+
+    /// Probe whose world is set directly by the test.
+    #[derive(Clone, Default)]
+    struct FakeProbe {
+        world: Arc<Mutex<World>>,
+    }
+
+    impl FakeProbe {
+        fn set(&self, processes: Vec<ScrProcess>, sockets: Vec<(u32, u16)>, api_ports: Vec<u16>) {
+            let mut world = self.world.lock().unwrap();
+            world.processes = processes;
+            world.sockets = sockets
+                .into_iter()
+                .map(|(pid, port)| ListeningSocket { pid, port })
+                .collect();
+            world.api_ports = api_ports;
+        }
+
+        fn take_probes(&self) -> Vec<u16> {
+            std::mem::take(&mut self.world.lock().unwrap().probes)
+        }
+    }
+
+    impl ProcessProbe for FakeProbe {
+        fn processes(&mut self) -> Vec<ScrProcess> {
+            self.world.lock().unwrap().processes.clone()
+        }
+
+        fn listening_sockets(&mut self) -> Vec<ListeningSocket> {
+            self.world.lock().unwrap().sockets.clone()
+        }
+
+        fn probe_web_api(&mut self, port: u16) -> Option<u16> {
+            let mut world = self.world.lock().unwrap();
+            world.probes.push(port);
+            world.api_ports.contains(&port).then_some(200)
+        }
+    }
+
+    fn process(pid: u32, name: &str) -> ScrProcess {
+        ScrProcess {
+            pid,
+            name: name.to_string(),
+            started_at: 1_700_000_000 + pid as u64,
+            exe_path: Some(format!("C:\\Games\\{name}")),
+        }
+    }
+
+    fn starcraft(pid: u32) -> ScrProcess {
+        process(pid, "StarCraft.exe")
+    }
+
+    fn started(pid: u32) -> ScrEvent {
+        let p = starcraft(pid);
+        ScrEvent::ProcessStarted {
+            pid,
+            started_at: p.started_at,
+            exe_path: p.exe_path,
+        }
+    }
+
+    fn running(port: u16, candidates: &[u16]) -> ScrEvent {
+        ScrEvent::WebServerRunning {
+            port,
+            verification: PortVerification {
+                probe_path: WEB_API_PROBE_PATH.to_string(),
+                status: 200,
+                candidates: candidates.to_vec(),
+            },
+        }
+    }
+
+    #[test]
+    fn reports_only_transitions() {
+        let mut probe = FakeProbe::default();
+        let mut snapshot = ScrSnapshot::default();
+
+        probe.set(vec![process(1, "explorer.exe")], vec![], vec![]);
+        assert_eq!(
+            poll(&mut probe, &mut snapshot),
+            vec![ScrEvent::WebServerDown]
+        );
+        assert_eq!(poll(&mut probe, &mut snapshot), vec![]);
+
+        probe.set(
+            vec![process(1, "explorer.exe"), starcraft(42)],
+            vec![(1, 80)],
+            vec![80],
+        );
+        assert_eq!(poll(&mut probe, &mut snapshot), vec![started(42)]);
+
+        probe.set(vec![starcraft(42)], vec![(42, 57421)], vec![57421]);
+        assert_eq!(
+            poll(&mut probe, &mut snapshot),
+            vec![running(57421, &[57421])]
+        );
+        assert_eq!(poll(&mut probe, &mut snapshot), vec![]);
+
+        probe.set(vec![], vec![], vec![]);
+        assert_eq!(
+            poll(&mut probe, &mut snapshot),
+            vec![ScrEvent::WebServerDown, ScrEvent::ProcessExited]
+        );
+        assert_eq!(poll(&mut probe, &mut snapshot), vec![]);
+    }
+
+    #[test]
+    fn restarted_process_is_reported_even_on_the_same_port() {
+        let mut probe = FakeProbe::default();
+        let mut snapshot = ScrSnapshot::default();
+
+        probe.set(vec![starcraft(42)], vec![(42, 57421)], vec![57421]);
+        assert_eq!(
+            poll(&mut probe, &mut snapshot),
+            vec![started(42), running(57421, &[57421])]
+        );
+
+        probe.set(vec![starcraft(43)], vec![(43, 57421)], vec![57421]);
+        assert_eq!(
+            poll(&mut probe, &mut snapshot),
+            vec![
+                ScrEvent::WebServerDown,
+                ScrEvent::ProcessExited,
+                started(43),
+                running(57421, &[57421]),
+            ]
+        );
+    }
+
+    #[test]
+    fn picks_the_listener_that_answers_the_probe() {
+        let mut probe = FakeProbe::default();
+        let mut snapshot = ScrSnapshot::default();
+
+        // Not ready yet: nothing answers.
+        probe.set(vec![starcraft(42)], vec![(42, 6112), (42, 57421)], vec![]);
+        assert_eq!(
+            poll(&mut probe, &mut snapshot),
+            vec![ScrEvent::WebServerDown, started(42)]
+        );
+        assert_eq!(probe.take_probes(), vec![6112, 57421]);
+
+        probe.set(
+            vec![starcraft(42)],
+            vec![(42, 6112), (42, 57421), (42, 57421), (7, 1234)],
+            vec![57421],
+        );
+        assert_eq!(
+            poll(&mut probe, &mut snapshot),
+            vec![running(57421, &[6112, 57421])]
+        );
+        assert_eq!(probe.take_probes(), vec![6112, 57421]);
+
+        // Confirmed ports are cached while the process keeps listening.
+        assert_eq!(poll(&mut probe, &mut snapshot), vec![]);
+        assert_eq!(probe.take_probes(), Vec::<u16>::new());
+
+        // ...and probed again once it stops.
+        probe.set(vec![starcraft(42)], vec![(42, 6112)], vec![]);
+        assert_eq!(
+            poll(&mut probe, &mut snapshot),
+            vec![ScrEvent::WebServerDown]
+        );
+        assert_eq!(probe.take_probes(), vec![6112]);
+    }
+
+    #[test]
+    fn provider_stops_and_restarts_without_repeating_events() {
+        let probe = FakeProbe::default();
+        probe.set(vec![starcraft(42)], vec![(42, 57421)], vec![57421]);
+        let (tx, events) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let factory_probe = probe.clone();
+        let provider = ScrProcessEventProvider::new(
+            move || Box::new(factory_probe.clone()),
+            move |event| tx.lock().unwrap().send(event).unwrap(),
+        );
+        provider.set_poll_interval(Duration::from_millis(5));
+
+        provider.start();
+        let timeout = Duration::from_secs(5);
+        assert_eq!(events.recv_timeout(timeout).unwrap(), started(42));
+        assert_eq!(
+            events.recv_timeout(timeout).unwrap(),
+            running(57421, &[57421])
+        );
+
+        provider.stop();
+        probe.set(vec![], vec![], vec![]);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(events.try_recv().is_err());
+
+        provider.start();
+        assert_eq!(
+            events.recv_timeout(timeout).unwrap(),
+            ScrEvent::WebServerDown
+        );
+        assert_eq!(
+            events.recv_timeout(timeout).unwrap(),
+            ScrEvent::ProcessExited
+        );
+        provider.stop();
+        assert!(provider.snapshot().process.is_none());
+    }
 }
@@ -1,35 +1,153 @@
-use netstat::TcpState;
-use sysinfo::{Pid, System};
+use std::time::Duration;
 
-fn tcp_state_eq(state: &TcpState, other: &TcpState) -> bool {
-    state.to_string() == other.to_string()
+use serde::Serialize;
+
+use crate::http::HttpClient;
+
+#[cfg(not(target_os = "linux"))]
+pub use system::SystemProbe;
+
+pub const STARCRAFT_EXE: &str = "StarCraft.exe";
+/// Cheap endpoint used to confirm a port belongs to the StarCraft web API.
+pub const WEB_API_PROBE_PATH: &str = "/web-api/v1/gateway";
+const WEB_API_PROBE_TIMEOUT: Duration = Duration::from_millis(750);
+
+/// A running process as seen by a [`ProcessProbe`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrProcess {
+    pub pid: u32,
+    pub name: String,
+    /// Seconds since the Unix epoch.
+    pub started_at: u64,
+    pub exe_path: Option<String>,
 }
 
-pub fn find_starcraft_process(system: &mut System) -> Option<Pid> {
-    system.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
-    let mut starcraft_pid = system.processes_by_exact_name("StarCraft.exe".as_ref());
+/// A TCP socket in the listening state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListeningSocket {
+    pub pid: u32,
+    pub port: u16,
+}
 
-    starcraft_pid.next().map(|process| process.pid())
+/// What the process monitor needs from the OS, so detection can run against
+/// a fake in tests. Linux uses `procfs::ProcfsProbe`, other platforms
+/// `SystemProbe`.
+pub trait ProcessProbe: Send {
+    fn processes(&mut self) -> Vec<ScrProcess>;
+    fn listening_sockets(&mut self) -> Vec<ListeningSocket>;
+    /// Requests [`WEB_API_PROBE_PATH`] on localhost:`port`, returning the
+    /// status code if the answer looks like the StarCraft web API.
+    fn probe_web_api(&mut self, port: u16) -> Option<u16>;
 }
 
-pub fn find_starcraft_api_port(pid: &Pid) -> Option<u16> {
-    let af_flags = netstat::AddressFamilyFlags::IPV4 | netstat::AddressFamilyFlags::IPV6;
-    let proto_flags = netstat::ProtocolFlags::TCP | netstat::ProtocolFlags::UDP;
-    let sockets_info = netstat::get_sockets_info(af_flags, proto_flags);
-
-    sockets_info
-        .ok()?
-        .iter()
-        .find_map(|si| match &si.protocol_socket_info {
-            netstat::ProtocolSocketInfo::Tcp(tcp_si) => {
-                if si.associated_pids.contains(&pid.as_u32())
-                    && tcp_state_eq(&tcp_si.state, &TcpState::Listen)
-                {
-                    Some(tcp_si.local_port)
-                } else {
-                    None
-                }
+/// Shared [`ProcessProbe::probe_web_api`] implementation for the real probes.
+pub fn probe_web_api(http: &HttpClient, port: u16) -> Option<u16> {
+    let url = format!("http://localhost:{port}{WEB_API_PROBE_PATH}");
+    let (status, body) =
+        tauri::async_runtime::block_on(http.probe(&url, WEB_API_PROBE_TIMEOUT)).ok()?;
+    // Other listeners may speak HTTP too; the web API answers with JSON.
+    let is_json = serde_json::from_str::<serde_json::Value>(&body).is_ok();
+    ((200..300).contains(&status) && is_json).then_some(status)
+}
+
+#[cfg(not(target_os = "linux"))]
+mod system {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use netstat::TcpState;
+    use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+    fn tcp_state_eq(state: &TcpState, other: &TcpState) -> bool {
+        state.to_string() == other.to_string()
+    }
+
+    /// [`ProcessProbe`] backed by `sysinfo`, `netstat` and the shared HTTP client.
+    pub struct SystemProbe {
+        system: System,
+        http: Arc<HttpClient>,
+    }
+
+    impl SystemProbe {
+        pub fn new(http: Arc<HttpClient>) -> Self {
+            Self {
+                system: System::new(),
+                http,
             }
-            _ => None,
-        })
+        }
+    }
+
+    impl ProcessProbe for SystemProbe {
+        fn processes(&mut self) -> Vec<ScrProcess> {
+            self.system.refresh_processes_specifics(
+                ProcessesToUpdate::All,
+                true,
+                ProcessRefreshKind::nothing().with_exe(UpdateKind::OnlyIfNotSet),
+            );
+
+            self.system
+                .processes()
+                .values()
+                .map(|process| ScrProcess {
+                    pid: process.pid().as_u32(),
+                    name: process.name().to_string_lossy().to_string(),
+                    started_at: process.start_time(),
+                    exe_path: process.exe().map(|p| p.to_string_lossy().to_string()),
+                })
+                .collect()
+        }
+
+        fn listening_sockets(&mut self) -> Vec<ListeningSocket> {
+            let af_flags = netstat::AddressFamilyFlags::IPV4 | netstat::AddressFamilyFlags::IPV6;
+            let proto_flags = netstat::ProtocolFlags::TCP;
+            let Ok(sockets_info) = netstat::get_sockets_info(af_flags, proto_flags) else {
+                return Vec::new();
+            };
+
+            sockets_info
+                .iter()
+                .filter_map(|si| match &si.protocol_socket_info {
+                    netstat::ProtocolSocketInfo::Tcp(tcp_si)
+                        if tcp_state_eq(&tcp_si.state, &TcpState::Listen) =>
+                    {
+                        Some((si, tcp_si.local_port))
+                    }
+                    _ => None,
+                })
+                .flat_map(|(si, port)| {
+                    si.associated_pids
+                        .iter()
+                        .map(move |&pid| ListeningSocket { pid, port })
+                })
+                .collect()
+        }
+
+        fn probe_web_api(&mut self, port: u16) -> Option<u16> {
+            probe_web_api(&self.http, port)
+        }
+    }
+}
+
+pub fn find_starcraft_process(probe: &mut dyn ProcessProbe) -> Option<ScrProcess> {
+    probe
+        .processes()
+        .into_iter()
+        .filter(|p| p.name.eq_ignore_ascii_case(STARCRAFT_EXE))
+        .min_by_key(|p| (p.started_at, p.pid))
+}
+
+/// Every TCP port `pid` listens on, ascending. StarCraft opens several, so
+/// these are only candidates for the web API.
+pub fn find_starcraft_listening_ports(probe: &mut dyn ProcessProbe, pid: u32) -> Vec<u16> {
+    let mut ports: Vec<u16> = probe
+        .listening_sockets()
+        .into_iter()
+        .filter(|s| s.pid == pid)
+        .map(|s| s.port)
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
 }
@@ -0,0 +1,389 @@
+//! Tracks whether the account picked in settings is in a ladder game, by
+//! polling the local web API while the client runs. The API has no endpoint
+//! naming the signed-in account, so nothing is followed until one is
+//! picked. Changes are reported on the `scr-event` channel alongside the
+//! process events.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+
+use crate::{
+    sc_api::{
+        CurrentAccount, Leaderboards, MatchHistoryRequest, MatchmakerGame, ProfileFlags,
+        ScApiClient, ToonRequest,
+    },
+    scr_events::ScrEvent,
+};
+
+pub const SESSION_POLL_INTERVAL: Duration = Duration::from_secs(3);
+/// Gamemode id of 1v1 ladder games.
+pub const LADDER_1V1: u32 = 1;
+/// How many recent games are checked for one still in progress.
+const RECENT_GAMES: u32 = 5;
+
+/// A ladder game the followed account is currently playing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveMatch {
+    pub match_guid: String,
+    pub opponent: String,
+    pub map: String,
+    /// The opponent's race.
+    pub race: String,
+    /// The opponent's gateway.
+    pub gateway_id: u16,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    /// The followed account, while the game runs.
+    pub user: Option<CurrentAccount>,
+    pub active_match: Option<ActiveMatch>,
+}
+
+impl Session {
+    /// Moves to `next`, returning the events describing what changed.
+    fn transition(&mut self, next: Session) -> Vec<ScrEvent> {
+        let mut events = Vec::new();
+        let match_changed = self.active_match.as_ref().map(|m| &m.match_guid)
+            != next.active_match.as_ref().map(|m| &m.match_guid);
+
+        if match_changed && self.active_match.is_some() {
+            events.push(ScrEvent::MatchEnded);
+        }
+        if self.user != next.user {
+            events.push(ScrEvent::UserChanged {
+                user: next.user.clone(),
+            });
+        }
+        if let (true, Some(active)) = (match_changed, &next.active_match) {
+            events.push(ScrEvent::MatchStarted {
+                opponent: active.opponent.clone(),
+                map: active.map.clone(),
+                race: active.race.clone(),
+                gateway_id: active.gateway_id,
+            });
+        }
+
+        *self = next;
+        events
+    }
+}
+
+/// Latest 1v1 season, from the leaderboard index.
+pub fn current_season(leaderboards: &Leaderboards) -> Option<u32> {
+    leaderboards
+        .leaderboards
+        .values()
+        .filter(|l| l.gamemode_id == LADDER_1V1)
+        .map(|l| l.season_id)
+        .max()
+}
+
+/// The newest game in `games` if `user` is in it and it has no result yet.
+fn find_active_match(games: &[MatchmakerGame], user: &CurrentAccount) -> Option<ActiveMatch> {
+    let newest = games
+        .iter()
+        .max_by_key(|g| g.match_created.parse::<u64>().unwrap_or_default())?;
+
+    let is_user = |toon: &str, gateway: u16| {
+        toon.eq_ignore_ascii_case(&user.toon) && gateway == user.gateway_id
+    };
+    let me = newest
+        .players
+        .iter()
+        .find(|p| is_user(&p.toon, p.gateway_id))?;
+    let undecided = me.result.is_empty() || me.result.eq_ignore_ascii_case("undecided");
+    if !undecided {
+        return None;
+    }
+    let opponent = newest
+        .players
+        .iter()
+        .find(|p| !is_user(&p.toon, p.gateway_id))?;
+
+    Some(ActiveMatch {
+        match_guid: newest.match_guid.clone(),
+        opponent: opponent.toon.clone(),
+        map: newest.map.clone(),
+        race: opponent.race.clone(),
+        gateway_id: opponent.gateway_id,
+    })
+}
+
+/// Polls the web API for the followed account's in-progress game.
+pub struct SessionMonitor {
+    api: Arc<ScApiClient>,
+    session: Mutex<Session>,
+    /// Toon picked in settings; `None` follows nobody.
+    account: Mutex<Option<ToonRequest>>,
+    /// The followed account and its 1v1 season, looked up once per toon.
+    user: Mutex<Option<(CurrentAccount, u32)>>,
+    last_error: Mutex<Option<String>>,
+    on_event: Box<dyn Fn(ScrEvent) + Send + Sync>,
+}
+
+impl SessionMonitor {
+    pub fn new(
+        api: Arc<ScApiClient>,
+        on_event: impl Fn(ScrEvent) + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            api,
+            session: Mutex::new(Session::default()),
+            account: Mutex::new(None),
+            user: Mutex::new(None),
+            last_error: Mutex::new(None),
+            on_event: Box::new(on_event),
+        })
+    }
+
+    pub fn session(&self) -> Session {
+        self.session.lock().unwrap().clone()
+    }
+
+    /// Follows `account` from the next poll on.
+    pub fn set_account(&self, account: Option<ToonRequest>) {
+        *self.account.lock().unwrap() = account;
+    }
+
+    /// Polls every `interval` until the returned task is aborted.
+    pub fn start(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let monitor = self.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let result = monitor.poll().await;
+                monitor.log_error(result.err());
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// Logs a poll failure once rather than on every poll until it clears.
+    fn log_error(&self, error: Option<String>) {
+        let mut last_error = self.last_error.lock().unwrap();
+        if let Some(e) = error.as_ref().filter(|e| last_error.as_ref() != Some(*e)) {
+            println!("[session] Failed to poll the web API: {e}");
+        }
+        *last_error = error;
+    }
+
+    /// Fetches the current state once and emits whatever changed. Transient
+    /// API failures leave the session as it was.
+    pub async fn poll(&self) -> Result<(), String> {
+        let account = self.account.lock().unwrap().clone();
+        let next = match account {
+            // The game isn't running; there is no session to report.
+            _ if !self.api.is_available() => Session::default(),
+            None => Session::default(),
+            Some(account) => match self.follow(&account).await {
+                Ok(session) => session,
+                Err(_) if !self.api.is_available() => Session::default(),
+                Err(e) => return Err(e),
+            },
+        };
+
+        let events = self.session.lock().unwrap().transition(next);
+        for event in events {
+            (self.on_event)(event);
+        }
+        Ok(())
+    }
+
+    async fn follow(&self, account: &ToonRequest) -> Result<Session, String> {
+        let (user, season) = self.user(account).await?;
+        let games = self
+            .api
+            .match_history(&MatchHistoryRequest {
+                toon: user.toon.clone(),
+                gateway: user.gateway_id,
+                gamemode: LADDER_1V1,
+                season,
+                offset: 0,
+                limit: RECENT_GAMES,
+            })
+            .await?;
+        Ok(Session {
+            active_match: find_active_match(&games, &user),
+            user: Some(user),
+        })
+    }
+
+    async fn user(&self, account: &ToonRequest) -> Result<(CurrentAccount, u32), String> {
+        let cached = self
+            .user
+            .lock()
+            .unwrap()
+            .clone()
+            .filter(|(u, _)| u.toon == account.toon && u.gateway_id == account.gateway);
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+
+        let profile = self
+            .api
+            .aurora_profile(account, ProfileFlags::Profile)
+            .await?;
+        let leaderboards = self.api.leaderboards().await?;
+        let season = current_season(&leaderboards)
+            .ok_or("No 1v1 leaderboard found to determine the season".to_string())?;
+        let user = CurrentAccount {
+            aurora_id: profile.aurora_id,
+            battle_tag: profile.battle_tag,
+            toon: account.toon.clone(),
+            gateway_id: account.gateway,
+        };
+        *self.user.lock().unwrap() = Some((user.clone(), season));
+        Ok((user, season))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{api_client, TestResponse, TestServer};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn flash() -> CurrentAccount {
+        CurrentAccount {
+            aurora_id: 1,
+            battle_tag: "Flash#1".to_string(),
+            toon: "Flash".to_string(),
+            gateway_id: 30,
+        }
+    }
+
+    fn active(guid: &str) -> ActiveMatch {
+        ActiveMatch {
+            match_guid: guid.to_string(),
+            opponent: "Jaedong".to_string(),
+            map: "Polypoid".to_string(),
+            race: "Zerg".to_string(),
+            gateway_id: 10,
+        }
+    }
+
+    fn started() -> ScrEvent {
+        ScrEvent::MatchStarted {
+            opponent: "Jaedong".to_string(),
+            map: "Polypoid".to_string(),
+            race: "Zerg".to_string(),
+            gateway_id: 10,
+        }
+    }
+
+    #[test]
+    fn session_transitions() {
+        let mut session = Session::default();
+        let signed_in = || Session {
+            user: Some(flash()),
+            active_match: None,
+        };
+        let playing = |guid: &str| Session {
+            user: Some(flash()),
+            active_match: Some(active(guid)),
+        };
+
+        assert_eq!(
+            session.transition(signed_in()),
+            vec![ScrEvent::UserChanged {
+                user: Some(flash())
+            }]
+        );
+        assert_eq!(session.transition(signed_in()), vec![]);
+        assert_eq!(session.transition(playing("a")), vec![started()]);
+        assert_eq!(session.transition(playing("a")), vec![]);
+        // Straight into the next game.
+        assert_eq!(
+            session.transition(playing("b")),
+            vec![ScrEvent::MatchEnded, started()]
+        );
+        assert_eq!(
+            session.transition(Session::default()),
+            vec![ScrEvent::MatchEnded, ScrEvent::UserChanged { user: None }]
+        );
+    }
+
+    #[test]
+    fn polls_the_picked_accounts_recent_games() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let game_over = finished.clone();
+        let server = TestServer::start(move |req| {
+            let path = req.path.as_str();
+            let body = if path.starts_with("/web-api/v2/aurora-profile-by-toon/Flash/30?") {
+                r#"{"aurora_id": 1, "battle_tag": "Flash#1"}"#.to_string()
+            } else if path == "/web-api/v1/leaderboard" {
+                r#"{"leaderboards": {
+                    "1": {"id": 1, "gamemode_id": 1, "season_id": 16},
+                    "2": {"id": 2, "gamemode_id": 1, "season_id": 17},
+                    "3": {"id": 3, "gamemode_id": 2, "season_id": 99}
+                }}"#
+                .to_string()
+            } else if path.starts_with("/web-api/v1/matchmaker-gameinfo-by-toon/Flash/30/1/17?") {
+                let result = if game_over.load(Ordering::SeqCst) {
+                    "win"
+                } else {
+                    ""
+                };
+                format!(
+                    r#"[
+                        {{"match_guid": "old", "match_created": "100", "map": "Eclipse",
+                          "players": [{{"toon": "Flash", "gateway_id": 30, "result": "loss"}},
+                                      {{"toon": "Bisu", "gateway_id": 30, "race": "Protoss", "result": "win"}}]}},
+                        {{"match_guid": "new", "match_created": "200", "map": "Polypoid",
+                          "players": [{{"toon": "Flash", "gateway_id": 30, "result": "{result}"}},
+                                      {{"toon": "Jaedong", "gateway_id": 10, "race": "Zerg", "result": ""}}]}}
+                    ]"#
+                )
+            } else {
+                return TestResponse::status(404);
+            };
+            TestResponse::ok(body.into_bytes())
+        });
+
+        let api = api_client(&server);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let monitor = SessionMonitor::new(api, move |e| sink.lock().unwrap().push(e));
+
+        // Nothing is followed until an account is picked.
+        tauri::async_runtime::block_on(monitor.poll()).unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        monitor.set_account(Some(ToonRequest {
+            toon: "Flash".to_string(),
+            gateway: 30,
+        }));
+        tauri::async_runtime::block_on(monitor.poll()).unwrap();
+        assert_eq!(
+            std::mem::take(&mut *events.lock().unwrap()),
+            vec![
+                ScrEvent::UserChanged {
+                    user: Some(flash())
+                },
+                started()
+            ]
+        );
+        assert_eq!(monitor.session().active_match, Some(active("new")));
+
+        finished.store(true, Ordering::SeqCst);
+        tauri::async_runtime::block_on(monitor.poll()).unwrap();
+        assert_eq!(
+            std::mem::take(&mut *events.lock().unwrap()),
+            vec![ScrEvent::MatchEnded]
+        );
+
+        monitor.set_account(None);
+        tauri::async_runtime::block_on(monitor.poll()).unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![ScrEvent::UserChanged { user: None }]
+        );
+    }
+}
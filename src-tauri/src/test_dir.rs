@@ -0,0 +1,37 @@
+//! Scratch directories for tests, removed again when dropped, so a failing
+//! assertion doesn't leave them behind.
+
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    /// An empty directory under the system temp dir, unique to `name` and
+    /// this process.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("cwal-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,212 @@
+//! Tiny HTTP/1.1 stand-in server for tests that exercise the backend's
+//! outbound requests without touching the network. Also backs the mock web
+//! API (`mock_sc_api.rs`), and is public under the `mock` feature like it,
+//! so the helpers only tests use aren't reported as unused there.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    http::{HttpClient, HttpClientConfig},
+    rate_limit::{RateLimitConfig, RateLimiter},
+    sc_api::ScApiClient,
+};
+
+#[derive(Debug, Clone)]
+pub struct TestRequest {
+    pub path: String,
+    /// Header names are lowercased.
+    pub headers: HashMap<String, String>,
+}
+
+impl TestRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TestResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Advertise the full body length but close the socket after this many bytes.
+    pub truncate_at: Option<usize>,
+    /// Close the connection without writing anything.
+    pub drop: bool,
+    /// Wait this long before answering.
+    pub delay: Option<Duration>,
+}
+
+impl TestResponse {
+    pub fn ok(body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            body,
+            ..Default::default()
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            ..Default::default()
+        }
+    }
+
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn truncated(mut self, at: usize) -> Self {
+        self.truncate_at = Some(at);
+        self
+    }
+
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    pub fn drop_connection() -> Self {
+        Self {
+            drop: true,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct TestServer {
+    addr: SocketAddr,
+}
+
+impl TestServer {
+    /// Serves every connection on a background thread until the test process exits.
+    pub fn start<F>(handler: F) -> TestServer
+    where
+        F: Fn(&TestRequest) -> TestResponse + Send + Sync + 'static,
+    {
+        Self::bind(0, handler).unwrap()
+    }
+
+    /// Like [`TestServer::start`] on a fixed localhost port; 0 picks a free one.
+    pub fn bind<F>(port: u16, handler: F) -> std::io::Result<TestServer>
+    where
+        F: Fn(&TestRequest) -> TestResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let addr = listener.local_addr()?;
+        let handler = Arc::new(handler);
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handler = handler.clone();
+                std::thread::spawn(move || {
+                    let _ = serve(stream, handler.as_ref());
+                });
+            }
+        });
+
+        Ok(TestServer { addr })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+}
+
+/// A limiter that never makes a request wait.
+pub fn unlimited() -> Arc<RateLimiter> {
+    let config = RateLimitConfig {
+        local_api_tps: 0.0,
+        remote_tps: 0.0,
+    };
+    Arc::new(RateLimiter::new(config, |_| {}))
+}
+
+/// An HTTP client that neither retries nor paces requests, so a test sees
+/// each response as served.
+pub fn http_client() -> Arc<HttpClient> {
+    let config = HttpClientConfig {
+        max_retries: 0,
+        ..HttpClientConfig::default()
+    };
+    Arc::new(HttpClient::new(config, unlimited()).unwrap())
+}
+
+/// A web API client talking to `server`, built on [`http_client`].
+pub fn api_client(server: &TestServer) -> Arc<ScApiClient> {
+    let port = server.port();
+    Arc::new(ScApiClient::new(http_client(), move || Some(port)))
+}
+
+fn serve(
+    mut stream: TcpStream,
+    handler: &(dyn Fn(&TestRequest) -> TestResponse + Send + Sync),
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let path = parts.nth(1).unwrap_or_default().to_string();
+
+    let headers = read_headers(&mut reader)?;
+
+    let response = handler(&TestRequest { path, headers });
+    if let Some(delay) = response.delay {
+        std::thread::sleep(delay);
+    }
+    if response.drop {
+        return Ok(());
+    }
+
+    let mut head = format!("HTTP/1.1 {} X\r\n", response.status);
+    if !response
+        .headers
+        .iter()
+        .any(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+    {
+        head.push_str(&format!("Content-Length: {}\r\n", response.body.len()));
+    }
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+
+    stream.write_all(head.as_bytes())?;
+    let end = response
+        .truncate_at
+        .unwrap_or(response.body.len())
+        .min(response.body.len());
+    stream.write_all(&response.body[..end])?;
+    stream.flush()
+}
+
+fn read_headers(reader: &mut impl BufRead) -> std::io::Result<HashMap<String, String>> {
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            return Ok(headers);
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+}
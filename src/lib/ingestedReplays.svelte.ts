@@ -0,0 +1,39 @@
+import { listen } from "@tauri-apps/api/event";
+import type { Event } from "@tauri-apps/api/event";
+import { toast } from "svelte-sonner";
+
+export type IngestedReplay = {
+  sourcePath: string;
+  path: string; // the templated copy
+  mapName: string;
+  matchup: string;
+  players: { name: string; race: string }[];
+  winners: string[];
+  durationMs: number;
+  startTimeMs: number;
+};
+
+const MAX_RECENT = 20;
+
+// Replays the backend picked up from the StarCraft replay folder, newest first.
+const ingestedReplays: { recent: IngestedReplay[] } = $state({ recent: [] });
+
+export const getIngestedReplays = () => ingestedReplays;
+
+export const configureReceiveIngestedReplays = async () => {
+  return await listen("replay-ingested", (ev: Event<IngestedReplay>) => {
+    const replay = ev.payload;
+    ingestedReplays.recent = [replay, ...ingestedReplays.recent].slice(
+      0,
+      MAX_RECENT,
+    );
+
+    const players = replay.players.map((p) => p.name).join(" vs ");
+    const winner = replay.winners.length
+      ? ` — ${replay.winners.join(", ")} won`
+      : "";
+    toast.success(`Replay saved: ${players} on ${replay.mapName}${winner}`, {
+      description: replay.path,
+    });
+  });
+};
@@ -0,0 +1,117 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export type RatingPoint = {
+  takenAtMs: number;
+  points: number;
+  wins: number;
+  losses: number;
+  // Set when the sample came from a leaderboard snapshot.
+  leaderboardId: number | null;
+  rank: number | null;
+};
+
+export type RankedToon = {
+  toon: string;
+  gateway: number;
+  rank: number;
+  points: number;
+};
+
+// Null ranks and points mean the toon wasn't on the board at that time.
+export type RankMovement = {
+  toon: string;
+  gateway: number;
+  fromRank: number | null;
+  toRank: number | null;
+  fromPoints: number | null;
+  toPoints: number | null;
+};
+
+export type RankMovements = {
+  fromTakenAtMs: number;
+  toTakenAtMs: number;
+  movements: RankMovement[];
+};
+
+export type TopChurn = {
+  fromTakenAtMs: number;
+  toTakenAtMs: number;
+  entered: RankedToon[];
+  left: RankedToon[];
+  stayed: number;
+};
+
+export type SnapshotSummary = {
+  takenAtMs: number;
+  leaderboards: number;
+  entries: number;
+  ratings: number;
+};
+
+export const getRatingHistory = (
+  toon: string,
+  gateway: number,
+  afterMs: number | null = null,
+  beforeMs: number | null = null,
+) =>
+  invoke<RatingPoint[]>("rating_history", {
+    toon,
+    gateway,
+    afterMs,
+    beforeMs,
+  });
+
+// Compares the first snapshot at or after fromMs with the last one at or
+// before toMs; null when there are none in between.
+export const getRankMovements = (
+  leaderboardId: number,
+  fromMs: number,
+  toMs: number,
+) =>
+  invoke<RankMovements | null>("rank_movements", {
+    leaderboardId,
+    fromMs,
+    toMs,
+  });
+
+export const getTopChurn = (
+  leaderboardId: number,
+  n: number,
+  fromMs: number,
+  toMs: number,
+) =>
+  invoke<TopChurn | null>("top_churn", { leaderboardId, n, fromMs, toMs });
+
+export const takeLadderSnapshot = () =>
+  invoke<SnapshotSummary>("take_ladder_snapshot");
+
+// A name an account was seen using, as the web API reported it. The replay
+// period covers replays under the name credited to the account, and is null
+// when there are none.
+export type ToonAlias = {
+  auroraId: number;
+  toon: string;
+  gateway: number;
+  firstSeenMs: number;
+  lastSeenMs: number;
+  replayFirstSeenMs: number | null;
+  replayLastSeenMs: number | null;
+};
+
+export type ResolvedToon = {
+  auroraId: number;
+  // The name the account was seen using last.
+  current: ToonAlias;
+  aliases: ToonAlias[];
+};
+
+export const getToonAliases = (auroraId: number) =>
+  invoke<ToonAlias[]>("toon_aliases", { auroraId });
+
+// Pass a replay's start time as atMs to tell apart accounts that used the
+// same name at different times.
+export const resolveToon = (
+  toon: string,
+  gateway: number | null = null,
+  atMs: number | null = null,
+) => invoke<ResolvedToon | null>("resolve_toon", { toon, gateway, atMs });
@@ -1,3 +1,30 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+
+interface RateLimitCounters {
+  localApiRequests: number;
+  remoteRequests: number;
+}
+
 let limitsStore = $state({ numApiRequests: 0, numReplayDownloads: 0 });
 
+// Counted by the backend rate limiter, which sees every outbound request.
+const applyCounters = (counters: RateLimitCounters) => {
+  limitsStore.numApiRequests = counters.localApiRequests;
+  limitsStore.numReplayDownloads = counters.remoteRequests;
+};
+
+const fetchCounters = () =>
+  invoke<RateLimitCounters>("get_rate_limit_counters")
+    .then(applyCounters)
+    .catch((error) => console.error("Failed to get request counters:", error));
+
+// The backend emits the counters at most once a second, so the last requests
+// of a burst only show up through polling.
+fetchCounters();
+setInterval(fetchCounters, 5000);
+listen<RateLimitCounters>("rate-limit-counters", (event) =>
+  applyCounters(event.payload),
+);
+
 export const getLimitsStore = () => limitsStore;
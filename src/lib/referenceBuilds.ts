@@ -0,0 +1,58 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export type ReferenceStep = {
+  // Name as in build orders, e.g. "Spawning Pool".
+  item: string;
+  targetSupply?: number;
+  targetTimeMs?: number;
+};
+
+export type ReferenceBuild = {
+  // Omitted when saving a new build.
+  id?: number;
+  name: string;
+  race: string;
+  notes: string;
+  steps: ReferenceStep[];
+};
+
+export type BuildStep = {
+  frame: number;
+  timeMs: number;
+  supply: number;
+  kind: "Unit" | "Building" | "Upgrade" | "Tech";
+  name: string;
+};
+
+export type StepComparison = {
+  reference: ReferenceStep;
+  actual: BuildStep | null;
+  // Positive when late.
+  timeDeltaMs: number | null;
+  supplyDelta: number | null;
+  score: number;
+};
+
+export type BuildComparison = {
+  steps: StepComparison[];
+  missing: ReferenceStep[];
+  extra: BuildStep[];
+  // 0 to 1.
+  adherence: number;
+};
+
+export const listReferenceBuilds = () =>
+  invoke<ReferenceBuild[]>("reference_builds_list");
+
+export const saveReferenceBuild = (build: ReferenceBuild) =>
+  invoke<ReferenceBuild>("reference_build_save", { build });
+
+export const deleteReferenceBuild = (id: number) =>
+  invoke<void>("reference_build_delete", { id });
+
+// Compares `player`'s build in library game `gameId` with the reference.
+export const compareBuild = (
+  referenceId: number,
+  gameId: number,
+  player: string,
+) => invoke<BuildComparison>("compare_build", { referenceId, gameId, player });
@@ -0,0 +1,140 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export type Outcome = "Win" | "Loss" | "Unknown";
+
+export type LibraryPlayer = {
+  name: string;
+  race: string;
+  side: number;
+  outcome: Outcome;
+  apm: number;
+  opening: string | null;
+  // Share of the opening's steps the build followed, 0.5 to 1.
+  openingConfidence: number | null;
+};
+
+export type LibraryReplay = {
+  id: number;
+  fingerprint: string;
+  mapName: string;
+  matchup: string;
+  startTimeMs: number;
+  durationMs: number;
+  players: LibraryPlayer[];
+  paths: string[];
+};
+
+export type RescanSummary = {
+  added: number;
+  updated: number;
+  unchanged: number;
+  removed: number;
+  failed: number;
+};
+
+export const listLibrary = (offset: number, limit: number) =>
+  invoke<LibraryReplay[]>("library_list", { offset, limit });
+
+export const countLibrary = () => invoke<number>("library_count");
+
+export const getLibraryReplay = (id: number) =>
+  invoke<LibraryReplay | null>("library_get", { id });
+
+// The backend always includes its replay cache.
+export const rescanLibrary = (paths: string[]) =>
+  invoke<RescanSummary>("library_rescan", { paths });
+
+export type SearchResults = {
+  total: number;
+  replays: LibraryReplay[];
+};
+
+// Query syntax: player:Flash vs:Jaedong mu:TvZ map:"Polypoid" winner:Flash
+// after:2025-01-01 before:2025-02-01 minlen:10m maxlen:30m chat:gg
+// sort:date|duration|map order:asc|desc. Bare words are player names. Names
+// also match the other names their account was seen using.
+export const searchLibrary = (query: string, offset: number, limit: number) =>
+  invoke<SearchResults>("library_search", { query, offset, limit });
+
+export type ChatHit = {
+  gameId: number;
+  mapName: string;
+  startTimeMs: number;
+  frame: number;
+  gameTimeMs: number;
+  sender: string;
+  message: string;
+};
+
+// `text` is FTS5 syntax: all words must appear, "gg wp" is a phrase,
+// gg OR wp matches either and noo* a prefix. Empty with a sender lists
+// everything that player said.
+export const searchChat = (
+  text: string,
+  sender: string | null,
+  offset: number,
+  limit: number,
+) =>
+  invoke<ChatHit[]>("library_search_chat", {
+    search: { text, sender },
+    offset,
+    limit,
+  });
+
+export type WinRecord = {
+  games: number;
+  wins: number;
+  losses: number;
+  winRate: number;
+};
+
+export type Breakdown = WinRecord & { key: string };
+
+export type RecentGame = {
+  gameId: number;
+  startTimeMs: number;
+  mapName: string;
+  matchup: string;
+  opponents: string[];
+  outcome: Outcome;
+};
+
+export type PlayerStats = {
+  overall: WinRecord;
+  byMatchup: Breakdown[];
+  byMap: Breakdown[];
+  openings: Breakdown[];
+  averageDurationMs: number;
+  averageApm: number;
+  recentForm: RecentGame[];
+};
+
+// `names` are the toons of one player; a game counts if any of them played.
+export const getPlayerStats = (
+  names: string[],
+  afterMs?: number,
+  beforeMs?: number,
+) =>
+  invoke<PlayerStats>("player_stats", {
+    filter: { names, afterMs, beforeMs },
+  });
+
+export type OpeningStep = {
+  item: string;
+  count: number;
+  minSupply?: number;
+  maxSupply?: number;
+  maxTimeMs?: number;
+};
+
+export type OpeningDefinition = {
+  name: string;
+  race: string;
+  steps: OpeningStep[];
+};
+
+export const getOpenings = () => invoke<OpeningDefinition[]>("get_openings");
+
+// Re-reads `openings.json` in the app data folder and reclassifies the library.
+export const reloadOpenings = () =>
+  invoke<OpeningDefinition[]>("reload_openings");
@@ -180,3 +180,33 @@ export const getSavedPlayersStore = async (): Promise<SavedPlayersStore> => {
   }
   return savedPlayersStore;
 };
+
+// A finished ladder game of a saved player's toon, found by the backend's
+// tracker and emitted on "saved-player-new-match".
+export type SavedPlayerMatch = {
+  auroraId: number;
+  alias: string;
+  toon: string;
+  gateway: number;
+  matchGuid: string;
+  matchCreatedMs: number;
+  map: string;
+  race: string;
+  opponent: string;
+  opponentGateway: number;
+  opponentRace: string;
+  outcome: "Win" | "Loss" | "Unknown";
+  points: number;
+};
+
+// Newest first; of every saved player when `auroraId` is omitted.
+export const getSavedPlayerActivity = (
+  offset: number,
+  limit: number,
+  auroraId?: number,
+) =>
+  invoke<SavedPlayerMatch[]>("saved_player_activity", {
+    auroraId,
+    offset,
+    limit,
+  });
@@ -1,3 +1,4 @@
+import { invoke } from "@tauri-apps/api/core";
 import { fetch as tauriFetch } from "@tauri-apps/plugin-http";
 import {
   type BroodWarApiPath,
@@ -7,18 +8,7 @@ import {
 import { GravaticBooster, SCApiWithCaching } from "gravatic-booster";
 import { LRUCache } from "lru-cache";
 
-import { getLimitsStore } from "@/lib/limits.svelte";
 import { getScrState } from "@/lib/scrState.svelte";
-import { getSettingsStore } from "@/lib/settingsStore.svelte";
-
-let limits = getLimitsStore();
-let nextRequestTime = 0;
-
-getSettingsStore().then((store) => {
-  store.onRateLimitChange(() => {
-    nextRequestTime = 0;
-  });
-});
 
 export const sleep = (ms: number) =>
   new Promise((resolve) => setTimeout(resolve, ms));
@@ -46,21 +36,10 @@ export class TauriConnection implements IBroodWarConnection {
     for (let i = 0; i < maxAttempts; i++) {
       await sleep(timeout * i);
 
-      const settingsStore = await getSettingsStore();
-      const tps = settingsStore.settings.maxApiRequestsTps;
-
-      if (tps > 0) {
-        const now = Date.now();
-        const interval = 1000 / tps;
-        const wait = Math.max(0, nextRequestTime - now);
-        nextRequestTime = Math.max(now, nextRequestTime) + interval;
-        if (wait > 0) {
-          await sleep(wait);
-        }
-      }
+      // Shares the per-host limits (and counters) of backend requests.
+      await invoke("acquire_request_slot", { url: key });
 
       try {
-        limits.numApiRequests++;
         response = await tauriFetch(key, {
           headers: {
             Accept: "application/json",
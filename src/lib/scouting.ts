@@ -0,0 +1,36 @@
+import { invoke } from "@tauri-apps/api/core";
+
+import type { Breakdown, Outcome, WinRecord } from "@/lib/replayLibrary";
+
+export type ScoutedGame = {
+  source: "Ladder" | "Library";
+  startTimeMs: number;
+  mapName: string;
+  race: string;
+  // From the scouted player's side, e.g. "ZvT".
+  matchup: string;
+  opponents: string[];
+  outcome: Outcome;
+  // Only known for games in the replay library.
+  durationMs: number | null;
+  opening: string | null;
+};
+
+export type ScoutingReport = {
+  toon: string;
+  gatewayId: number;
+  map: string;
+  preferredRace: string | null;
+  races: Breakdown[];
+  openingsByMatchup: { matchup: string; openings: Breakdown[] }[];
+  mapRecord: WinRecord;
+  averageDurationMs: number | null;
+  recentResults: ScoutedGame[];
+  ladderGames: number;
+  libraryGames: number;
+  ladderError: string | null;
+};
+
+// The backend emits this on "scouting-report" when a match starts.
+export const scoutOpponent = (toon: string, gateway: number, map: string) =>
+  invoke<ScoutingReport>("scout_opponent", { toon, gateway, map });
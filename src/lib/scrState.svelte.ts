@@ -2,35 +2,107 @@ import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import type { Event } from "@tauri-apps/api/event";
 
+import { type ScoutingReport, scoutOpponent } from "@/lib/scouting";
+
 export enum GameServerState {
   Running,
   NotRunning,
   Indeterminate, // haven't received tauri notification yet to say either way
 }
 
+export type ScrProcess = {
+  pid: number;
+  startedAt: number; // seconds since the Unix epoch
+  exePath: string | null;
+};
+
+// How the backend told the web API port apart from StarCraft's other listeners.
+export type PortVerification = {
+  probePath: string;
+  status: number;
+  candidates: number[];
+};
+
+// The account picked in settings, followed while the game runs. The web API
+// doesn't say who is signed in.
+export type CurrentAccount = {
+  auroraId: number;
+  battleTag: string;
+  toon: string;
+  gatewayId: number;
+};
+
+// A ladder game in progress; race and gateway are the opponent's.
+export type CurrentGame = {
+  opponent: string;
+  map: string;
+  race: string;
+  gatewayId: number;
+};
+
 export type ScrState = {
   gameServerState: GameServerState;
   port: number | null;
+  process: ScrProcess | null;
+  verification: PortVerification | null;
+  user: CurrentAccount | null;
+  currentGame: CurrentGame | null;
+  // On the opponent of currentGame, once the backend has put it together.
+  scoutingReport: ScoutingReport | null;
+};
+
+type ScrSnapshot = {
+  process: ScrProcess | null;
+  port: number | null;
+  verification: PortVerification | null;
+  polled: boolean;
+};
+
+type Session = {
+  user: CurrentAccount | null;
+  activeMatch: (CurrentGame & { matchGuid: string }) | null;
 };
 
 const scrState: ScrState = $state({
   gameServerState: GameServerState.Indeterminate,
   port: null,
+  process: null,
+  verification: null,
   user: null,
   currentGame: null,
+  scoutingReport: null,
 });
 
 export const getScrState = () => scrState;
 
 type BackendEvent =
+  | {
+      name: "ProcessStarted";
+      payload: ScrProcess;
+    }
+  | {
+      name: "ProcessExited";
+    }
   | {
       name: "WebServerRunning";
       payload: {
         port: number;
+        verification: PortVerification;
       };
     }
   | {
       name: "WebServerDown";
+    }
+  | {
+      name: "UserChanged";
+      payload: { user: CurrentAccount | null };
+    }
+  | {
+      name: "MatchStarted";
+      payload: CurrentGame;
+    }
+  | {
+      name: "MatchEnded";
     };
 
 // This function is used to convert the Rust event to a TypeScript event. The events are modeled
@@ -61,19 +133,74 @@ const convertBackendEvent = (ev: Event<object>): BackendEvent => {
 };
 
 export const configureReceiveBackendEvents = async () => {
-  // Inform the backend to start generating events.
-  await invoke("init_process");
-
-  // Listen for them and modify our exposed state object
-  return await listen("scr-event", (ev: Event<object>) => {
+  // Listen for events and modify our exposed state object. The backend only
+  // reports changes, so subscribe before it starts generating them.
+  const unlisten = await listen("scr-event", (ev: Event<object>) => {
     const event = convertBackendEvent(ev);
 
-    if ("WebServerDown" === event.name) {
+    if ("ProcessStarted" === event.name) {
+      scrState.process = event.payload;
+    } else if ("ProcessExited" === event.name) {
+      scrState.process = null;
+    } else if ("WebServerDown" === event.name) {
       scrState.port = null;
+      scrState.verification = null;
       scrState.gameServerState = GameServerState.NotRunning;
     } else if ("WebServerRunning" === event.name) {
       scrState.port = event.payload.port;
+      scrState.verification = event.payload.verification;
       scrState.gameServerState = GameServerState.Running;
+    } else if ("UserChanged" === event.name) {
+      scrState.user = event.payload.user;
+    } else if ("MatchStarted" === event.name) {
+      scrState.currentGame = event.payload;
+    } else if ("MatchEnded" === event.name) {
+      scrState.currentGame = null;
+      scrState.scoutingReport = null;
     }
   });
+  const unlistenScouting = await listen(
+    "scouting-report",
+    (ev: Event<ScoutingReport>) => {
+      scrState.scoutingReport = ev.payload;
+    },
+  );
+
+  // Inform the backend to start generating events.
+  await invoke("init_process");
+
+  // Pick up the current state when the backend was already running (e.g.
+  // after a frontend reload).
+  const snapshot = await invoke<ScrSnapshot>("get_scr_snapshot");
+  if (snapshot.polled) {
+    scrState.process = snapshot.process;
+    scrState.port = snapshot.port;
+    scrState.verification = snapshot.verification;
+    scrState.gameServerState = snapshot.port
+      ? GameServerState.Running
+      : GameServerState.NotRunning;
+  }
+
+  const session = await invoke<Session>("get_session");
+  scrState.user = session.user;
+  scrState.currentGame = session.activeMatch
+    ? {
+        opponent: session.activeMatch.opponent,
+        map: session.activeMatch.map,
+        race: session.activeMatch.race,
+        gatewayId: session.activeMatch.gatewayId,
+      }
+    : null;
+  // The report for a match already underway was emitted before we listened.
+  if (scrState.currentGame) {
+    const { opponent, gatewayId, map } = scrState.currentGame;
+    scoutOpponent(opponent, gatewayId, map)
+      .then((report) => (scrState.scoutingReport = report))
+      .catch((e) => console.error("Failed to scout opponent:", e));
+  }
+
+  return () => {
+    unlisten();
+    unlistenScouting();
+  };
 };
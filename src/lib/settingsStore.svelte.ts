@@ -5,8 +5,17 @@ import { toast } from "svelte-sonner";
 export interface AppSettings {
   replayDownloadPath: string;
   mapDownloadPath: string;
+  replayFilenameTemplate: string;
   hideShortReplays: boolean;
   maxApiRequestsTps: number;
+  maxReplayDownloadsTps: number;
+  replayWatchPath: string;
+  autoIngestReplays: boolean;
+  ladderSnapshotLeaderboardIds: number[];
+  ladderSnapshotTopN: number;
+  // The account whose ladder games are followed; empty follows nobody.
+  sessionToon: string;
+  sessionGateway: number;
 }
 
 export class SettingsStore {
@@ -15,15 +24,13 @@ export class SettingsStore {
   static create = async () => {
     const settings = await SettingsStore.loadSettings();
     const store = new SettingsStore(settings);
+    await store.applyRateLimits();
+    await store.applyReplayWatcher();
+    await store.applyLadderSnapshots();
+    await store.applySessionAccount();
     return store;
   };
 
-  private rateLimitListeners: ((tps: number) => void)[] = [];
-
-  onRateLimitChange(callback: (tps: number) => void) {
-    this.rateLimitListeners.push(callback);
-  }
-
   get settings(): AppSettings {
     return this._settings;
   }
@@ -65,8 +72,16 @@ export class SettingsStore {
     return {
       replayDownloadPath: `${home}\\StarCraft\\Maps\\Replays\\CWAL`,
       mapDownloadPath: `${home}\\StarCraft\\Maps\\CWAL`,
+      replayFilenameTemplate: "{date}_{time}_{p1}({r1})_vs_{p2}({r2})",
       hideShortReplays: true,
       maxApiRequestsTps: 10,
+      maxReplayDownloadsTps: 2,
+      replayWatchPath: `${home}\\StarCraft\\Maps\\Replays`,
+      autoIngestReplays: true,
+      ladderSnapshotLeaderboardIds: [],
+      ladderSnapshotTopN: 100,
+      sessionToon: "",
+      sessionGateway: 10,
     };
   };
 
@@ -74,6 +89,7 @@ export class SettingsStore {
     try {
       this._settings.replayDownloadPath = path;
       await this.saveSettings();
+      await this.applyReplayWatcher();
       toast.success("Replay download path updated");
     } catch (error) {
       console.error("Failed to update replay path:", error);
@@ -92,6 +108,42 @@ export class SettingsStore {
     }
   };
 
+  updateReplayFilenameTemplate = async (template: string) => {
+    try {
+      this._settings.replayFilenameTemplate = template;
+      await this.saveSettings();
+      await this.applyReplayWatcher();
+      toast.success("Replay filename template updated");
+    } catch (error) {
+      console.error("Failed to update replay filename template:", error);
+      toast.error("Failed to update replay filename template");
+    }
+  };
+
+  updateReplayWatchPath = async (path: string) => {
+    try {
+      this._settings.replayWatchPath = path;
+      await this.saveSettings();
+      await this.applyReplayWatcher();
+      toast.success("Replay watch folder updated");
+    } catch (error) {
+      console.error("Failed to update replay watch path:", error);
+      toast.error("Failed to update replay watch folder");
+    }
+  };
+
+  updateAutoIngestReplays = async (autoIngestReplays: boolean) => {
+    try {
+      this._settings.autoIngestReplays = autoIngestReplays;
+      await this.saveSettings();
+      await this.applyReplayWatcher();
+      toast.success("Replay auto-import preference updated");
+    } catch (error) {
+      console.error("Failed to update replay auto-import:", error);
+      toast.error("Failed to update replay auto-import preference");
+    }
+  };
+
   updateHideShortReplays = async (hideShortReplays: boolean) => {
     try {
       this._settings.hideShortReplays = hideShortReplays;
@@ -107,7 +159,7 @@ export class SettingsStore {
     try {
       this._settings.maxApiRequestsTps = value;
       await this.saveSettings();
-      this.rateLimitListeners.forEach((cb) => cb(value));
+      await this.applyRateLimits();
       toast.success("API rate preference updated");
     } catch (error) {
       console.error("Failed to update API TPS:", error);
@@ -115,10 +167,114 @@ export class SettingsStore {
     }
   };
 
+  updateMaxReplayDownloadsTps = async (value: number) => {
+    try {
+      this._settings.maxReplayDownloadsTps = value;
+      await this.saveSettings();
+      await this.applyRateLimits();
+      toast.success("Replay download rate preference updated");
+    } catch (error) {
+      console.error("Failed to update replay download TPS:", error);
+      toast.error("Failed to update replay download rate preference");
+    }
+  };
+
+  updateLadderSnapshots = async (leaderboardIds: number[], topN: number) => {
+    try {
+      this._settings.ladderSnapshotLeaderboardIds = leaderboardIds;
+      this._settings.ladderSnapshotTopN = topN;
+      await this.saveSettings();
+      await this.applyLadderSnapshots();
+      toast.success("Ladder snapshot preferences updated");
+    } catch (error) {
+      console.error("Failed to update ladder snapshots:", error);
+      toast.error("Failed to update ladder snapshot preferences");
+    }
+  };
+
+  updateSessionAccount = async (toon: string, gateway: number) => {
+    try {
+      this._settings.sessionToon = toon;
+      this._settings.sessionGateway = gateway;
+      await this.saveSettings();
+      await this.applySessionAccount();
+      toast.success("Account updated");
+    } catch (error) {
+      console.error("Failed to update account:", error);
+      toast.error("Failed to update account");
+    }
+  };
+
+  // The limits themselves are enforced by the backend for every request.
+  private applyRateLimits = async () => {
+    try {
+      await invoke("configure_rate_limits", {
+        config: {
+          localApiTps: this._settings.maxApiRequestsTps,
+          remoteTps: this._settings.maxReplayDownloadsTps,
+        },
+      });
+    } catch (error) {
+      console.error("Failed to apply rate limits:", error);
+    }
+  };
+
+  // New games in the watch folder are copied into the replay download path.
+  private applyReplayWatcher = async () => {
+    const { autoIngestReplays, replayWatchPath } = this._settings;
+    try {
+      await invoke("configure_replay_watcher", {
+        config: autoIngestReplays
+          ? {
+              watchPath: replayWatchPath,
+              destinationPath: this._settings.replayDownloadPath,
+              template: { pattern: this._settings.replayFilenameTemplate },
+            }
+          : null,
+      });
+    } catch (error) {
+      console.error("Failed to apply replay watcher:", error);
+    }
+  };
+
+  // Saved players' ratings are snapshotted along with the leaderboards, so
+  // snapshots stay on even without any leaderboards selected.
+  private applyLadderSnapshots = async () => {
+    try {
+      await invoke("configure_ladder_snapshots", {
+        config: {
+          leaderboardIds: this._settings.ladderSnapshotLeaderboardIds,
+          topN: this._settings.ladderSnapshotTopN,
+        },
+      });
+    } catch (error) {
+      console.error("Failed to apply ladder snapshots:", error);
+    }
+  };
+
+  // The web API doesn't say who is signed in, so the backend follows the
+  // account picked here for matches in progress.
+  private applySessionAccount = async () => {
+    const { sessionToon, sessionGateway } = this._settings;
+    try {
+      await invoke("configure_session_account", {
+        account: sessionToon
+          ? { toon: sessionToon, gateway: sessionGateway }
+          : null,
+      });
+    } catch (error) {
+      console.error("Failed to apply account:", error);
+    }
+  };
+
   resetToDefaults = async () => {
     const defaults = await SettingsStore.getDefaultSettings();
     this._settings = defaults;
     await this.saveSettings();
+    await this.applyRateLimits();
+    await this.applyReplayWatcher();
+    await this.applyLadderSnapshots();
+    await this.applySessionAccount();
     toast.success("Settings reset to defaults");
   };
 